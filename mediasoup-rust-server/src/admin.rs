@@ -0,0 +1,94 @@
+use crate::manager::MediasoupManager;
+use crate::room::{Peer, Room};
+use mediasoup::prelude::*;
+use serde::Serialize;
+
+/// Room as listed by the admin API. Built from the server's own state without
+/// asking the workers, so it stays cheap enough to poll.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomSummary {
+    pub room_id: String,
+    pub worker_index: usize,
+    pub routers: usize,
+    pub recording: bool,
+    pub teacher_id: Option<String>,
+    /// Student sharing their screen, if any
+    pub presenter_id: Option<String>,
+    /// Teacher first, then students by name
+    pub peers: Vec<PeerSummary>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerSummary {
+    pub peer_id: String,
+    pub name: String,
+    pub is_teacher: bool,
+    pub hand_raised: bool,
+    pub producers: Vec<ProducerSummary>,
+    pub consumers: usize,
+    pub data_producers: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProducerSummary {
+    pub producer_id: String,
+    pub kind: MediaKind,
+    pub paused: bool,
+}
+
+/// Every room, sorted by id
+pub fn rooms(manager: &MediasoupManager) -> Vec<RoomSummary> {
+    let mut rooms: Vec<RoomSummary> = manager.rooms().iter().map(|r| room_summary(r)).collect();
+    rooms.sort_by(|a, b| a.room_id.cmp(&b.room_id));
+    rooms
+}
+
+pub fn room_summary(room: &Room) -> RoomSummary {
+    let mut peers: Vec<PeerSummary> = room
+        .get_all_peers()
+        .iter()
+        .map(|p| peer_summary(p))
+        .collect();
+    peers.sort_by(|a, b| {
+        b.is_teacher
+            .cmp(&a.is_teacher)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    RoomSummary {
+        room_id: room.id.clone(),
+        worker_index: room.worker_index,
+        routers: room.router_count(),
+        recording: room.is_recording(),
+        teacher_id: room.get_teacher().map(|t| t.id.clone()),
+        presenter_id: room.presenter().map(|p| p.id.clone()),
+        peers,
+    }
+}
+
+fn peer_summary(peer: &Peer) -> PeerSummary {
+    let mut producers: Vec<ProducerSummary> = peer
+        .producers
+        .read()
+        .values()
+        .map(|producer| ProducerSummary {
+            producer_id: producer.id().to_string(),
+            kind: producer.kind(),
+            paused: producer.paused(),
+        })
+        .collect();
+    producers.sort_by(|a, b| a.producer_id.cmp(&b.producer_id));
+
+    PeerSummary {
+        peer_id: peer.id.clone(),
+        name: peer.name.clone(),
+        is_teacher: peer.is_teacher,
+        hand_raised: *peer.hand_raised.read(),
+        producers,
+        consumers: peer.consumers.read().len(),
+        data_producers: peer.data_producers.read().len(),
+    }
+}
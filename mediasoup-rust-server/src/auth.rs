@@ -0,0 +1,200 @@
+use crate::messages::JoinData;
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD},
+    Engine as _,
+};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::Deserialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Role granted by a join token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinRole {
+    Teacher,
+    Student,
+}
+
+/// Claims inside a join token.
+///
+/// Tokens are issued by the Tauri app with the teacher's Ed25519 key:
+/// `base64url(claims JSON) "." base64url(signature of the first part)`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinClaims {
+    pub room_id: String,
+    pub role: JoinRole,
+    /// Binds the token to one peer id when set
+    #[serde(default)]
+    pub peer_id: Option<String>,
+    /// Unix seconds
+    pub expires_at: u64,
+}
+
+/// Room access policy checked before a peer is added to a room
+#[derive(Default)]
+pub struct RoomAuth {
+    teacher_keys: Vec<VerifyingKey>,
+    password: Option<String>,
+}
+
+impl RoomAuth {
+    pub fn new(teacher_keys: Vec<VerifyingKey>, password: Option<String>) -> Self {
+        Self {
+            teacher_keys,
+            password: password.filter(|p| !p.is_empty()),
+        }
+    }
+
+    /// Replace the keys whose join tokens may grant the teacher role
+    pub fn set_teacher_keys(&mut self, keys: Vec<VerifyingKey>) {
+        self.teacher_keys = keys;
+    }
+
+    /// Student password for joining without a token; empty means none
+    pub fn set_password(&mut self, password: Option<String>) {
+        self.password = password.filter(|p| !p.is_empty());
+    }
+
+    /// Also trust the key pair the Tauri app keeps in `~/.smartlab`
+    pub fn add_local_teacher_key(&mut self) {
+        if let Some(key) = load_local_teacher_key() {
+            if !self.teacher_keys.contains(&key) {
+                self.teacher_keys.push(key);
+            }
+        }
+    }
+
+    pub fn teacher_key_count(&self) -> usize {
+        self.teacher_keys.len()
+    }
+
+    pub fn has_password(&self) -> bool {
+        self.password.is_some()
+    }
+
+    /// Check a token's signature against the teacher keys and its expiry
+    pub fn verify_token(&self, token: &str, now: u64) -> Result<JoinClaims, String> {
+        let (payload, signature) = token
+            .split_once('.')
+            .ok_or_else(|| "Malformed join token".to_string())?;
+        let signature: [u8; 64] = URL_SAFE_NO_PAD
+            .decode(signature)
+            .ok()
+            .and_then(|s| s.try_into().ok())
+            .ok_or_else(|| "Malformed join token".to_string())?;
+        let signature = Signature::from_bytes(&signature);
+
+        if !self
+            .teacher_keys
+            .iter()
+            .any(|key| key.verify(payload.as_bytes(), &signature).is_ok())
+        {
+            return Err("Invalid join token signature".to_string());
+        }
+
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| "Malformed join token".to_string())?;
+        let claims: JoinClaims =
+            serde_json::from_slice(&payload).map_err(|e| format!("Invalid join token: {}", e))?;
+        if claims.expires_at <= now {
+            return Err("Join token has expired".to_string());
+        }
+
+        Ok(claims)
+    }
+
+    /// Decide whether `join` may enter its room; returns whether the peer is the teacher.
+    ///
+    /// The teacher role only ever comes from a token. Students may use a token or,
+    /// when one is configured, the room password.
+    pub fn authorize(&self, join: &JoinData, now: u64) -> Result<bool, String> {
+        if let Some(token) = join.token.as_deref().filter(|t| !t.is_empty()) {
+            let claims = self.verify_token(token, now)?;
+            if claims.room_id != join.room_id {
+                return Err("Join token is for another room".to_string());
+            }
+            if claims
+                .peer_id
+                .as_ref()
+                .is_some_and(|id| id != &join.peer_id)
+            {
+                return Err("Join token is for another peer".to_string());
+            }
+            if join.is_teacher && claims.role != JoinRole::Teacher {
+                return Err("Join token does not grant the teacher role".to_string());
+            }
+            return Ok(claims.role == JoinRole::Teacher);
+        }
+
+        if join.is_teacher {
+            return Err("Joining as teacher requires a signed join token".to_string());
+        }
+
+        match &self.password {
+            Some(expected) => {
+                let given = join.password.as_deref().unwrap_or("");
+                if constant_time_eq(given.as_bytes(), expected.as_bytes()) {
+                    Ok(false)
+                } else {
+                    Err("Wrong room password".to_string())
+                }
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/// Parse a base64 Ed25519 public key, with or without the SMARTLAB PUBLIC KEY armor
+pub fn parse_public_key(text: &str) -> Result<VerifyingKey, String> {
+    let key: String = text
+        .replace("-----BEGIN SMARTLAB PUBLIC KEY-----", "")
+        .replace("-----END SMARTLAB PUBLIC KEY-----", "")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    let bytes: [u8; 32] = BASE64
+        .decode(&key)
+        .map_err(|e| format!("Invalid base64: {}", e))?
+        .try_into()
+        .map_err(|_| "Invalid public key length".to_string())?;
+
+    VerifyingKey::from_bytes(&bytes).map_err(|e| format!("Invalid public key: {}", e))
+}
+
+/// Parse a comma separated list of teacher public keys
+pub fn parse_teacher_keys(list: &str) -> Result<Vec<VerifyingKey>, String> {
+    list.split(',')
+        .filter(|key| !key.trim().is_empty())
+        .map(parse_public_key)
+        .collect()
+}
+
+/// Public half of the teacher key pair saved by the Tauri app on this machine
+fn load_local_teacher_key() -> Option<VerifyingKey> {
+    #[derive(Deserialize)]
+    struct KeyPairFile {
+        public_key: String,
+    }
+
+    let path = dirs::home_dir()?
+        .join(".smartlab")
+        .join("teacher_keypair.json");
+    let json = std::fs::read_to_string(path).ok()?;
+    let keypair: KeyPairFile = serde_json::from_str(&json).ok()?;
+    parse_public_key(&keypair.public_key).ok()
+}
+
+/// Compare secrets without leaking the position of the first difference
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
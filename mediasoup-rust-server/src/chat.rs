@@ -0,0 +1,96 @@
+use crate::messages::ChatMessageBroadcast;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Receives every chat message, e.g. to store it with the practice session
+pub trait ChatSink: Send + Sync {
+    fn store(&self, room_id: &str, message: &ChatMessageBroadcast);
+}
+
+/// Appends messages to a file as JSON lines: `{"roomId": ..., "message": {...}}`
+pub struct JsonLinesSink {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl JsonLinesSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl ChatSink for JsonLinesSink {
+    fn store(&self, room_id: &str, message: &ChatMessageBroadcast) {
+        let line = serde_json::json!({ "roomId": room_id, "message": message }).to_string();
+
+        let _guard = self.lock.lock();
+        let result = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(e) = result {
+            tracing::warn!("Failed to write chat log {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Last messages of a room, oldest first
+pub struct ChatHistory {
+    messages: VecDeque<ChatMessageBroadcast>,
+    capacity: usize,
+    next_id: u64,
+}
+
+impl ChatHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            messages: VecDeque::with_capacity(capacity.min(1024)),
+            capacity,
+            next_id: 1,
+        }
+    }
+
+    /// Id for the next message of this room
+    pub fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn push(&mut self, message: ChatMessageBroadcast) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+    }
+
+    /// Room messages plus the private ones `peer_id` sent or received
+    pub fn visible_to(&self, peer_id: &str) -> Vec<ChatMessageBroadcast> {
+        self.messages
+            .iter()
+            .filter(|m| match &m.receiver_id {
+                None => true,
+                Some(receiver) => receiver == peer_id || m.sender_id == peer_id,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Drop the private messages `peer_id` sent or received, once the peer is gone for
+    /// good; clients pick their own ids, so the next peer with that id must not see them
+    pub fn forget_private(&mut self, peer_id: &str) {
+        self.messages.retain(|m| match &m.receiver_id {
+            None => true,
+            Some(receiver) => receiver != peer_id && m.sender_id != peer_id,
+        });
+    }
+}
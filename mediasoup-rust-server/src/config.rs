@@ -1,13 +1,88 @@
+use crate::auth::{self, RoomAuth};
+use crate::chat::{ChatSink, JsonLinesSink};
+use crate::layers::LayerPolicy;
+use crate::recording::RecordingConfig;
 use mediasoup::prelude::*;
 use mediasoup::worker::{WorkerLogLevel, WorkerLogTag, WorkerSettings};
+use serde::Deserialize;
+use std::net::IpAddr;
 use std::num::{NonZeroU32, NonZeroU8};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+const USAGE: &str = "\
+Usage: mediasoup-rust-server [OPTIONS]
+
+Options:
+  --config <FILE>        TOML config file (default: ~/.smartlab/sfu.toml if it exists)
+  --port <PORT>          WebSocket signaling port [env: SMARTLAB_SFU_PORT]
+  --metrics-port <PORT>  HTTP port for /health, /stats and /metrics, 0 to disable
+                         [env: SMARTLAB_SFU_METRICS_PORT]
+  --admin-token <TOKEN>  Bearer token for /stats, /metrics and the /admin room API;
+                         the admin API is off without it [env: SMARTLAB_SFU_ADMIN_TOKEN]
+  --workers <N>          Number of mediasoup workers [env: SMARTLAB_SFU_WORKERS]
+  --max-clients <N>      Max clients per room [env: SMARTLAB_SFU_MAX_CLIENTS]
+  --peers-per-router <N> Students per router before a class spreads to another worker
+                         [env: SMARTLAB_SFU_PEERS_PER_ROUTER]
+  --max-bitrate <BPS>    Max incoming bitrate per transport [env: SMARTLAB_SFU_MAX_BITRATE]
+  --rtc-ports <MIN-MAX>  UDP port range for media [env: SMARTLAB_SFU_RTC_PORTS]
+  --webrtc-port <PORT>   Single UDP+TCP media port (one per worker, counting up);
+                         replaces the RTC port range [env: SMARTLAB_SFU_WEBRTC_PORT]
+  --ice-tcp <BOOL>       Offer TCP candidates as a fallback to UDP (default true)
+                         [env: SMARTLAB_SFU_ICE_TCP]
+  --announced-ip <IP>    IP given to clients in ICE candidates [env: SMARTLAB_ANNOUNCED_IP]
+  --reconnect-grace <S>  Seconds a dropped peer may take to reconnect, 0 to disable
+                         [env: SMARTLAB_SFU_RECONNECT_GRACE]
+  --auto-pause-silent <S>
+                         Pause a student's microphone after S silent seconds, 0 to disable
+                         [env: SMARTLAB_SFU_AUTO_PAUSE_SILENT]
+  --chat-history <N>     Chat messages kept per room for peers who join later
+                         [env: SMARTLAB_SFU_CHAT_HISTORY]
+  --chat-log <FILE>      Append every chat message to FILE as JSON lines
+                         [env: SMARTLAB_CHAT_LOG]
+  --room-password <PW>   Password students join with when they have no join token
+                         [env: SMARTLAB_ROOM_PASSWORD]
+  --teacher-keys <KEYS>  Comma separated public keys whose join tokens grant the teacher
+                         role, besides the key saved by the app [env: SMARTLAB_TEACHER_KEYS]
+  --recordings-dir <DIR> Where lesson recordings are saved [env: SMARTLAB_RECORDINGS_DIR]
+  --ffmpeg <FILE>        ffmpeg binary for Matroska recordings (default: ffmpeg on the PATH)
+                         [env: SMARTLAB_FFMPEG]
+  -h, --help             Print this help
+
+Command-line flags override environment variables, which override the config file.";
 
 /// Server configuration
 pub struct Config {
     pub listen_port: u16,
+    /// HTTP port for health, stats and Prometheus metrics
+    pub metrics_port: Option<u16>,
+    /// Token the admin API requires; the API is off when unset
+    pub admin_token: Option<String>,
     pub num_workers: usize,
     pub max_clients_per_room: usize,
+    /// Peers on one router; larger classes get routers on other workers
+    pub peers_per_router: usize,
     pub max_incoming_bitrate: u32,
+    /// UDP ports for RTP/RTCP, opened in the school firewall
+    pub rtc_port_range: RangeInclusive<u16>,
+    /// First port of the per-worker WebRtcServers; all media on one port per worker
+    pub webrtc_port: Option<u16>,
+    /// Advertise TCP candidates besides UDP for networks that block UDP
+    pub ice_tcp: bool,
+    /// IP clients connect to for media; detected when unset
+    pub announced_ip: Option<String>,
+    /// How long a dropped peer keeps its media while it reconnects
+    pub reconnect_grace: Duration,
+    /// Pause student microphones that stay silent this long
+    pub auto_pause_silent: Option<Duration>,
+    pub chat_history_size: usize,
+    /// Where chat messages are persisted, if anywhere
+    pub chat_sink: Option<Arc<dyn ChatSink>>,
+    pub room_auth: RoomAuth,
+    pub layer_policy: LayerPolicy,
+    pub recording: RecordingConfig,
 }
 
 impl Default for Config {
@@ -15,17 +90,504 @@ impl Default for Config {
         let num_cpus = num_cpus::get().min(3);
         Self {
             listen_port: 3016,
+            metrics_port: Some(3026),
+            admin_token: None,
             num_workers: num_cpus,
             max_clients_per_room: 50,
+            peers_per_router: 50,
             max_incoming_bitrate: 6_000_000, // 6 Mbps
+            rtc_port_range: 40000..=45000,
+            webrtc_port: None,
+            ice_tcp: true,
+            announced_ip: None,
+            reconnect_grace: Duration::from_secs(30),
+            auto_pause_silent: None,
+            chat_history_size: 200,
+            chat_sink: None,
+            room_auth: RoomAuth::default(),
+            layer_policy: LayerPolicy::default(),
+            recording: RecordingConfig::default(),
+        }
+    }
+}
+
+/// Settings a TOML config file may contain; anything left out keeps its default
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    listen_port: Option<u16>,
+    /// 0 disables the HTTP endpoint
+    metrics_port: Option<u16>,
+    /// Empty disables the admin API
+    admin_token: Option<String>,
+    num_workers: Option<usize>,
+    max_clients_per_room: Option<usize>,
+    peers_per_router: Option<usize>,
+    max_incoming_bitrate: Option<u32>,
+    /// "40000-45000"
+    rtc_ports: Option<String>,
+    /// 0 keeps the port range
+    webrtc_port: Option<u16>,
+    ice_tcp: Option<bool>,
+    announced_ip: Option<String>,
+    reconnect_grace_secs: Option<u64>,
+    auto_pause_silent_secs: Option<u64>,
+    chat_history_size: Option<usize>,
+    chat_log: Option<PathBuf>,
+    room_password: Option<String>,
+    /// Base64 public keys
+    teacher_keys: Option<Vec<String>>,
+    recordings_dir: Option<PathBuf>,
+    ffmpeg: Option<PathBuf>,
+}
+
+/// Parsed command line
+#[derive(Debug, Default)]
+struct CliArgs {
+    config: Option<PathBuf>,
+    port: Option<String>,
+    metrics_port: Option<String>,
+    admin_token: Option<String>,
+    workers: Option<String>,
+    max_clients: Option<String>,
+    peers_per_router: Option<String>,
+    max_bitrate: Option<String>,
+    rtc_ports: Option<String>,
+    webrtc_port: Option<String>,
+    ice_tcp: Option<String>,
+    announced_ip: Option<String>,
+    reconnect_grace: Option<String>,
+    auto_pause_silent: Option<String>,
+    chat_history: Option<String>,
+    chat_log: Option<String>,
+    room_password: Option<String>,
+    teacher_keys: Option<String>,
+    recordings_dir: Option<String>,
+    ffmpeg: Option<String>,
+}
+
+impl Config {
+    /// Build the configuration from defaults, the config file, environment and `args`
+    /// (without the program name). `Ok(None)` means `--help` was printed.
+    pub fn load(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        Self::load_with_env(args, |name| std::env::var(name).ok())
+    }
+
+    /// `load` with environment variables looked up through `env`
+    fn load_with_env(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Option<Self>, String> {
+        let Some(cli) = parse_args(args)? else {
+            println!("{}", USAGE);
+            return Ok(None);
+        };
+
+        let mut config = Config::default();
+
+        let config_path = cli
+            .config
+            .clone()
+            .or_else(|| env("SMARTLAB_SFU_CONFIG").map(PathBuf::from));
+        match config_path {
+            Some(path) => config.apply_file(&path)?,
+            None => {
+                let default_path = dirs::home_dir()
+                    .map(|home| home.join(".smartlab").join("sfu.toml"))
+                    .filter(|path| path.exists());
+                if let Some(path) = default_path {
+                    config.apply_file(&path)?;
+                }
+            }
+        }
+
+        let overrides = [
+            ("port", cli.port, "SMARTLAB_SFU_PORT"),
+            (
+                "metrics-port",
+                cli.metrics_port,
+                "SMARTLAB_SFU_METRICS_PORT",
+            ),
+            ("admin-token", cli.admin_token, "SMARTLAB_SFU_ADMIN_TOKEN"),
+            ("workers", cli.workers, "SMARTLAB_SFU_WORKERS"),
+            ("max-clients", cli.max_clients, "SMARTLAB_SFU_MAX_CLIENTS"),
+            (
+                "peers-per-router",
+                cli.peers_per_router,
+                "SMARTLAB_SFU_PEERS_PER_ROUTER",
+            ),
+            ("max-bitrate", cli.max_bitrate, "SMARTLAB_SFU_MAX_BITRATE"),
+            ("rtc-ports", cli.rtc_ports, "SMARTLAB_SFU_RTC_PORTS"),
+            ("webrtc-port", cli.webrtc_port, "SMARTLAB_SFU_WEBRTC_PORT"),
+            ("ice-tcp", cli.ice_tcp, "SMARTLAB_SFU_ICE_TCP"),
+            ("announced-ip", cli.announced_ip, "SMARTLAB_ANNOUNCED_IP"),
+            (
+                "reconnect-grace",
+                cli.reconnect_grace,
+                "SMARTLAB_SFU_RECONNECT_GRACE",
+            ),
+            (
+                "auto-pause-silent",
+                cli.auto_pause_silent,
+                "SMARTLAB_SFU_AUTO_PAUSE_SILENT",
+            ),
+            (
+                "chat-history",
+                cli.chat_history,
+                "SMARTLAB_SFU_CHAT_HISTORY",
+            ),
+            ("chat-log", cli.chat_log, "SMARTLAB_CHAT_LOG"),
+            ("room-password", cli.room_password, "SMARTLAB_ROOM_PASSWORD"),
+            ("teacher-keys", cli.teacher_keys, "SMARTLAB_TEACHER_KEYS"),
+            (
+                "recordings-dir",
+                cli.recordings_dir,
+                "SMARTLAB_RECORDINGS_DIR",
+            ),
+            ("ffmpeg", cli.ffmpeg, "SMARTLAB_FFMPEG"),
+        ];
+        for (name, from_cli, env_name) in overrides {
+            // Flags win over the environment
+            let value = match from_cli {
+                Some(value) => Some((value, format!("--{}", name))),
+                None => env(env_name)
+                    .filter(|v| !v.trim().is_empty())
+                    .map(|value| (value, env_name.to_string())),
+            };
+            if let Some((value, source)) = value {
+                config.set(name, &value, &source)?;
+            }
+        }
+
+        config.room_auth.add_local_teacher_key();
+        config.recording.detect_ffmpeg()?;
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    fn apply_file(&mut self, path: &Path) -> Result<(), String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read config file {}: {}", path.display(), e))?;
+        let file: FileConfig = toml::from_str(&text)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+
+        if let Some(port) = file.listen_port {
+            self.listen_port = port;
+        }
+        if let Some(port) = file.metrics_port {
+            self.metrics_port = (port > 0).then_some(port);
+        }
+        if let Some(token) = file.admin_token {
+            self.admin_token = admin_token(&token);
+        }
+        if let Some(workers) = file.num_workers {
+            self.num_workers = workers;
+        }
+        if let Some(max_clients) = file.max_clients_per_room {
+            self.max_clients_per_room = max_clients;
+        }
+        if let Some(peers) = file.peers_per_router {
+            self.peers_per_router = peers;
+        }
+        if let Some(bitrate) = file.max_incoming_bitrate {
+            self.max_incoming_bitrate = bitrate;
+        }
+        if let Some(ports) = file.rtc_ports {
+            self.rtc_port_range = parse_port_range(&ports)
+                .map_err(|e| format!("Invalid rtc_ports in {}: {}", path.display(), e))?;
+        }
+        if let Some(port) = file.webrtc_port {
+            self.webrtc_port = (port > 0).then_some(port);
+        }
+        if let Some(ice_tcp) = file.ice_tcp {
+            self.ice_tcp = ice_tcp;
         }
+        if let Some(ip) = file.announced_ip {
+            self.announced_ip = Some(ip);
+        }
+        if let Some(secs) = file.reconnect_grace_secs {
+            self.reconnect_grace = Duration::from_secs(secs);
+        }
+        if let Some(secs) = file.auto_pause_silent_secs {
+            self.auto_pause_silent = silence_timeout(secs);
+        }
+        if let Some(size) = file.chat_history_size {
+            self.chat_history_size = size;
+        }
+        if let Some(path) = file.chat_log {
+            self.chat_sink = Some(Arc::new(JsonLinesSink::new(path)));
+        }
+        if let Some(password) = file.room_password {
+            self.room_auth.set_password(Some(password));
+        }
+        if let Some(keys) = file.teacher_keys {
+            let keys = keys
+                .iter()
+                .map(|key| auth::parse_public_key(key))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("Invalid teacher_keys in {}: {}", path.display(), e))?;
+            self.room_auth.set_teacher_keys(keys);
+        }
+        if let Some(dir) = file.recordings_dir {
+            self.recording.dir = dir;
+        }
+        if let Some(ffmpeg) = file.ffmpeg {
+            self.recording.ffmpeg = Some(ffmpeg);
+        }
+
+        tracing::info!("Loaded config from {}", path.display());
+        Ok(())
+    }
+
+    /// Set one option from its flag name and text value; `source` names it in errors
+    fn set(&mut self, name: &str, value: &str, source: &str) -> Result<(), String> {
+        let value = value.trim();
+        let invalid = |e: &dyn std::fmt::Display| format!("Invalid {} '{}': {}", source, value, e);
+        match name {
+            "port" => self.listen_port = value.parse().map_err(|e| invalid(&e))?,
+            "metrics-port" => {
+                let port: u16 = value.parse().map_err(|e| invalid(&e))?;
+                self.metrics_port = (port > 0).then_some(port);
+            }
+            "admin-token" => self.admin_token = admin_token(value),
+            "workers" => self.num_workers = value.parse().map_err(|e| invalid(&e))?,
+            "max-clients" => self.max_clients_per_room = value.parse().map_err(|e| invalid(&e))?,
+            "peers-per-router" => self.peers_per_router = value.parse().map_err(|e| invalid(&e))?,
+            "max-bitrate" => self.max_incoming_bitrate = value.parse().map_err(|e| invalid(&e))?,
+            "rtc-ports" => {
+                self.rtc_port_range = parse_port_range(value).map_err(|e| invalid(&e))?
+            }
+            "webrtc-port" => {
+                let port: u16 = value.parse().map_err(|e| invalid(&e))?;
+                self.webrtc_port = (port > 0).then_some(port);
+            }
+            "ice-tcp" => self.ice_tcp = parse_bool(value).map_err(|e| invalid(&e))?,
+            "announced-ip" => self.announced_ip = Some(value.to_string()),
+            "reconnect-grace" => {
+                self.reconnect_grace = Duration::from_secs(value.parse().map_err(|e| invalid(&e))?)
+            }
+            "auto-pause-silent" => {
+                self.auto_pause_silent = silence_timeout(value.parse().map_err(|e| invalid(&e))?)
+            }
+            "chat-history" => self.chat_history_size = value.parse().map_err(|e| invalid(&e))?,
+            "chat-log" => self.chat_sink = Some(Arc::new(JsonLinesSink::new(PathBuf::from(value)))),
+            "room-password" => self.room_auth.set_password(Some(value.to_string())),
+            "teacher-keys" => self
+                .room_auth
+                .set_teacher_keys(auth::parse_teacher_keys(value).map_err(|e| invalid(&e))?),
+            "recordings-dir" => self.recording.dir = PathBuf::from(value),
+            "ffmpeg" => self.recording.ffmpeg = Some(PathBuf::from(value)),
+            _ => return Err(format!("Unknown option --{}", name)),
+        }
+        Ok(())
+    }
+
+    /// Reject settings that would only fail later, with a message saying what to fix
+    fn validate(&self) -> Result<(), String> {
+        if self.listen_port == 0 {
+            return Err("Port must be between 1 and 65535".to_string());
+        }
+        if self.num_workers == 0 {
+            return Err("At least one worker is required".to_string());
+        }
+        if self.max_clients_per_room == 0 {
+            return Err("Max clients per room must be at least 1".to_string());
+        }
+        if self.peers_per_router == 0 {
+            return Err("Peers per router must be at least 1".to_string());
+        }
+        if self.max_incoming_bitrate < 100_000 {
+            return Err(format!(
+                "Max bitrate {} bps is too low for screen sharing (minimum 100000)",
+                self.max_incoming_bitrate
+            ));
+        }
+        if self.rtc_port_range.contains(&self.listen_port) {
+            return Err(format!(
+                "Signaling port {} is inside the RTC port range {}-{}",
+                self.listen_port,
+                self.rtc_port_range.start(),
+                self.rtc_port_range.end()
+            ));
+        }
+        if let Some(first) = self.webrtc_port {
+            // One WebRtcServer per worker, on consecutive ports
+            let last = first as usize + self.num_workers - 1;
+            if last > u16::MAX as usize {
+                return Err(format!(
+                    "WebRTC ports {}-{} for {} workers go past 65535",
+                    first, last, self.num_workers
+                ));
+            }
+            let ports = first..=last as u16;
+            let taken = [Some(self.listen_port), self.metrics_port];
+            if taken.iter().flatten().any(|port| ports.contains(port)) {
+                return Err(format!(
+                    "WebRTC ports {}-{} clash with the signaling or metrics port",
+                    first, last
+                ));
+            }
+        }
+        if let Some(port) = self.metrics_port {
+            if port == self.listen_port || self.rtc_port_range.contains(&port) {
+                return Err(format!(
+                    "Metrics port {} clashes with the signaling port or the RTC port range",
+                    port
+                ));
+            }
+        }
+        if let Some(token) = &self.admin_token {
+            if self.metrics_port.is_none() {
+                return Err("The admin API needs the metrics port; set metrics_port".to_string());
+            }
+            if token.len() < 16 {
+                return Err(
+                    "Admin token is too short to protect the admin API (minimum 16 characters)"
+                        .to_string(),
+                );
+            }
+        }
+        if self.chat_history_size > 10_000 {
+            return Err(format!(
+                "Chat history of {} messages per room is too large (maximum 10000)",
+                self.chat_history_size
+            ));
+        }
+        if self.reconnect_grace > Duration::from_secs(600) {
+            return Err(format!(
+                "Reconnect grace of {}s is too long; peers would hold their media for over 10 minutes",
+                self.reconnect_grace.as_secs()
+            ));
+        }
+        if let Some(after) = self.auto_pause_silent {
+            if after < Duration::from_secs(10) {
+                return Err(format!(
+                    "Auto-pause after {}s of silence would cut students off between sentences (minimum 10)",
+                    after.as_secs()
+                ));
+            }
+        }
+        if let Some(ip) = &self.announced_ip {
+            match ip.parse::<IpAddr>() {
+                Ok(addr) if addr.is_unspecified() => {
+                    return Err(format!(
+                        "Announced IP {} is not reachable; use this machine's LAN address",
+                        ip
+                    ));
+                }
+                Ok(_) => {}
+                Err(_) => return Err(format!("Announced IP '{}' is not an IP address", ip)),
+            }
+        }
+        Ok(())
+    }
+
+    /// IP put in ICE candidates: the configured one, or the detected LAN address
+    pub fn announced_ip(&self) -> String {
+        self.announced_ip.clone().unwrap_or_else(get_local_ip)
     }
 }
 
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+/// Empty turns the admin API off
+fn admin_token(value: &str) -> Option<String> {
+    let token = value.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+/// 0 turns auto-pause off
+fn silence_timeout(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<CliArgs>, String> {
+    let mut cli = CliArgs::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        // Accept both "--port 3016" and "--port=3016"
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+        if flag == "-h" || flag == "--help" {
+            return Ok(None);
+        }
+
+        let slot = match flag.as_str() {
+            "--port" => &mut cli.port,
+            "--metrics-port" => &mut cli.metrics_port,
+            "--admin-token" => &mut cli.admin_token,
+            "--workers" => &mut cli.workers,
+            "--max-clients" => &mut cli.max_clients,
+            "--peers-per-router" => &mut cli.peers_per_router,
+            "--max-bitrate" => &mut cli.max_bitrate,
+            "--rtc-ports" => &mut cli.rtc_ports,
+            "--webrtc-port" => &mut cli.webrtc_port,
+            "--ice-tcp" => &mut cli.ice_tcp,
+            "--announced-ip" => &mut cli.announced_ip,
+            "--reconnect-grace" => &mut cli.reconnect_grace,
+            "--auto-pause-silent" => &mut cli.auto_pause_silent,
+            "--chat-history" => &mut cli.chat_history,
+            "--chat-log" => &mut cli.chat_log,
+            "--room-password" => &mut cli.room_password,
+            "--teacher-keys" => &mut cli.teacher_keys,
+            "--recordings-dir" => &mut cli.recordings_dir,
+            "--ffmpeg" => &mut cli.ffmpeg,
+            "--config" => {
+                let value = inline_value
+                    .or_else(|| args.next())
+                    .ok_or("Missing value for --config")?;
+                cli.config = Some(PathBuf::from(value));
+                continue;
+            }
+            _ => return Err(format!("Unknown argument '{}'\n\n{}", arg, USAGE)),
+        };
+        *slot = Some(
+            inline_value
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("Missing value for {}", flag))?,
+        );
+    }
+
+    Ok(Some(cli))
+}
+
+/// Parse "40000-45000"
+fn parse_port_range(text: &str) -> Result<RangeInclusive<u16>, String> {
+    let (min, max) = text
+        .split_once('-')
+        .ok_or_else(|| "expected MIN-MAX, e.g. 40000-45000".to_string())?;
+    let min: u16 = min
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' is not a port", min.trim()))?;
+    let max: u16 = max
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' is not a port", max.trim()))?;
+
+    if min < 1024 {
+        return Err("ports below 1024 need administrator rights".to_string());
+    }
+    // Every transport takes a port; one is not enough for a class
+    if max <= min {
+        return Err(format!("range {}-{} is empty or backwards", min, max));
+    }
+    Ok(min..=max)
+}
+
 /// Create worker settings
-pub fn worker_settings() -> WorkerSettings {
+pub fn worker_settings(config: &Config) -> WorkerSettings {
     let mut settings = WorkerSettings::default();
-    settings.rtc_port_range = 40000..=45000;
+    settings.rtc_port_range = config.rtc_port_range.clone();
     settings.log_level = WorkerLogLevel::Warn;
     settings.log_tags = vec![
         WorkerLogTag::Ice,
@@ -84,23 +646,61 @@ fn media_codecs() -> Vec<RtpCodecCapability> {
     ]
 }
 
-/// Create WebRTC transport options
-pub fn webrtc_transport_options(announced_ip: String) -> WebRtcTransportOptions {
-    let listen_info = ListenInfo {
-        protocol: Protocol::Udp,
+/// Media listen address on all interfaces, announced to clients as `announced_ip`
+fn listen_info(protocol: Protocol, announced_ip: &str, port: Option<u16>) -> ListenInfo {
+    ListenInfo {
+        protocol,
         ip: "0.0.0.0".parse().unwrap(),
-        announced_address: Some(announced_ip),
-        port: None,
+        announced_address: Some(announced_ip.to_string()),
+        port,
         port_range: None,
         flags: None,
         send_buffer_size: None,
         recv_buffer_size: None,
         expose_internal_ip: false,
-    };
+    }
+}
 
-    let listen_infos = WebRtcTransportListenInfos::new(listen_info);
+/// WebRtcServer for one worker: UDP, and TCP when enabled, on the same port
+pub fn webrtc_server_options(
+    config: &Config,
+    announced_ip: &str,
+    port: u16,
+) -> WebRtcServerOptions {
+    let mut listen_infos =
+        WebRtcServerListenInfos::new(listen_info(Protocol::Udp, announced_ip, Some(port)));
+    if config.ice_tcp {
+        listen_infos = listen_infos.insert(listen_info(Protocol::Tcp, announced_ip, Some(port)));
+    }
+    WebRtcServerOptions::new(listen_infos)
+}
 
-    let mut options = WebRtcTransportOptions::new(listen_infos);
+/// Create WebRTC transport options: on the worker's WebRtcServer when there is one,
+/// otherwise on its own port from the RTC range. UDP is preferred, TCP is the fallback.
+pub fn webrtc_transport_options(
+    announced_ip: String,
+    webrtc_server: Option<WebRtcServer>,
+    ice_tcp: bool,
+) -> WebRtcTransportOptions {
+    let mut options = match webrtc_server {
+        Some(server) => {
+            let mut options = WebRtcTransportOptions::new_with_server(server);
+            options.enable_udp = true;
+            options.enable_tcp = ice_tcp;
+            options
+        }
+        None => {
+            let mut listen_infos =
+                WebRtcTransportListenInfos::new(listen_info(Protocol::Udp, &announced_ip, None));
+            if ice_tcp {
+                listen_infos = listen_infos.insert(listen_info(Protocol::Tcp, &announced_ip, None));
+            }
+            WebRtcTransportOptions::new(listen_infos)
+        }
+    };
+    options.prefer_udp = true;
+    // SCTP carries the teacher's data channels (pointer, whiteboard strokes, polls)
+    options.enable_sctp = true;
     options.initial_available_outgoing_bitrate = 6_000_000;
     options
 }
@@ -111,3 +711,195 @@ pub fn get_local_ip() -> String {
         .map(|ip| ip.to_string())
         .unwrap_or_else(|_| "127.0.0.1".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Load `args` with `file` as the config file and only `env` set
+    fn load(args: &[&str], env: &[(&str, &str)], file: &str) -> Result<Config, String> {
+        let path = std::env::temp_dir().join(format!("sfu-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, file).unwrap();
+
+        let mut all = vec!["--config".to_string(), path.display().to_string()];
+        all.extend(args.iter().map(|arg| arg.to_string()));
+        let result = Config::load_with_env(all, |name| {
+            env.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        });
+
+        let _ = std::fs::remove_file(&path);
+        result.map(|config| config.expect("help was not asked for"))
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn flags_beat_env_beat_file() {
+        let config = load(
+            &["--port", "6000"],
+            &[("SMARTLAB_SFU_PORT", "5000"), ("SMARTLAB_SFU_WORKERS", "3")],
+            "listen_port = 4000\nnum_workers = 2\nmax_clients_per_room = 20\n",
+        )
+        .unwrap();
+        assert_eq!(config.listen_port, 6000);
+        assert_eq!(config.num_workers, 3);
+        assert_eq!(config.max_clients_per_room, 20);
+    }
+
+    #[test]
+    fn empty_env_var_is_ignored() {
+        let config = load(&[], &[("SMARTLAB_SFU_PORT", " ")], "listen_port = 4000\n").unwrap();
+        assert_eq!(config.listen_port, 4000);
+    }
+
+    #[test]
+    fn accepts_both_flag_forms() {
+        let cli = parse_args(args(&["--port=4000", "--workers", "2"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(cli.port.as_deref(), Some("4000"));
+        assert_eq!(cli.workers.as_deref(), Some("2"));
+
+        let cli = parse_args(args(&["--config=/etc/sfu.toml"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(cli.config, Some(PathBuf::from("/etc/sfu.toml")));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse_args(args(&["--help"])).unwrap().is_none());
+        assert_eq!(
+            parse_args(args(&["--port"])).unwrap_err(),
+            "Missing value for --port"
+        );
+        assert!(parse_args(args(&["--prot", "4000"]))
+            .unwrap_err()
+            .starts_with("Unknown argument '--prot'"));
+    }
+
+    #[test]
+    fn names_the_source_of_a_bad_value() {
+        assert_eq!(
+            load(&["--workers", "many"], &[], "").unwrap_err(),
+            "Invalid --workers 'many': invalid digit found in string"
+        );
+        assert!(load(&[], &[("SMARTLAB_SFU_ICE_TCP", "maybe")], "")
+            .unwrap_err()
+            .starts_with("Invalid SMARTLAB_SFU_ICE_TCP 'maybe'"));
+        assert!(load(&[], &[], "listen_prot = 4000\n")
+            .unwrap_err()
+            .starts_with("Invalid config file"));
+    }
+
+    #[test]
+    fn parses_port_ranges() {
+        assert_eq!(parse_port_range("40000-45000"), Ok(40000..=45000));
+        assert_eq!(parse_port_range(" 40000 - 45000 "), Ok(40000..=45000));
+        assert_eq!(
+            parse_port_range("40000").unwrap_err(),
+            "expected MIN-MAX, e.g. 40000-45000"
+        );
+        assert_eq!(
+            parse_port_range("40000-x").unwrap_err(),
+            "'x' is not a port"
+        );
+        assert_eq!(
+            parse_port_range("45000-40000").unwrap_err(),
+            "range 45000-40000 is empty or backwards"
+        );
+        assert_eq!(
+            parse_port_range("40000-40000").unwrap_err(),
+            "range 40000-40000 is empty or backwards"
+        );
+        assert_eq!(
+            parse_port_range("80-2000").unwrap_err(),
+            "ports below 1024 need administrator rights"
+        );
+        assert!(load(&[], &[], "rtc_ports = \"45000-40000\"\n")
+            .unwrap_err()
+            .starts_with("Invalid rtc_ports in"));
+    }
+
+    #[test]
+    fn rejects_port_clashes() {
+        assert_eq!(
+            load(&["--port", "40500"], &[], "").unwrap_err(),
+            "Signaling port 40500 is inside the RTC port range 40000-45000"
+        );
+        assert_eq!(
+            load(&["--metrics-port", "3016"], &[], "").unwrap_err(),
+            "Metrics port 3016 clashes with the signaling port or the RTC port range"
+        );
+        assert_eq!(
+            load(&["--webrtc-port", "3025", "--workers", "2"], &[], "").unwrap_err(),
+            "WebRTC ports 3025-3026 clash with the signaling or metrics port"
+        );
+        assert_eq!(
+            load(&["--webrtc-port", "65535", "--workers", "2"], &[], "").unwrap_err(),
+            "WebRTC ports 65535-65536 for 2 workers go past 65535"
+        );
+
+        // Without the metrics endpoint its port is free
+        let config = load(&["--metrics-port", "0", "--webrtc-port", "3026"], &[], "").unwrap();
+        assert_eq!(config.metrics_port, None);
+        assert_eq!(config.webrtc_port, Some(3026));
+    }
+
+    #[test]
+    fn admin_token_rules() {
+        let token = "0123456789abcdef";
+        let config = load(&["--admin-token", token], &[], "").unwrap();
+        assert_eq!(config.admin_token.as_deref(), Some(token));
+
+        assert_eq!(
+            load(&["--admin-token", "short"], &[], "").unwrap_err(),
+            "Admin token is too short to protect the admin API (minimum 16 characters)"
+        );
+        assert_eq!(
+            load(&["--admin-token", token, "--metrics-port", "0"], &[], "").unwrap_err(),
+            "The admin API needs the metrics port; set metrics_port"
+        );
+
+        // Empty turns the API off, from a flag or from the file
+        let config = load(&["--admin-token="], &[], "admin_token = \"short\"\n").unwrap();
+        assert_eq!(config.admin_token, None);
+        let config = load(&[], &[], "admin_token = \"\"\n").unwrap();
+        assert_eq!(config.admin_token, None);
+    }
+
+    #[test]
+    fn room_access_and_recording_settings() {
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]).verifying_key();
+        let key = STANDARD.encode(key.as_bytes());
+
+        let file = format!(
+            "room_password = \"lop10a1\"\nteacher_keys = [\"{}\"]\nrecordings_dir = \"/srv/rec\"\n",
+            key
+        );
+        let config = load(&[], &[("SMARTLAB_RECORDINGS_DIR", "/data/rec")], &file).unwrap();
+        assert!(config.room_auth.has_password());
+        assert!(config.room_auth.teacher_key_count() >= 1);
+        assert_eq!(config.recording.dir, PathBuf::from("/data/rec"));
+
+        // A bad key stops the server instead of being skipped, unless a flag overrides it
+        let bad = [("SMARTLAB_TEACHER_KEYS", "not-a-key")];
+        assert!(load(&[], &bad, "")
+            .unwrap_err()
+            .starts_with("Invalid SMARTLAB_TEACHER_KEYS 'not-a-key'"));
+        assert!(load(&["--teacher-keys", &key], &bad, "").is_ok());
+        assert!(load(&[], &[], "teacher_keys = [\"AAAA\"]\n")
+            .unwrap_err()
+            .starts_with("Invalid teacher_keys in"));
+
+        assert_eq!(
+            load(&["--ffmpeg", "/nonexistent/ffmpeg"], &[], "").unwrap_err(),
+            "ffmpeg at /nonexistent/ffmpeg does not run"
+        );
+    }
+}
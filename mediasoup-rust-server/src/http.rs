@@ -0,0 +1,185 @@
+use crate::admin;
+use crate::auth;
+use crate::manager::MediasoupManager;
+use crate::signaling::RoomControl;
+use crate::stats;
+use axum::extract::{FromRef, Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Clone)]
+struct AppState {
+    manager: Arc<MediasoupManager>,
+    rooms: RoomControl,
+}
+
+impl FromRef<AppState> for Arc<MediasoupManager> {
+    fn from_ref(state: &AppState) -> Self {
+        state.manager.clone()
+    }
+}
+
+/// Serve health, stats and Prometheus metrics over HTTP, plus the admin API when
+/// an admin token is configured. Stats name rooms and peers, so they need the admin
+/// token too once one is set.
+pub async fn serve(
+    manager: Arc<MediasoupManager>,
+    rooms: RoomControl,
+    port: u16,
+) -> Result<(), BoxError> {
+    let state = AppState { manager, rooms };
+
+    let admin = Router::new()
+        .route("/rooms", get(list_rooms))
+        .route("/rooms/:room_id", get(get_room).delete(close_room))
+        .route("/rooms/:room_id/peers/:peer_id", delete(kick_peer))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_admin_token,
+        ));
+
+    let stats = Router::new()
+        .route("/stats", get(stats_json))
+        .route("/metrics", get(metrics))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_token_if_set,
+        ));
+
+    let app = Router::new()
+        .route("/health", get(health))
+        .merge(stats)
+        .nest("/admin", admin)
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port))).await?;
+    tracing::info!("Stats server listening on port {}", port);
+
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+/// Cheap liveness check that does not query the workers
+async fn health(State(manager): State<Arc<MediasoupManager>>) -> impl IntoResponse {
+    let rooms = manager.rooms();
+    Json(serde_json::json!({
+        "status": if manager.workers().iter().all(|w| !w.closed()) { "ok" } else { "degraded" },
+        "uptimeSecs": manager.uptime().as_secs(),
+        "workers": manager.workers().len(),
+        "rooms": rooms.len(),
+        "peers": rooms.iter().map(|r| r.peer_count()).sum::<usize>(),
+    }))
+}
+
+async fn stats_json(State(manager): State<Arc<MediasoupManager>>) -> impl IntoResponse {
+    Json(stats::collect(&manager).await)
+}
+
+async fn metrics(State(manager): State<Arc<MediasoupManager>>) -> impl IntoResponse {
+    let stats = stats::collect(&manager).await;
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        stats::render_prometheus(&stats),
+    )
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// Admin requests carry `Authorization: Bearer <admin token>`
+async fn require_admin_token(
+    State(manager): State<Arc<MediasoupManager>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = manager.admin_token() else {
+        return error(StatusCode::NOT_FOUND, "Admin API is disabled");
+    };
+    if !has_bearer_token(&request, expected) {
+        return error(StatusCode::UNAUTHORIZED, "Invalid admin token");
+    }
+    next.run(request).await
+}
+
+/// Stats are open without an admin token, as on a lab server with no admin API
+async fn require_token_if_set(
+    State(manager): State<Arc<MediasoupManager>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match manager.admin_token() {
+        Some(expected) if !has_bearer_token(&request, expected) => {
+            error(StatusCode::UNAUTHORIZED, "Invalid admin token")
+        }
+        _ => next.run(request).await,
+    }
+}
+
+fn has_bearer_token(request: &Request, expected: &str) -> bool {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| auth::constant_time_eq(token.as_bytes(), expected.as_bytes()))
+}
+
+/// Told to the peers that get disconnected
+#[derive(Deserialize)]
+struct ReasonQuery {
+    reason: Option<String>,
+}
+
+async fn list_rooms(State(manager): State<Arc<MediasoupManager>>) -> impl IntoResponse {
+    Json(admin::rooms(&manager))
+}
+
+async fn get_room(
+    State(manager): State<Arc<MediasoupManager>>,
+    Path(room_id): Path<String>,
+) -> Response {
+    match manager.get_room(&room_id) {
+        Some(room) => Json(admin::room_summary(&room)).into_response(),
+        None => error(StatusCode::NOT_FOUND, "Room not found"),
+    }
+}
+
+async fn close_room(
+    State(state): State<AppState>,
+    Path(room_id): Path<String>,
+    Query(query): Query<ReasonQuery>,
+) -> Response {
+    if state.rooms.close_room(&room_id, query.reason).await {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        error(StatusCode::NOT_FOUND, "Room not found")
+    }
+}
+
+async fn kick_peer(
+    State(state): State<AppState>,
+    Path((room_id, peer_id)): Path<(String, String)>,
+    Query(query): Query<ReasonQuery>,
+) -> Response {
+    if state
+        .rooms
+        .kick_peer(&room_id, &peer_id, query.reason)
+        .await
+    {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        error(StatusCode::NOT_FOUND, "Peer not found")
+    }
+}
@@ -0,0 +1,312 @@
+use mediasoup::prelude::*;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// When to switch simulcast/SVC layers on their own. Consumer scores go from 0 to 10.
+#[derive(Debug, Clone, Copy)]
+pub struct LayerPolicy {
+    /// Drop one layer when the score falls below this
+    pub downgrade_score: u8,
+    /// Go back up one layer once the score has stayed at or above this...
+    pub upgrade_score: u8,
+    /// ...for this long
+    pub upgrade_delay: Duration,
+}
+
+impl Default for LayerPolicy {
+    fn default() -> Self {
+        Self {
+            downgrade_score: 5,
+            upgrade_score: 9,
+            upgrade_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Layers a simulcast/SVC producer sends, or None for a single encoding
+pub fn producer_layers(producer: &Producer) -> Option<(u8, u8)> {
+    let encodings = &producer.rtp_parameters().encodings;
+    let first = encodings.first()?;
+    let spatial = if encodings.len() > 1 {
+        encodings.len() as u8
+    } else {
+        first.scalability_mode.spatial_layers().get()
+    };
+    let temporal = first.scalability_mode.temporal_layers().get();
+
+    (spatial > 1 || temporal > 1).then_some((spatial, temporal))
+}
+
+/// Preferred layers of one consumer: the ceiling asked for by the client and what
+/// the score currently allows under it
+#[derive(Debug)]
+pub struct LayerController {
+    spatial_layers: u8,
+    temporal_layers: u8,
+    ceiling: (u8, u8),
+    current: (u8, u8),
+    score: u8,
+    /// Bumped on every change so delayed upgrades can tell they are stale
+    generation: u64,
+}
+
+impl LayerController {
+    pub fn new(spatial_layers: u8, temporal_layers: u8) -> Self {
+        let highest = (spatial_layers.max(1) - 1, temporal_layers.max(1) - 1);
+        Self {
+            spatial_layers: spatial_layers.max(1),
+            temporal_layers: temporal_layers.max(1),
+            ceiling: highest,
+            current: highest,
+            score: 10,
+            generation: 0,
+        }
+    }
+
+    pub fn current(&self) -> ConsumerLayers {
+        to_layers(self.current)
+    }
+
+    /// Set the highest layers the client wants; missing temporal layer means the highest
+    pub fn request(&mut self, spatial_layer: u8, temporal_layer: Option<u8>) -> ConsumerLayers {
+        let spatial = spatial_layer.min(self.spatial_layers - 1);
+        let temporal = temporal_layer
+            .unwrap_or(u8::MAX)
+            .min(self.temporal_layers - 1);
+        self.ceiling = (spatial, temporal);
+        self.current = self.ceiling;
+        self.generation += 1;
+        self.current()
+    }
+
+    /// Record a new score. Returns the layers to switch to right away, if any.
+    pub fn on_score(&mut self, score: u8, policy: &LayerPolicy) -> Option<ConsumerLayers> {
+        self.score = score;
+        if score >= policy.downgrade_score {
+            return None;
+        }
+
+        // Lower resolution first, then frame rate
+        let (spatial, temporal) = self.current;
+        self.current = if spatial > 0 {
+            (spatial - 1, temporal)
+        } else if temporal > 0 {
+            (0, temporal - 1)
+        } else {
+            return None;
+        };
+        self.generation += 1;
+        Some(self.current())
+    }
+
+    /// Generation to check again after `upgrade_delay`, when there is room to go up
+    pub fn upgrade_pending(&self, policy: &LayerPolicy) -> Option<u64> {
+        (self.score >= policy.upgrade_score && self.current != self.ceiling)
+            .then_some(self.generation)
+    }
+
+    /// Step one layer back up towards the ceiling if nothing changed since `generation`
+    pub fn upgrade(&mut self, generation: u64, policy: &LayerPolicy) -> Option<ConsumerLayers> {
+        if self.generation != generation || self.upgrade_pending(policy).is_none() {
+            return None;
+        }
+
+        let (spatial, temporal) = self.current;
+        self.current = if temporal < self.ceiling.1 {
+            (spatial, temporal + 1)
+        } else {
+            (spatial + 1, temporal)
+        };
+        self.generation += 1;
+        Some(self.current())
+    }
+}
+
+fn to_layers((spatial, temporal): (u8, u8)) -> ConsumerLayers {
+    ConsumerLayers {
+        spatial_layer: spatial,
+        temporal_layer: Some(temporal),
+    }
+}
+
+/// Follow a consumer's score and move its preferred layers with it.
+/// `notify` is told about every automatic change.
+pub fn watch_consumer<F>(
+    consumer: &Consumer,
+    controller: Arc<Mutex<LayerController>>,
+    policy: LayerPolicy,
+    notify: F,
+) where
+    F: Fn(ConsumerLayers) + Send + Sync + 'static,
+{
+    // Score events arrive on a worker thread
+    let runtime = tokio::runtime::Handle::current();
+    let weak_consumer = consumer.downgrade();
+    let notify = Arc::new(notify);
+
+    consumer
+        .on_score(move |score| {
+            let mut state = controller.lock();
+            if let Some(layers) = state.on_score(score.score, &policy) {
+                runtime.spawn(apply_layers(
+                    weak_consumer.clone(),
+                    layers,
+                    Arc::clone(&notify),
+                ));
+            } else if let Some(generation) = state.upgrade_pending(&policy) {
+                runtime.spawn(delayed_upgrade(
+                    weak_consumer.clone(),
+                    Arc::clone(&controller),
+                    generation,
+                    policy,
+                    Arc::clone(&notify),
+                ));
+            }
+        })
+        .detach();
+}
+
+/// Keep going up one layer at a time while the score stays good.
+/// Scores are only reported when they change, so this cannot wait for the next one.
+async fn delayed_upgrade<F>(
+    consumer: WeakConsumer,
+    controller: Arc<Mutex<LayerController>>,
+    mut generation: u64,
+    policy: LayerPolicy,
+    notify: Arc<F>,
+) where
+    F: Fn(ConsumerLayers) + Send + Sync + 'static,
+{
+    loop {
+        tokio::time::sleep(policy.upgrade_delay).await;
+
+        let layers = {
+            let mut state = controller.lock();
+            match state.upgrade(generation, &policy) {
+                Some(layers) => {
+                    generation = state.generation;
+                    layers
+                }
+                None => return,
+            }
+        };
+        apply_layers(consumer.clone(), layers, Arc::clone(&notify)).await;
+    }
+}
+
+async fn apply_layers<F>(consumer: WeakConsumer, layers: ConsumerLayers, notify: Arc<F>)
+where
+    F: Fn(ConsumerLayers) + Send + Sync + 'static,
+{
+    let Some(consumer) = consumer.upgrade() else {
+        return;
+    };
+    match consumer.set_preferred_layers(layers).await {
+        Ok(_) => {
+            tracing::debug!(
+                "Consumer {} switched to layers {}/{:?}",
+                consumer.id(),
+                layers.spatial_layer,
+                layers.temporal_layer
+            );
+            notify(layers);
+        }
+        Err(e) => tracing::warn!("Failed to set layers on consumer {}: {}", consumer.id(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(layers: ConsumerLayers) -> (u8, u8) {
+        (layers.spatial_layer, layers.temporal_layer.unwrap())
+    }
+
+    #[test]
+    fn downgrades_spatial_before_temporal() {
+        let policy = LayerPolicy::default();
+        let mut controller = LayerController::new(3, 3);
+
+        // A score at the threshold is still good enough
+        assert!(controller.on_score(5, &policy).is_none());
+
+        let steps: Vec<(u8, u8)> = (0..4)
+            .filter_map(|_| controller.on_score(2, &policy))
+            .map(pair)
+            .collect();
+        assert_eq!(steps, [(1, 2), (0, 2), (0, 1), (0, 0)]);
+
+        // Nothing lower to go to
+        assert!(controller.on_score(0, &policy).is_none());
+        assert!(LayerController::new(1, 1).on_score(0, &policy).is_none());
+    }
+
+    #[test]
+    fn request_is_clamped_to_the_producer() {
+        let mut controller = LayerController::new(3, 2);
+        assert_eq!(pair(controller.current()), (2, 1));
+        assert_eq!(pair(controller.request(7, None)), (2, 1));
+        assert_eq!(pair(controller.request(1, Some(9))), (1, 1));
+        assert_eq!(pair(controller.request(0, Some(0))), (0, 0));
+    }
+
+    #[test]
+    fn upgrades_back_to_the_ceiling_only() {
+        let policy = LayerPolicy::default();
+        let mut controller = LayerController::new(3, 3);
+        controller.request(1, Some(0));
+        assert_eq!(pair(controller.on_score(2, &policy).unwrap()), (0, 0));
+
+        // Not good enough yet to go back up
+        controller.on_score(7, &policy);
+        assert!(controller.upgrade_pending(&policy).is_none());
+
+        controller.on_score(10, &policy);
+        let generation = controller.upgrade_pending(&policy).unwrap();
+        assert_eq!(
+            pair(controller.upgrade(generation, &policy).unwrap()),
+            (1, 0)
+        );
+        assert!(controller.upgrade_pending(&policy).is_none());
+    }
+
+    #[test]
+    fn upgrades_frame_rate_before_resolution() {
+        let policy = LayerPolicy::default();
+        let mut controller = LayerController::new(2, 2);
+        for _ in 0..3 {
+            controller.on_score(1, &policy);
+        }
+        assert_eq!(pair(controller.current()), (0, 0));
+
+        controller.on_score(10, &policy);
+        let mut steps = Vec::new();
+        while let Some(generation) = controller.upgrade_pending(&policy) {
+            steps.push(pair(controller.upgrade(generation, &policy).unwrap()));
+        }
+        assert_eq!(steps, [(0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn skips_stale_upgrades() {
+        let policy = LayerPolicy::default();
+        let mut controller = LayerController::new(3, 3);
+        controller.on_score(2, &policy);
+        controller.on_score(10, &policy);
+        let generation = controller.upgrade_pending(&policy).unwrap();
+
+        // The score fell again before the delay was over
+        assert_eq!(pair(controller.on_score(3, &policy).unwrap()), (0, 2));
+        assert!(controller.upgrade(generation, &policy).is_none());
+        assert_eq!(pair(controller.current()), (0, 2));
+
+        // So does a new request from the client
+        controller.on_score(10, &policy);
+        let generation = controller.upgrade_pending(&policy).unwrap();
+        controller.request(1, None);
+        assert!(controller.upgrade(generation, &policy).is_none());
+        assert_eq!(pair(controller.current()), (1, 2));
+    }
+}
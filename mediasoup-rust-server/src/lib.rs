@@ -0,0 +1,16 @@
+//! SFU for the SmartLab classroom: WebSocket signaling in front of mediasoup.
+//! `main.rs` runs it as a server; the tests in `tests/` drive it in-process.
+
+pub mod admin;
+pub mod auth;
+pub mod chat;
+pub mod config;
+pub mod http;
+pub mod layers;
+pub mod manager;
+pub mod messages;
+pub mod recording;
+pub mod room;
+pub mod signaling;
+pub mod speakers;
+pub mod stats;
@@ -1,12 +1,7 @@
-mod config;
-mod manager;
-mod messages;
-mod room;
-mod signaling;
-
-use config::Config;
-use manager::MediasoupManager;
-use signaling::SignalingServer;
+use mediasoup_rust_server::config::Config;
+use mediasoup_rust_server::http;
+use mediasoup_rust_server::manager::MediasoupManager;
+use mediasoup_rust_server::signaling::SignalingServer;
 use std::sync::Arc;
 
 type BoxError = Box<dyn std::error::Error + Send + Sync>;
@@ -21,17 +16,74 @@ async fn main() -> Result<(), BoxError> {
         )
         .init();
 
+    let config = match Config::load(std::env::args().skip(1)) {
+        Ok(Some(config)) => config,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            eprintln!("Configuration error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
     println!("{}", "=".repeat(50));
     println!("Screen Sharing SFU Server (Mediasoup Rust)");
     println!("{}", "=".repeat(50));
 
-    let config = Config::default();
-    let local_ip = config::get_local_ip();
+    let local_ip = config.announced_ip();
     let listen_port = config.listen_port;
+    let metrics_port = config.metrics_port;
 
     println!("Local IP: {}", local_ip);
     println!("WebSocket Port: {}", listen_port);
+    match metrics_port {
+        Some(port) => println!("Metrics Port: {}", port),
+        None => println!("Metrics Port: off"),
+    }
+    println!(
+        "Admin API: {}",
+        if config.admin_token.is_some() {
+            "on"
+        } else {
+            "off"
+        }
+    );
     println!("Max Clients: {}", config.max_clients_per_room);
+    match config.webrtc_port {
+        Some(port) if config.num_workers > 1 => println!(
+            "WebRTC Ports: {}-{} (one per worker)",
+            port,
+            port as usize + config.num_workers - 1
+        ),
+        Some(port) => println!("WebRTC Port: {}", port),
+        None => println!(
+            "RTC Ports: {}-{}",
+            config.rtc_port_range.start(),
+            config.rtc_port_range.end()
+        ),
+    }
+    println!("ICE-TCP: {}", if config.ice_tcp { "on" } else { "off" });
+    println!("Reconnect Grace: {}s", config.reconnect_grace.as_secs());
+    println!("Teacher Keys: {}", config.room_auth.teacher_key_count());
+    println!(
+        "Room Password: {}",
+        if config.room_auth.has_password() {
+            "on"
+        } else {
+            "off"
+        }
+    );
+    println!(
+        "Recording: {} ({})",
+        config.recording.dir.display(),
+        if config.recording.ffmpeg.is_some() {
+            "mkv"
+        } else {
+            "rtpdump"
+        }
+    );
+    if config.room_auth.teacher_key_count() == 0 {
+        tracing::warn!("No teacher keys configured; nobody can join as teacher");
+    }
     println!("{}", "=".repeat(50));
 
     // Initialize Mediasoup
@@ -40,6 +92,17 @@ async fn main() -> Result<(), BoxError> {
     // Start signaling server
     let signaling = SignalingServer::new(manager.clone());
 
+    // Stats, metrics and the admin API run beside signaling; losing them must not take the SFU down
+    if let Some(port) = metrics_port {
+        let manager = manager.clone();
+        let rooms = signaling.room_control();
+        tokio::spawn(async move {
+            if let Err(e) = http::serve(manager, rooms, port).await {
+                tracing::error!("Stats server error: {}", e);
+            }
+        });
+    }
+
     println!("\nServer ready!");
     println!("Students can connect to: ws://{}:{}", local_ip, listen_port);
 
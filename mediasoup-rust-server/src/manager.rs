@@ -1,77 +1,247 @@
+use crate::auth::RoomAuth;
+use crate::chat::ChatSink;
 use crate::config::{self, Config};
+use crate::layers::LayerPolicy;
+use crate::recording::RecordingConfig;
 use crate::room::Room;
+use crate::speakers::SpeakerObservers;
 use mediasoup::prelude::*;
 use mediasoup::worker_manager::WorkerManager;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 
 type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
+const RESPAWN_ATTEMPTS: u32 = 5;
+
+/// A worker and, in single-port mode, the WebRtcServer its transports share
+struct WorkerSlot {
+    worker: Worker,
+    webrtc_server: Option<WebRtcServer>,
+}
+
 /// Mediasoup manager handling workers and rooms
 pub struct MediasoupManager {
-    workers: Vec<Worker>,
+    worker_manager: WorkerManager,
+    /// One worker per slot; a dead worker stays in its slot until it is replaced
+    workers: RwLock<Vec<WorkerSlot>>,
     rooms: RwLock<HashMap<String, Arc<Room>>>,
-    next_worker_index: AtomicUsize,
+    /// Slot of every worker that died, for the signaling server to recover
+    dead_tx: mpsc::UnboundedSender<usize>,
+    dead_rx: Mutex<Option<mpsc::UnboundedReceiver<usize>>>,
     config: Config,
     local_ip: String,
+    started_at: Instant,
 }
 
 impl MediasoupManager {
     /// Initialize the manager with workers
     pub async fn new(config: Config) -> Result<Self, BoxError> {
-        let local_ip = config::get_local_ip();
+        let local_ip = config.announced_ip();
         tracing::info!("Creating {} mediasoup workers...", config.num_workers);
 
-        let worker_manager = WorkerManager::new();
-        let mut workers = Vec::with_capacity(config.num_workers);
+        let (dead_tx, dead_rx) = mpsc::unbounded_channel();
+        let mut manager = Self {
+            worker_manager: WorkerManager::new(),
+            workers: RwLock::new(Vec::with_capacity(config.num_workers)),
+            rooms: RwLock::new(HashMap::new()),
+            dead_tx,
+            dead_rx: Mutex::new(Some(dead_rx)),
+            config,
+            local_ip,
+            started_at: Instant::now(),
+        };
 
-        for i in 0..config.num_workers {
-            let worker = worker_manager
-                .create_worker(config::worker_settings())
-                .await?;
+        for i in 0..manager.config.num_workers {
+            let slot = manager.spawn_worker(i).await?;
+            manager.workers.get_mut().push(slot);
+        }
 
-            let worker_id = worker.id();
-            worker
-                .on_dead(move |_| {
-                    tracing::error!("Worker {} died!", worker_id);
-                })
-                .detach();
+        Ok(manager)
+    }
 
-            tracing::info!("Worker {} created [id: {}]", i, worker.id());
-            workers.push(worker);
-        }
+    /// Start a worker for slot `index` that reports its death on `dead_tx`
+    async fn spawn_worker(&self, index: usize) -> Result<WorkerSlot, BoxError> {
+        let worker = self
+            .worker_manager
+            .create_worker(config::worker_settings(&self.config))
+            .await?;
 
-        Ok(Self {
-            workers,
-            rooms: RwLock::new(HashMap::new()),
-            next_worker_index: AtomicUsize::new(0),
-            config,
-            local_ip,
+        let worker_id = worker.id();
+        let dead_tx = self.dead_tx.clone();
+        worker
+            .on_dead(move |reason| {
+                tracing::error!("Worker {} [id: {}] died: {:?}", index, worker_id, reason);
+                let _ = dead_tx.send(index);
+            })
+            .detach();
+
+        // A respawned worker gets the same port back; the dead one released it
+        let webrtc_server = match self.config.webrtc_port {
+            Some(first) => {
+                let port = first + index as u16;
+                let options = config::webrtc_server_options(&self.config, &self.local_ip, port);
+                let server = worker.create_webrtc_server(options).await?;
+                tracing::info!("Worker {} listening for media on port {}", index, port);
+                Some(server)
+            }
+            None => None,
+        };
+
+        tracing::info!("Worker {} created [id: {}]", index, worker.id());
+        Ok(WorkerSlot {
+            worker,
+            webrtc_server,
         })
     }
 
-    fn get_next_worker(&self) -> &Worker {
-        let index = self.next_worker_index.fetch_add(1, Ordering::Relaxed) % self.workers.len();
-        &self.workers[index]
+    /// Slots of dead workers, handed out once to whoever recovers them
+    pub fn take_worker_deaths(&self) -> Option<mpsc::UnboundedReceiver<usize>> {
+        self.dead_rx.lock().take()
+    }
+
+    /// Recover the worker in `index` as if it had died, the way its `on_dead` handler
+    /// reports a crash. Only for the integration tests, which cannot crash a worker;
+    /// not part of the server's API.
+    #[doc(hidden)]
+    pub fn report_worker_death(&self, index: usize) {
+        let _ = self.dead_tx.send(index);
+    }
+
+    /// Remove and close the rooms that lived on the worker in `index`. Their routers
+    /// died with it, so peers have to join again to get a room on a working worker.
+    pub fn close_rooms_on_worker(&self, index: usize) -> Vec<Arc<Room>> {
+        let rooms: Vec<Arc<Room>> = {
+            let mut rooms = self.rooms.write();
+            let ids: Vec<String> = rooms
+                .values()
+                .filter(|room| room.uses_worker(index))
+                .map(|room| room.id.clone())
+                .collect();
+            ids.iter().filter_map(|id| rooms.remove(id)).collect()
+        };
+
+        for room in &rooms {
+            room.close();
+        }
+        rooms
+    }
+
+    /// Replace the dead worker in `index`, retrying with backoff. The slot is
+    /// skipped by room placement until this succeeds.
+    pub async fn respawn_worker(&self, index: usize) {
+        let mut delay = Duration::from_secs(1);
+        for attempt in 1..=RESPAWN_ATTEMPTS {
+            match self.spawn_worker(index).await {
+                Ok(slot) => {
+                    self.workers.write()[index] = slot;
+                    return;
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to respawn worker {} (attempt {}/{}): {}",
+                        index,
+                        attempt,
+                        RESPAWN_ATTEMPTS,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+        tracing::error!("Giving up on worker {}; rooms go to the others", index);
+    }
+
+    /// Current load of every worker slot, None for dead ones
+    fn worker_loads(&self) -> Vec<Option<usize>> {
+        let mut loads: Vec<Option<usize>> = self
+            .workers
+            .read()
+            .iter()
+            .map(|slot| (!slot.worker.closed()).then_some(0))
+            .collect();
+
+        for room in self.rooms.read().values() {
+            for (index, room_load) in room.worker_loads() {
+                if let Some(Some(load)) = loads.get_mut(index) {
+                    *load += room_load;
+                }
+            }
+        }
+        loads
+    }
+
+    /// Router for a peer joining `room`. The teacher and the first students share the
+    /// main router; once it holds `peers_per_router`, students go to routers on the
+    /// least loaded workers and the teacher's media is piped to them.
+    pub async fn router_for_peer(&self, room: &Room, is_teacher: bool) -> Result<Router, BoxError> {
+        if is_teacher {
+            return Ok(room.router.clone());
+        }
+        if let Some(router) = room.router_with_space(self.config.peers_per_router) {
+            return Ok(router);
+        }
+
+        let (worker_index, worker) = self.pick_worker()?;
+        let router = worker.create_router(config::router_options()).await?;
+        room.add_router(worker_index, router.clone());
+
+        tracing::info!(
+            "[Room {}] Added router {} on worker {} ({} routers)",
+            room.id,
+            router.id(),
+            worker_index,
+            room.router_count()
+        );
+        Ok(router)
+    }
+
+    /// Worker with the fewest routers and consumers
+    fn pick_worker(&self) -> Result<(usize, Worker), BoxError> {
+        let index = least_loaded(&self.worker_loads()).ok_or("No mediasoup worker available")?;
+        Ok((index, self.workers.read()[index].worker.clone()))
     }
 
     /// Create a new room
-    pub async fn create_room(
-        &self,
-        room_id: Option<String>,
-    ) -> Result<Arc<Room>, BoxError> {
-        let worker = self.get_next_worker();
+    pub async fn create_room(&self, room_id: Option<String>) -> Result<Arc<Room>, BoxError> {
+        let (worker_index, worker) = self.pick_worker()?;
         let router = worker.create_router(config::router_options()).await?;
 
-        let room = Arc::new(Room::new(router, room_id));
+        let speakers = SpeakerObservers::new(&router).await?;
+
+        let room = Arc::new(Room::new(
+            router,
+            room_id,
+            worker_index,
+            self.config.chat_history_size,
+            speakers,
+        ));
         self.rooms.write().insert(room.id.clone(), room.clone());
 
-        tracing::info!("Room created: {}", room.id);
+        tracing::info!("Room created: {} on worker {}", room.id, worker_index);
         Ok(room)
     }
 
+    pub fn workers(&self) -> Vec<Worker> {
+        self.workers
+            .read()
+            .iter()
+            .map(|slot| slot.worker.clone())
+            .collect()
+    }
+
+    pub fn rooms(&self) -> Vec<Arc<Room>> {
+        self.rooms.read().values().cloned().collect()
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
     /// Get existing room
     pub fn get_room(&self, room_id: &str) -> Option<Arc<Room>> {
         self.rooms.read().get(room_id).cloned()
@@ -95,13 +265,23 @@ impl MediasoupManager {
         }
     }
 
-    /// Create WebRTC transport
+    /// Create WebRTC transport on `router`, which lives on the worker in `worker_index`
     pub async fn create_webrtc_transport(
         &self,
-        room: &Room,
+        router: &Router,
+        worker_index: usize,
     ) -> Result<(WebRtcTransport, TransportParams), BoxError> {
-        let options = config::webrtc_transport_options(self.local_ip.clone());
-        let transport = room.router.create_webrtc_transport(options).await?;
+        let webrtc_server = self
+            .workers
+            .read()
+            .get(worker_index)
+            .and_then(|slot| slot.webrtc_server.clone());
+        let options = config::webrtc_transport_options(
+            self.local_ip.clone(),
+            webrtc_server,
+            self.config.ice_tcp,
+        );
+        let transport = router.create_webrtc_transport(options).await?;
 
         // Set max incoming bitrate
         transport
@@ -113,6 +293,7 @@ impl MediasoupManager {
             ice_parameters: transport.ice_parameters().clone(),
             ice_candidates: transport.ice_candidates().clone(),
             dtls_parameters: transport.dtls_parameters(),
+            sctp_parameters: transport.sctp_parameters(),
         };
 
         Ok((transport, params))
@@ -147,21 +328,20 @@ impl MediasoupManager {
 
     /// Create consumer
     pub async fn create_consumer(
-        room: &Room,
+        router: &Router,
         transport: &WebRtcTransport,
         producer: &Producer,
         rtp_capabilities: &RtpCapabilities,
+        preferred_layers: Option<ConsumerLayers>,
     ) -> Result<Option<Consumer>, BoxError> {
-        if !room
-            .router
-            .can_consume(&producer.id(), rtp_capabilities)
-        {
+        if !router.can_consume(&producer.id(), rtp_capabilities) {
             tracing::warn!("Cannot consume producer {}", producer.id());
             return Ok(None);
         }
 
         let mut options = ConsumerOptions::new(producer.id(), rtp_capabilities.clone());
         options.paused = true; // Start paused
+        options.preferred_layers = preferred_layers;
 
         let consumer = transport.consume(options).await?;
 
@@ -174,9 +354,87 @@ impl MediasoupManager {
         Ok(Some(consumer))
     }
 
+    /// Create data producer on an SCTP stream the client picked
+    pub async fn create_data_producer(
+        transport: &WebRtcTransport,
+        sctp_stream_parameters: SctpStreamParameters,
+        label: String,
+        protocol: String,
+    ) -> Result<DataProducer, BoxError> {
+        let mut options = DataProducerOptions::new_sctp(sctp_stream_parameters);
+        options.label = label;
+        options.protocol = protocol;
+        let data_producer = transport.produce_data(options).await?;
+
+        tracing::info!(
+            "Data producer {} created (label: {})",
+            data_producer.id(),
+            data_producer.label()
+        );
+
+        Ok(data_producer)
+    }
+
+    /// Create data consumer; it keeps the producer's ordering and retransmit settings
+    pub async fn create_data_consumer(
+        transport: &WebRtcTransport,
+        data_producer_id: DataProducerId,
+    ) -> Result<DataConsumer, BoxError> {
+        let options = DataConsumerOptions::new_sctp(data_producer_id);
+        let data_consumer = transport.consume_data(options).await?;
+
+        tracing::info!(
+            "Data consumer {} created for data producer {}",
+            data_consumer.id(),
+            data_producer_id
+        );
+
+        Ok(data_consumer)
+    }
+
     pub fn max_clients_per_room(&self) -> usize {
         self.config.max_clients_per_room
     }
+
+    pub fn room_auth(&self) -> &RoomAuth {
+        &self.config.room_auth
+    }
+
+    pub fn layer_policy(&self) -> LayerPolicy {
+        self.config.layer_policy
+    }
+
+    pub fn reconnect_grace(&self) -> Duration {
+        self.config.reconnect_grace
+    }
+
+    /// How long a student's microphone may stay silent before it is paused, if at all
+    pub fn auto_pause_silent(&self) -> Option<Duration> {
+        self.config.auto_pause_silent
+    }
+
+    /// Bearer token of the admin API, if it is enabled
+    pub fn admin_token(&self) -> Option<&str> {
+        self.config.admin_token.as_deref()
+    }
+
+    pub fn chat_sink(&self) -> Option<&dyn ChatSink> {
+        self.config.chat_sink.as_deref()
+    }
+
+    pub fn recording_config(&self) -> &RecordingConfig {
+        &self.config.recording
+    }
+}
+
+/// Index of the lowest load, skipping dead slots; the first one wins a tie
+fn least_loaded(loads: &[Option<usize>]) -> Option<usize> {
+    loads
+        .iter()
+        .enumerate()
+        .filter_map(|(index, load)| load.map(|load| (index, load)))
+        .min_by_key(|&(_, load)| load)
+        .map(|(index, _)| index)
 }
 
 /// Transport parameters for client
@@ -189,4 +447,43 @@ pub struct TransportParams {
     pub ice_candidates: Vec<IceCandidate>,
     #[serde(rename = "dtlsParameters")]
     pub dtls_parameters: DtlsParameters,
+    #[serde(rename = "sctpParameters")]
+    pub sctp_parameters: Option<SctpParameters>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_least_loaded_worker() {
+        assert_eq!(least_loaded(&[Some(12), Some(3), Some(7)]), Some(1));
+    }
+
+    #[test]
+    fn ties_go_to_first_worker() {
+        assert_eq!(least_loaded(&[Some(2), Some(2), Some(2)]), Some(0));
+    }
+
+    #[test]
+    fn skips_dead_worker() {
+        // Worker 1 crashed while it was the least loaded
+        assert_eq!(least_loaded(&[Some(12), None, Some(7)]), Some(2));
+    }
+
+    #[test]
+    fn respawned_worker_takes_new_rooms() {
+        let mut loads = vec![Some(40), None, Some(25)];
+        assert_eq!(least_loaded(&loads), Some(2));
+
+        // Its rooms were closed, so the replacement starts empty
+        loads[1] = Some(0);
+        assert_eq!(least_loaded(&loads), Some(1));
+    }
+
+    #[test]
+    fn no_worker_when_all_dead() {
+        assert_eq!(least_loaded(&[None, None]), None);
+        assert_eq!(least_loaded(&[]), None);
+    }
 }
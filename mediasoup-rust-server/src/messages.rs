@@ -14,9 +14,28 @@ pub enum ClientMessage {
     Produce { data: ProduceData },
     Consume { data: ConsumeData },
     ResumeConsumer { data: ResumeConsumerData },
+    SetPreferredLayers { data: SetPreferredLayersData },
+    ResumeProducer { data: ResumeProducerData },
     #[serde(alias = "getProducers")]
     GetProducers { #[serde(default)] data: Option<serde_json::Value> },
     ChatMessage { data: ChatMessageData },
+    // Moderation
+    RaiseHand { data: RaiseHandData },
+    MutePeer { data: MutePeerData },
+    GrantFloor { data: PeerTargetData },
+    KickPeer { data: KickPeerData },
+    // Student screen presenting
+    RequestPresent { data: RequestPresentData },
+    ApprovePresent { data: PeerTargetData },
+    RevokePresent { data: PeerTargetData },
+    // Recording (teacher only)
+    StartRecording { #[serde(default)] data: Option<serde_json::Value> },
+    StopRecording { #[serde(default)] data: Option<serde_json::Value> },
+    // Data channels (teacher produces, students consume)
+    ProduceData { data: ProduceDataChannelData },
+    ConsumeData { data: ConsumeDataChannelData },
+    CloseDataProducer { data: CloseDataProducerData },
+    GetDataProducers { #[serde(default)] data: Option<serde_json::Value> },
 }
 
 #[derive(Debug, Deserialize)]
@@ -25,7 +44,18 @@ pub struct JoinData {
     pub room_id: String,
     pub peer_id: String,
     pub name: String,
+    /// Requested role; only honoured with a teacher join token
+    #[serde(default)]
     pub is_teacher: bool,
+    /// Token from an earlier `joined`, to take the peer back after a dropped connection
+    #[serde(default)]
+    pub resume_token: Option<String>,
+    /// Join token signed by the teacher's key
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Room password for students without a token
+    #[serde(default)]
+    pub password: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -60,6 +90,34 @@ pub struct ProduceData {
 pub struct ConsumeData {
     pub producer_id: String,
     pub rtp_capabilities: RtpCapabilities,
+    /// Highest simulcast/SVC layer to receive, e.g. 0 on weak machines
+    #[serde(default)]
+    pub preferred_spatial_layer: Option<u8>,
+}
+
+/// Data channel opened by the teacher on its send transport
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProduceDataChannelData {
+    /// Stream id and ordered/unordered delivery picked by the client
+    pub sctp_stream_parameters: SctpStreamParameters,
+    /// What the channel carries, e.g. "pointer", "strokes" or "poll"
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub protocol: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumeDataChannelData {
+    pub data_producer_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloseDataProducerData {
+    pub data_producer_id: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -68,11 +126,69 @@ pub struct ResumeConsumerData {
     pub consumer_id: String,
 }
 
+/// Unpause one of the sender's own producers, e.g. a microphone paused for silence
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumeProducerData {
+    pub producer_id: String,
+}
+
+/// Highest layers a consumer should receive; the server may go lower on a poor link
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetPreferredLayersData {
+    pub consumer_id: String,
+    pub spatial_layer: u8,
+    /// Highest temporal layer when unset
+    #[serde(default)]
+    pub temporal_layer: Option<u8>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChatMessageData {
     pub content: String,
     pub timestamp: String,
+    /// Peer id for a private message; the whole room when unset
+    #[serde(default)]
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RaiseHandData {
+    pub raised: bool,
+}
+
+/// Teacher request to silence a student's microphone
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MutePeerData {
+    pub peer_id: String,
+    /// Close the audio producer instead of pausing it
+    #[serde(default)]
+    pub close: bool,
+}
+
+/// Student asks to present their screen, or withdraws the request
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestPresentData {
+    pub requested: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerTargetData {
+    pub peer_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KickPeerData {
+    pub peer_id: String,
+    #[serde(default)]
+    pub reason: Option<String>,
 }
 
 /// Outgoing message to client
@@ -93,6 +209,25 @@ pub enum ServerMessage {
     PeerLeft(PeerLeftData),
     NewProducer(NewProducerData),
     ChatMessage(ChatMessageBroadcast),
+    ProducerClosed(ProducerClosedData),
+    HandRaised(HandRaisedData),
+    PermissionsChanged(PermissionsData),
+    Kicked(KickedData),
+    PresentRequested(PresentRequestedData),
+    PresenterChanged(PresenterChangedData),
+    ConsumerLayersChanged(ConsumerLayersData),
+    RecordingStarted(RecordingStartedData),
+    RecordingStopped(RecordingStoppedData),
+    ActiveSpeaker(ActiveSpeakerData),
+    AudioLevels(AudioLevelsData),
+    ProducerPaused(ProducerPausedData),
+    ProducerResumed(ProducerResumedData),
+    RoomReset(RoomResetData),
+    DataProduced(DataProducedData),
+    DataConsumed(DataConsumedData),
+    DataProducers(Vec<DataProducerInfo>),
+    NewDataProducer(DataProducerInfo),
+    DataProducerClosed(DataProducerClosedData),
 }
 
 #[derive(Debug, Serialize)]
@@ -107,6 +242,12 @@ pub struct JoinedData {
     pub peer_id: String,
     pub is_teacher: bool,
     pub rtp_capabilities: RtpCapabilitiesFinalized,
+    /// Send back as `resumeToken` in `join` to reconnect within the grace period
+    pub resume_token: String,
+    /// The peer's transports, producers and consumers were kept
+    pub resumed: bool,
+    /// Recent room messages and this peer's private ones, oldest first
+    pub chat_history: Vec<ChatMessageBroadcast>,
 }
 
 #[derive(Debug, Serialize)]
@@ -117,6 +258,8 @@ pub struct TransportCreatedData {
     pub ice_parameters: IceParameters,
     pub ice_candidates: Vec<IceCandidate>,
     pub dtls_parameters: DtlsParameters,
+    /// Set when the transport carries data channels
+    pub sctp_parameters: Option<SctpParameters>,
 }
 
 #[derive(Debug, Serialize)]
@@ -178,12 +321,180 @@ pub struct NewProducerData {
     pub peer_id: String,
 }
 
-#[derive(Debug, Serialize)]
+/// Same values as `practice_messages.message_type` in the app database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ChatMessageType {
+    All,
+    Direct,
+}
+
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChatMessageBroadcast {
+    /// Increasing within a room
+    pub id: u64,
     pub sender_id: String,
     pub sender_name: String,
     pub content: String,
     pub timestamp: String,
     pub is_teacher: bool,
+    pub message_type: ChatMessageType,
+    /// Set for direct messages
+    pub receiver_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProducerClosedData {
+    pub producer_id: String,
+    pub peer_id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandRaisedData {
+    pub peer_id: String,
+    pub name: String,
+    pub raised: bool,
+}
+
+/// What a student may currently publish
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionsData {
+    pub peer_id: String,
+    pub audio: bool,
+    pub video: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KickedData {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentRequestedData {
+    pub peer_id: String,
+    pub name: String,
+    pub requested: bool,
+}
+
+/// Student currently presenting, if any
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenterChangedData {
+    pub peer_id: Option<String>,
+    pub name: Option<String>,
+}
+
+/// Layers a consumer now prefers, after a client request or an automatic switch
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumerLayersData {
+    pub consumer_id: String,
+    pub spatial_layer: u8,
+    pub temporal_layer: Option<u8>,
+    /// Changed by the server because of the consumer's score
+    pub automatic: bool,
+}
+
+/// Sent to the whole room so students know the lesson is being recorded
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingStartedData {
+    /// File on the server machine (the .sdp next to the dumps for rtpdump)
+    pub path: String,
+    /// "mkv" or "rtpdump"
+    pub format: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingStoppedData {
+    pub path: String,
+    pub duration_secs: u64,
+}
+
+/// Student the teacher hears most right now
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveSpeakerData {
+    pub peer_id: String,
+    pub producer_id: String,
+}
+
+/// Students currently making sound, loudest first; empty when the room is silent
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioLevelsData {
+    pub levels: Vec<AudioLevel>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioLevel {
+    pub peer_id: String,
+    pub producer_id: String,
+    /// dBvo, from -127 (silence) to 0 (loudest)
+    pub volume: i8,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProducerPausedData {
+    pub producer_id: String,
+    pub peer_id: String,
+    /// "silence" when paused automatically
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProducerResumedData {
+    pub producer_id: String,
+    pub peer_id: String,
+}
+
+/// The room's media server process failed and the room is gone. Clients drop their
+/// transports and join again (without a resume token) to get a fresh room.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomResetData {
+    pub room_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataProducedData {
+    pub data_producer_id: String,
+    pub label: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataConsumedData {
+    pub data_consumer_id: String,
+    pub data_producer_id: String,
+    pub sctp_stream_parameters: Option<SctpStreamParameters>,
+    pub label: String,
+    pub protocol: String,
+}
+
+/// Teacher data channel students can consume
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataProducerInfo {
+    pub data_producer_id: String,
+    pub label: String,
+    pub peer_id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataProducerClosedData {
+    pub data_producer_id: String,
+    pub peer_id: String,
 }
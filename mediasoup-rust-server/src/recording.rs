@@ -0,0 +1,507 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use mediasoup::prelude::*;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket as StdUdpSocket};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+const LOCALHOST: Ipv4Addr = Ipv4Addr::LOCALHOST;
+
+/// Where and how lessons are recorded
+#[derive(Debug, Clone)]
+pub struct RecordingConfig {
+    pub dir: PathBuf,
+    /// ffmpeg binary used to write Matroska; RTP dumps are written without it
+    pub ffmpeg: Option<PathBuf>,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            dir: dirs::home_dir()
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join(".smartlab")
+                .join("recordings"),
+            ffmpeg: None,
+        }
+    }
+}
+
+impl RecordingConfig {
+    /// Look for `ffmpeg` on the PATH when no binary is configured; recordings fall back
+    /// to RTP dumps without it. A configured binary that does not run is an error.
+    pub fn detect_ffmpeg(&mut self) -> Result<(), String> {
+        match &self.ffmpeg {
+            Some(ffmpeg) if !runs(ffmpeg) => {
+                Err(format!("ffmpeg at {} does not run", ffmpeg.display()))
+            }
+            Some(_) => Ok(()),
+            None => {
+                let ffmpeg = PathBuf::from("ffmpeg");
+                self.ffmpeg = runs(&ffmpeg).then_some(ffmpeg);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn runs(ffmpeg: &Path) -> bool {
+    std::process::Command::new(ffmpeg)
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// One .mkv file written by ffmpeg, codecs copied as sent
+    Matroska,
+    /// One rtpdump file per track plus an .sdp describing them
+    RtpDump,
+}
+
+impl RecordingFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordingFormat::Matroska => "mkv",
+            RecordingFormat::RtpDump => "rtpdump",
+        }
+    }
+}
+
+/// A track being recorded: the consumer feeding it and the local port it is sent to
+struct Track {
+    /// Only held so it stays open
+    _transport: PlainTransport,
+    consumer: Consumer,
+    port: u16,
+}
+
+enum Sink {
+    Ffmpeg(Child),
+    RtpDump {
+        stop: watch::Sender<bool>,
+        writers: Vec<JoinHandle<()>>,
+    },
+}
+
+/// Recording of a room's teacher media in progress
+pub struct Recording {
+    pub path: PathBuf,
+    pub format: RecordingFormat,
+    started_at: Instant,
+    tracks: Vec<Track>,
+    sink: Sink,
+}
+
+impl Recording {
+    /// Consume `producers` over plain RTP and write them under `config.dir/<room>/`
+    pub async fn start(
+        router: &Router,
+        producers: &[Producer],
+        config: &RecordingConfig,
+        room_id: &str,
+    ) -> Result<Self, BoxError> {
+        if producers.is_empty() {
+            return Err("No teacher media to record".into());
+        }
+
+        let dir = config.dir.join(sanitize_file_name(room_id));
+        tokio::fs::create_dir_all(&dir).await?;
+        let name = timestamp_name();
+        let mut base = dir.join(&name);
+        // A recording restarted within the same second gets its own files
+        let mut suffix = 1;
+        while base.with_extension("mkv").exists() || base.with_extension("sdp").exists() {
+            base = dir.join(format!("{}-{}", name, suffix));
+            suffix += 1;
+        }
+
+        let rtp_capabilities = recording_rtp_capabilities(router);
+        let format = if config.ffmpeg.is_some() {
+            RecordingFormat::Matroska
+        } else {
+            RecordingFormat::RtpDump
+        };
+
+        // ffmpeg opens its own ports from the SDP; RTP dumps are received here
+        let mut sockets = Vec::new();
+        let mut tracks = Vec::new();
+        for producer in producers {
+            let port = match format {
+                RecordingFormat::Matroska => free_port_pair()?,
+                RecordingFormat::RtpDump => {
+                    let socket = tokio::net::UdpSocket::bind((LOCALHOST, 0)).await?;
+                    let port = socket.local_addr()?.port();
+                    sockets.push(socket);
+                    port
+                }
+            };
+
+            // ffmpeg expects RTCP on port + 1; the dumps take both on one port
+            let mut options = PlainTransportOptions::new(ListenInfo {
+                protocol: Protocol::Udp,
+                ip: IpAddr::V4(LOCALHOST),
+                announced_address: None,
+                port: None,
+                port_range: None,
+                flags: None,
+                send_buffer_size: None,
+                recv_buffer_size: None,
+                expose_internal_ip: false,
+            });
+            let rtcp_mux = format == RecordingFormat::RtpDump;
+            options.rtcp_mux = rtcp_mux;
+            options.comedia = false;
+            let transport = router.create_plain_transport(options).await?;
+            transport
+                .connect(PlainTransportRemoteParameters {
+                    ip: Some(IpAddr::V4(LOCALHOST)),
+                    port: Some(port),
+                    rtcp_port: (!rtcp_mux).then_some(port + 1),
+                    srtp_parameters: None,
+                })
+                .await?;
+
+            let mut consumer_options =
+                ConsumerOptions::new(producer.id(), rtp_capabilities.clone());
+            // Nothing flows until the sink is listening
+            consumer_options.paused = true;
+            let consumer = transport.consume(consumer_options).await?;
+
+            tracks.push(Track {
+                _transport: transport,
+                consumer,
+                port,
+            });
+        }
+
+        let sdp = build_sdp(&tracks);
+        let (path, sink) = match &config.ffmpeg {
+            Some(ffmpeg) if format == RecordingFormat::Matroska => {
+                let path = base.with_extension("mkv");
+                (
+                    path.clone(),
+                    Sink::Ffmpeg(spawn_ffmpeg(ffmpeg, &sdp, &path).await?),
+                )
+            }
+            _ => {
+                let sdp_path = base.with_extension("sdp");
+                tokio::fs::write(&sdp_path, &sdp).await?;
+                let (stop, stopped) = watch::channel(false);
+                let mut writers = Vec::new();
+                for (index, (socket, track)) in sockets.into_iter().zip(&tracks).enumerate() {
+                    let kind = match track.consumer.kind() {
+                        MediaKind::Audio => "audio",
+                        MediaKind::Video => "video",
+                    };
+                    let file_path =
+                        PathBuf::from(format!("{}-{}-{}.rtpdump", base.display(), index, kind));
+                    writers.push(tokio::spawn(write_rtpdump(
+                        socket,
+                        file_path,
+                        stopped.clone(),
+                    )));
+                }
+                (sdp_path, Sink::RtpDump { stop, writers })
+            }
+        };
+
+        // Give ffmpeg a moment to open its sockets, then start with a key frame
+        if format == RecordingFormat::Matroska {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        for track in &tracks {
+            track.consumer.resume().await?;
+            if track.consumer.kind() == MediaKind::Video {
+                track.consumer.request_key_frame().await?;
+            }
+        }
+
+        tracing::info!("[Room {}] Recording to {}", room_id, path.display());
+
+        Ok(Self {
+            path,
+            format,
+            started_at: Instant::now(),
+            tracks,
+            sink,
+        })
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Stop consuming and let the sink finish its file
+    pub async fn stop(self) -> Duration {
+        let duration = self.duration();
+        for track in &self.tracks {
+            let _ = track.consumer.pause().await;
+        }
+
+        match self.sink {
+            Sink::Ffmpeg(mut child) => {
+                // "q" makes ffmpeg write the Matroska index before exiting
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(b"q").await;
+                }
+                match tokio::time::timeout(Duration::from_secs(10), child.wait()).await {
+                    Ok(Ok(status)) if !status.success() => {
+                        tracing::warn!("ffmpeg exited with {} for {}", status, self.path.display())
+                    }
+                    Ok(_) => {}
+                    Err(_) => {
+                        tracing::warn!("ffmpeg did not stop in time, killing it");
+                        let _ = child.kill().await;
+                    }
+                }
+            }
+            Sink::RtpDump { stop, writers } => {
+                let _ = stop.send(true);
+                for writer in writers {
+                    let _ = writer.await;
+                }
+            }
+        }
+
+        // Consumers and transports close when dropped
+        drop(self.tracks);
+        tracing::info!(
+            "Recording {} stopped after {:?}",
+            self.path.display(),
+            duration
+        );
+        duration
+    }
+}
+
+/// Router codecs as consumer capabilities, without RTX which the sinks cannot use
+fn recording_rtp_capabilities(router: &Router) -> RtpCapabilities {
+    let codecs = router
+        .rtp_capabilities()
+        .codecs
+        .iter()
+        .filter_map(|codec| match codec.clone() {
+            RtpCodecCapabilityFinalized::Audio {
+                mime_type,
+                preferred_payload_type,
+                clock_rate,
+                channels,
+                parameters,
+                rtcp_feedback,
+            } => Some(RtpCodecCapability::Audio {
+                mime_type,
+                preferred_payload_type: Some(preferred_payload_type),
+                clock_rate,
+                channels,
+                parameters,
+                rtcp_feedback,
+            }),
+            RtpCodecCapabilityFinalized::Video {
+                mime_type,
+                preferred_payload_type,
+                clock_rate,
+                parameters,
+                rtcp_feedback,
+            } => (mime_type != MimeTypeVideo::Rtx).then_some(RtpCodecCapability::Video {
+                mime_type,
+                preferred_payload_type: Some(preferred_payload_type),
+                clock_rate,
+                parameters,
+                rtcp_feedback,
+            }),
+        })
+        .collect();
+
+    RtpCapabilities {
+        codecs,
+        header_extensions: Vec::new(),
+    }
+}
+
+/// SDP describing the consumers' RTP, for ffmpeg or to replay the dumps
+fn build_sdp(tracks: &[Track]) -> String {
+    let mut sdp = format!(
+        "v=0\r\no=- 0 0 IN IP4 {ip}\r\ns=SmartLab Recording\r\nc=IN IP4 {ip}\r\nt=0 0\r\n",
+        ip = LOCALHOST
+    );
+
+    for track in tracks {
+        let rtp_parameters = track.consumer.rtp_parameters();
+        let Some(codec) = rtp_parameters.codecs.first() else {
+            continue;
+        };
+        let (media, encoding, channels) = match codec {
+            RtpCodecParameters::Audio {
+                mime_type: MimeTypeAudio::Opus,
+                channels,
+                ..
+            } => ("audio", "opus", Some(channels.get())),
+            RtpCodecParameters::Video { mime_type, .. } => match mime_type {
+                MimeTypeVideo::H264 => ("video", "H264", None),
+                MimeTypeVideo::Vp8 => ("video", "VP8", None),
+                MimeTypeVideo::Vp9 => ("video", "VP9", None),
+                _ => continue,
+            },
+            _ => continue,
+        };
+        let payload_type = codec.payload_type();
+
+        sdp.push_str(&format!(
+            "m={} {} RTP/AVP {}\r\n",
+            media, track.port, payload_type
+        ));
+        match channels {
+            Some(channels) => sdp.push_str(&format!(
+                "a=rtpmap:{} {}/{}/{}\r\n",
+                payload_type,
+                encoding,
+                codec.clock_rate(),
+                channels
+            )),
+            None => sdp.push_str(&format!(
+                "a=rtpmap:{} {}/{}\r\n",
+                payload_type,
+                encoding,
+                codec.clock_rate()
+            )),
+        }
+
+        let fmtp: Vec<String> = codec
+            .parameters()
+            .iter()
+            .map(|(key, value)| match value {
+                RtpCodecParametersParametersValue::String(s) => format!("{}={}", key, s),
+                RtpCodecParametersParametersValue::Number(n) => format!("{}={}", key, n),
+            })
+            .collect();
+        if !fmtp.is_empty() {
+            sdp.push_str(&format!("a=fmtp:{} {}\r\n", payload_type, fmtp.join(";")));
+        }
+        sdp.push_str("a=recvonly\r\n");
+    }
+
+    sdp
+}
+
+async fn spawn_ffmpeg(ffmpeg: &Path, sdp: &str, output: &Path) -> Result<Child, BoxError> {
+    // The SDP is passed as a data URI so stdin stays free for the "q" that stops ffmpeg
+    let sdp_uri = format!("data:application/sdp;base64,{}", BASE64.encode(sdp));
+
+    let child = Command::new(ffmpeg)
+        .args([
+            "-loglevel",
+            "warning",
+            "-protocol_whitelist",
+            "data,udp,rtp",
+            "-fflags",
+            "+genpts",
+            "-f",
+            "sdp",
+            "-i",
+            &sdp_uri,
+            "-map",
+            "0",
+            "-c",
+            "copy",
+            "-y",
+        ])
+        .arg(output)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+    Ok(child)
+}
+
+/// Write packets arriving on `socket` in rtpdump format (`rtpplay`, Wireshark).
+/// Each packet carries its offset in milliseconds from the start of the file.
+async fn write_rtpdump(
+    socket: tokio::net::UdpSocket,
+    path: PathBuf,
+    mut stop: watch::Receiver<bool>,
+) {
+    let result: Result<(), BoxError> = async {
+        let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(&path).await?);
+        let local = socket.local_addr()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+
+        file.write_all(format!("#!rtpplay1.0 {}/{}\n", local.ip(), local.port()).as_bytes())
+            .await?;
+        file.write_u32(now.as_secs() as u32).await?;
+        file.write_u32(now.subsec_micros()).await?;
+        file.write_u32(u32::from(LOCALHOST)).await?;
+        file.write_u16(local.port()).await?;
+        file.write_u16(0).await?;
+
+        let started = Instant::now();
+        let mut buf = vec![0u8; 65536];
+        loop {
+            let len = tokio::select! {
+                len = socket.recv(&mut buf) => len?,
+                _ = stop.changed() => break,
+            };
+            let packet = &buf[..len];
+            // RTCP packet types are 200-204; rtpdump marks them with a zero RTP length
+            let is_rtcp = packet.len() > 1 && (200..=204).contains(&packet[1]);
+
+            file.write_u16((len + 8) as u16).await?;
+            file.write_u16(if is_rtcp { 0 } else { len as u16 }).await?;
+            file.write_u32(started.elapsed().as_millis() as u32).await?;
+            file.write_all(packet).await?;
+        }
+
+        file.flush().await?;
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!("RTP dump {} failed: {}", path.display(), e);
+    }
+}
+
+/// Even port with the next one free as well, for RTP and RTCP
+fn free_port_pair() -> Result<u16, BoxError> {
+    for _ in 0..50 {
+        let rtp = StdUdpSocket::bind((LOCALHOST, 0))?;
+        let port = rtp.local_addr()?.port();
+        if port % 2 == 0 && port < u16::MAX && StdUdpSocket::bind((LOCALHOST, port + 1)).is_ok() {
+            return Ok(port);
+        }
+    }
+    Err("No free UDP port pair for recording".into())
+}
+
+fn timestamp_name() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!("lesson-{}", secs)
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
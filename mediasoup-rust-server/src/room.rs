@@ -1,34 +1,128 @@
+use crate::chat::ChatHistory;
+use crate::layers::LayerController;
+use crate::recording::Recording;
+use crate::speakers::SpeakerObservers;
 use mediasoup::prelude::*;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use uuid::Uuid;
 
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Media a student may publish. The teacher can always produce.
+#[derive(Debug, Clone, Copy)]
+pub struct ProducePermissions {
+    /// Microphone; on until the teacher mutes the student
+    pub audio: bool,
+    /// Screen share
+    pub video: bool,
+}
+
+impl Default for ProducePermissions {
+    fn default() -> Self {
+        Self {
+            audio: true,
+            video: false,
+        }
+    }
+}
+
 /// Peer in a room
 #[derive(Debug)]
 pub struct Peer {
     pub id: String,
     pub name: String,
     pub is_teacher: bool,
+    /// Router this peer's transports live on: the room's main router or one on another worker
+    pub router: Router,
+    /// Lets a new connection take this peer over after a network drop
+    pub resume_token: String,
+    /// Bumped whenever the connection drops or comes back
+    connection_epoch: AtomicU64,
+    pub permissions: RwLock<ProducePermissions>,
+    pub hand_raised: RwLock<bool>,
+    pub present_requested: RwLock<bool>,
     pub send_transport: RwLock<Option<WebRtcTransport>>,
     pub recv_transport: RwLock<Option<WebRtcTransport>>,
     pub producers: RwLock<HashMap<ProducerId, Producer>>,
     pub consumers: RwLock<HashMap<ConsumerId, Consumer>>,
+    /// Layer state of simulcast/SVC consumers
+    pub consumer_layers: RwLock<HashMap<ConsumerId, Arc<Mutex<LayerController>>>>,
+    /// Teacher's data channels: annotations, pointer, polls
+    pub data_producers: RwLock<HashMap<DataProducerId, DataProducer>>,
+    pub data_consumers: RwLock<HashMap<DataConsumerId, DataConsumer>>,
 }
 
 impl Peer {
-    pub fn new(id: String, name: String, is_teacher: bool) -> Self {
+    pub fn new(id: String, name: String, is_teacher: bool, router: Router) -> Self {
         Self {
             id,
             name,
             is_teacher,
+            router,
+            resume_token: Uuid::new_v4().simple().to_string(),
+            connection_epoch: AtomicU64::new(0),
+            permissions: RwLock::new(ProducePermissions::default()),
+            hand_raised: RwLock::new(false),
+            present_requested: RwLock::new(false),
             send_transport: RwLock::new(None),
             recv_transport: RwLock::new(None),
             producers: RwLock::new(HashMap::new()),
             consumers: RwLock::new(HashMap::new()),
+            consumer_layers: RwLock::new(HashMap::new()),
+            data_producers: RwLock::new(HashMap::new()),
+            data_consumers: RwLock::new(HashMap::new()),
         }
     }
 
+    /// Record a lost connection; returns the epoch to check when the grace period ends
+    pub fn mark_disconnected(&self) -> u64 {
+        self.connection_epoch.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn mark_reconnected(&self) {
+        self.connection_epoch.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Whether the peer has not come back since `mark_disconnected` returned `epoch`
+    pub fn still_disconnected(&self, epoch: u64) -> bool {
+        self.connection_epoch.load(Ordering::SeqCst) == epoch
+    }
+
+    /// Whether this peer may create a producer of `kind`
+    pub fn can_produce(&self, kind: MediaKind) -> bool {
+        if self.is_teacher {
+            return true;
+        }
+        let permissions = self.permissions.read();
+        match kind {
+            MediaKind::Audio => permissions.audio,
+            MediaKind::Video => permissions.video,
+        }
+    }
+
+    pub fn producers_of_kind(&self, kind: MediaKind) -> Vec<Producer> {
+        self.producers
+            .read()
+            .values()
+            .filter(|p| p.kind() == kind)
+            .cloned()
+            .collect()
+    }
+
+    /// Remove this peer's producers of `kind`; they close once the returned handles drop
+    pub fn take_producers(&self, kind: MediaKind) -> Vec<Producer> {
+        let mut producers = self.producers.write();
+        let ids: Vec<ProducerId> = producers
+            .values()
+            .filter(|p| p.kind() == kind)
+            .map(|p| p.id())
+            .collect();
+        ids.iter().filter_map(|id| producers.remove(id)).collect()
+    }
+
     pub fn close(&self) {
         // Close all producers
         for producer in self.producers.write().drain() {
@@ -38,6 +132,9 @@ impl Peer {
         for consumer in self.consumers.write().drain() {
             drop(consumer);
         }
+        self.consumer_layers.write().clear();
+        self.data_producers.write().clear();
+        self.data_consumers.write().clear();
         // Close transports
         if let Some(transport) = self.send_transport.write().take() {
             drop(transport);
@@ -48,21 +145,55 @@ impl Peer {
     }
 }
 
+/// Router on another worker that takes the peers the main router has no room for
+struct ExtraRouter {
+    worker_index: usize,
+    router: Router,
+}
+
 /// Room containing peers
 pub struct Room {
     pub id: String,
+    /// Main router: the teacher, the observers, the recording and the first students
     pub router: Router,
+    /// Slot of the worker the router lives on
+    pub worker_index: usize,
+    extra_routers: RwLock<Vec<ExtraRouter>>,
+    /// Producers piped from their own router to another, keyed by the target router
+    pipes: tokio::sync::Mutex<HashMap<(ProducerId, RouterId), PipeProducerToRouterPair>>,
+    data_pipes:
+        tokio::sync::Mutex<HashMap<(DataProducerId, RouterId), PipeDataProducerToRouterPair>>,
     peers: RwLock<HashMap<String, Arc<Peer>>>,
     teacher_id: RwLock<Option<String>>,
+    /// Student allowed to share their screen
+    presenter_id: RwLock<Option<String>>,
+    /// Recording of the teacher's media, held across its async start and stop
+    pub recording: tokio::sync::Mutex<Option<Recording>>,
+    pub chat: Mutex<ChatHistory>,
+    pub speakers: SpeakerObservers,
 }
 
 impl Room {
-    pub fn new(router: Router, room_id: Option<String>) -> Self {
+    pub fn new(
+        router: Router,
+        room_id: Option<String>,
+        worker_index: usize,
+        chat_history_size: usize,
+        speakers: SpeakerObservers,
+    ) -> Self {
         Self {
             id: room_id.unwrap_or_else(|| Uuid::new_v4().to_string()),
             router,
+            worker_index,
+            extra_routers: RwLock::new(Vec::new()),
+            pipes: tokio::sync::Mutex::new(HashMap::new()),
+            data_pipes: tokio::sync::Mutex::new(HashMap::new()),
             peers: RwLock::new(HashMap::new()),
             teacher_id: RwLock::new(None),
+            presenter_id: RwLock::new(None),
+            recording: tokio::sync::Mutex::new(None),
+            chat: Mutex::new(ChatHistory::new(chat_history_size)),
+            speakers,
         }
     }
 
@@ -78,8 +209,154 @@ impl Room {
         self.teacher_id.read().is_some()
     }
 
-    pub fn add_peer(&self, id: String, name: String, is_teacher: bool) -> Arc<Peer> {
-        let peer = Arc::new(Peer::new(id.clone(), name.clone(), is_teacher));
+    /// First router, main one first, with fewer than `capacity` peers on it
+    pub fn router_with_space(&self, capacity: usize) -> Option<Router> {
+        let peers = self.peers.read();
+        let extra_routers = self.extra_routers.read();
+        std::iter::once(&self.router)
+            .chain(extra_routers.iter().map(|extra| &extra.router))
+            .find(|router| {
+                let on_router = peers.values().filter(|p| p.router.id() == router.id());
+                on_router.count() < capacity
+            })
+            .cloned()
+    }
+
+    pub fn add_router(&self, worker_index: usize, router: Router) {
+        self.extra_routers.write().push(ExtraRouter {
+            worker_index,
+            router,
+        });
+    }
+
+    /// Slot of the worker `router` lives on
+    pub fn worker_index_of(&self, router: &Router) -> usize {
+        self.extra_routers
+            .read()
+            .iter()
+            .find(|extra| extra.router.id() == router.id())
+            .map_or(self.worker_index, |extra| extra.worker_index)
+    }
+
+    /// Whether the room is being recorded; also true while a recording starts or stops
+    pub fn is_recording(&self) -> bool {
+        self.recording
+            .try_lock()
+            .map(|recording| recording.is_some())
+            .unwrap_or(true)
+    }
+
+    pub fn router_count(&self) -> usize {
+        1 + self.extra_routers.read().len()
+    }
+
+    /// Whether any of the room's routers lives on the worker in `index`
+    pub fn uses_worker(&self, index: usize) -> bool {
+        self.worker_index == index
+            || self
+                .extra_routers
+                .read()
+                .iter()
+                .any(|extra| extra.worker_index == index)
+    }
+
+    /// Load the room puts on each worker it uses: one per router plus its consumers
+    pub fn worker_loads(&self) -> Vec<(usize, usize)> {
+        let mut routers = vec![(self.worker_index, self.router.id())];
+        routers.extend(
+            self.extra_routers
+                .read()
+                .iter()
+                .map(|extra| (extra.worker_index, extra.router.id())),
+        );
+        let peers = self.get_all_peers();
+
+        routers
+            .into_iter()
+            .map(|(worker_index, router_id)| {
+                let consumers: usize = peers
+                    .iter()
+                    .filter(|p| p.router.id() == router_id)
+                    .map(|p| p.consumers.read().len())
+                    .sum();
+                (worker_index, 1 + consumers)
+            })
+            .collect()
+    }
+
+    /// Make a producer that lives on `origin` consumable on `target`. Each producer is
+    /// piped to a router once; mediasoup reuses the pipe transports between two routers.
+    pub async fn pipe_producer(
+        &self,
+        producer_id: ProducerId,
+        origin: &Router,
+        target: &Router,
+    ) -> Result<(), BoxError> {
+        if origin.id() == target.id() {
+            return Ok(());
+        }
+
+        let mut pipes = self.pipes.lock().await;
+        // Pipes close with their producer
+        pipes.retain(|_, pair| !pair.pipe_producer.closed());
+        if pipes.contains_key(&(producer_id, target.id())) {
+            return Ok(());
+        }
+
+        let pair = origin
+            .pipe_producer_to_router(producer_id, PipeToRouterOptions::new(target.clone()))
+            .await?;
+        tracing::debug!(
+            "[Room {}] Piped producer {} to router {}",
+            self.id,
+            producer_id,
+            target.id()
+        );
+        pipes.insert((producer_id, target.id()), pair);
+        Ok(())
+    }
+
+    /// Same as `pipe_producer`, for a data producer
+    pub async fn pipe_data_producer(
+        &self,
+        data_producer_id: DataProducerId,
+        origin: &Router,
+        target: &Router,
+    ) -> Result<(), BoxError> {
+        if origin.id() == target.id() {
+            return Ok(());
+        }
+
+        let mut pipes = self.data_pipes.lock().await;
+        pipes.retain(|_, pair| !pair.pipe_data_producer.closed());
+        if pipes.contains_key(&(data_producer_id, target.id())) {
+            return Ok(());
+        }
+
+        let pair = origin
+            .pipe_data_producer_to_router(
+                data_producer_id,
+                PipeToRouterOptions::new(target.clone()),
+            )
+            .await?;
+        tracing::debug!(
+            "[Room {}] Piped data producer {} to router {}",
+            self.id,
+            data_producer_id,
+            target.id()
+        );
+        pipes.insert((data_producer_id, target.id()), pair);
+        Ok(())
+    }
+
+    pub fn add_peer(
+        &self,
+        id: String,
+        name: String,
+        is_teacher: bool,
+        router: Router,
+    ) -> Arc<Peer> {
+        let peer = Arc::new(Peer::new(id.clone(), name.clone(), is_teacher, router));
         self.peers.write().insert(id.clone(), peer.clone());
 
         if is_teacher {
@@ -104,11 +381,15 @@ impl Room {
         let peer = self.peers.write().remove(id);
 
         if let Some(ref p) = peer {
+            for producer in p.producers_of_kind(MediaKind::Audio) {
+                self.speakers.forget(&producer.id());
+            }
             p.close();
 
             if p.is_teacher {
                 *self.teacher_id.write() = None;
             }
+            self.clear_presenter(id);
 
             tracing::info!("[Room {}] Peer left: {}", self.id, p.name);
         }
@@ -134,6 +415,30 @@ impl Room {
         self.peers.read().get(&teacher_id).cloned()
     }
 
+    pub fn presenter(&self) -> Option<Arc<Peer>> {
+        let presenter_id = self.presenter_id.read().clone()?;
+        self.peers.read().get(&presenter_id).cloned()
+    }
+
+    /// Make `peer_id` the presenter; returns the student who was presenting before
+    pub fn set_presenter(&self, peer_id: &str) -> Option<String> {
+        self.presenter_id
+            .write()
+            .replace(peer_id.to_string())
+            .filter(|previous| previous != peer_id)
+    }
+
+    /// Clear the presenter if it is `peer_id`; returns whether it was
+    pub fn clear_presenter(&self, peer_id: &str) -> bool {
+        let mut presenter_id = self.presenter_id.write();
+        if presenter_id.as_deref() == Some(peer_id) {
+            *presenter_id = None;
+            true
+        } else {
+            false
+        }
+    }
+
     /// Get teacher's producers
     pub fn teacher_producers(&self) -> Vec<Producer> {
         self.get_teacher()
@@ -151,6 +456,19 @@ impl Room {
         None
     }
 
+    /// Find data producer by ID across all peers
+    pub fn find_data_producer(
+        &self,
+        data_producer_id: &DataProducerId,
+    ) -> Option<(DataProducer, Arc<Peer>)> {
+        for peer in self.peers.read().values() {
+            if let Some(data_producer) = peer.data_producers.read().get(data_producer_id) {
+                return Some((data_producer.clone(), peer.clone()));
+            }
+        }
+        None
+    }
+
     pub fn is_empty(&self) -> bool {
         self.peers.read().is_empty()
     }
@@ -159,6 +477,13 @@ impl Room {
         for peer in self.peers.write().drain() {
             peer.1.close();
         }
+        if let Ok(mut pipes) = self.pipes.try_lock() {
+            pipes.clear();
+        }
+        if let Ok(mut data_pipes) = self.data_pipes.try_lock() {
+            data_pipes.clear();
+        }
+        self.extra_routers.write().clear();
         tracing::info!("[Room {}] Closed", self.id);
     }
 }
@@ -1,11 +1,16 @@
+use crate::auth;
+use crate::layers::{self, LayerController};
 use crate::manager::MediasoupManager;
 use crate::messages::*;
+use crate::recording::Recording;
+use crate::room::{Peer, Room};
 use futures_util::{SinkExt, StreamExt};
 use mediasoup::prelude::*;
 use parking_lot::RwLock;
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 use tokio_tungstenite::{accept_async, tungstenite::Message};
@@ -33,12 +38,33 @@ impl SignalingServer {
         }
     }
 
+    /// Handle for closing rooms and removing peers from outside a connection
+    pub fn room_control(&self) -> RoomControl {
+        RoomControl {
+            manager: self.manager.clone(),
+            clients: self.clients.clone(),
+        }
+    }
+
     /// Start the signaling server
     pub async fn run(&self, port: u16) -> Result<(), Box<dyn std::error::Error>> {
         let addr = format!("0.0.0.0:{}", port);
         let listener = TcpListener::bind(&addr).await?;
         tracing::info!("Signaling server listening on port {}", port);
 
+        self.serve(listener).await
+    }
+
+    /// Accept clients on an already bound listener, e.g. an ephemeral port in tests
+    pub async fn serve(&self, listener: TcpListener) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(deaths) = self.manager.take_worker_deaths() {
+            tokio::spawn(recover_workers(
+                self.manager.clone(),
+                self.clients.clone(),
+                deaths,
+            ));
+        }
+
         loop {
             let (stream, addr) = listener.accept().await?;
             tracing::info!("New WebSocket connection from {}", addr);
@@ -55,6 +81,47 @@ impl SignalingServer {
     }
 }
 
+/// Room management for the admin API. Peers it removes are told they were kicked
+/// and disconnected, without a grace period.
+#[derive(Clone)]
+pub struct RoomControl {
+    manager: Arc<MediasoupManager>,
+    clients: Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+}
+
+impl RoomControl {
+    /// Close a room and disconnect everyone in it; false if there is no such room
+    pub async fn close_room(&self, room_id: &str, reason: Option<String>) -> bool {
+        let Some(room) = self.manager.get_room(room_id) else {
+            return false;
+        };
+
+        stop_recording(&room, &self.clients).await;
+        for peer in room.get_all_peers() {
+            disconnect_kicked(&self.clients, room_id, &peer.id, reason.clone());
+        }
+        self.manager.remove_room(room_id);
+
+        tracing::info!("[Room {}] Closed by admin", room_id);
+        true
+    }
+
+    /// Remove one peer from a room; false if it is not there
+    pub async fn kick_peer(&self, room_id: &str, peer_id: &str, reason: Option<String>) -> bool {
+        let Some(room) = self.manager.get_room(room_id) else {
+            return false;
+        };
+        let Some(peer) = room.get_peer(peer_id) else {
+            return false;
+        };
+
+        disconnect_kicked(&self.clients, room_id, peer_id, reason);
+        tracing::info!("[Room {}] Admin removed {}", room_id, peer.name);
+        leave_room(room_id, peer_id, &self.manager, &self.clients).await;
+        true
+    }
+}
+
 async fn handle_connection(
     stream: TcpStream,
     addr: SocketAddr,
@@ -77,7 +144,14 @@ async fn handle_connection(
 
     // Handle incoming messages
     while let Some(msg) = ws_receiver.next().await {
-        let msg = msg?;
+        // A reset connection still has to go through disconnect handling
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(e) => {
+                tracing::warn!("WebSocket error from {}: {}", addr, e);
+                break;
+            }
+        };
 
         if let Message::Text(text) = msg {
             match serde_json::from_str::<ClientMessage>(&text) {
@@ -164,12 +238,57 @@ async fn handle_message(
         ClientMessage::ResumeConsumer { data } => {
             handle_resume_consumer(addr, data, manager, clients, tx).await?;
         }
+        ClientMessage::SetPreferredLayers { data } => {
+            handle_set_preferred_layers(addr, data, manager, clients, tx).await?;
+        }
+        ClientMessage::ResumeProducer { data } => {
+            handle_resume_producer(addr, data, manager, clients).await?;
+        }
         ClientMessage::GetProducers { .. } => {
             handle_get_producers(addr, manager, clients, tx)?;
         }
         ClientMessage::ChatMessage { data } => {
             handle_chat_message(addr, data, manager, clients)?;
         }
+        ClientMessage::RaiseHand { data } => {
+            handle_raise_hand(addr, data, manager, clients)?;
+        }
+        ClientMessage::MutePeer { data } => {
+            handle_mute_peer(addr, data, manager, clients).await?;
+        }
+        ClientMessage::GrantFloor { data } => {
+            handle_grant_floor(addr, data, manager, clients).await?;
+        }
+        ClientMessage::KickPeer { data } => {
+            handle_kick_peer(addr, data, manager, clients).await?;
+        }
+        ClientMessage::RequestPresent { data } => {
+            handle_request_present(addr, data, manager, clients)?;
+        }
+        ClientMessage::ApprovePresent { data } => {
+            handle_approve_present(addr, data, manager, clients)?;
+        }
+        ClientMessage::RevokePresent { data } => {
+            handle_revoke_present(addr, data, manager, clients)?;
+        }
+        ClientMessage::StartRecording { .. } => {
+            handle_start_recording(addr, manager, clients).await?;
+        }
+        ClientMessage::StopRecording { .. } => {
+            handle_stop_recording(addr, manager, clients).await?;
+        }
+        ClientMessage::ProduceData { data } => {
+            handle_produce_data(addr, data, manager, clients, tx).await?;
+        }
+        ClientMessage::ConsumeData { data } => {
+            handle_consume_data(addr, data, manager, clients, tx).await?;
+        }
+        ClientMessage::CloseDataProducer { data } => {
+            handle_close_data_producer(addr, data, manager, clients)?;
+        }
+        ClientMessage::GetDataProducers { .. } => {
+            handle_get_data_producers(addr, manager, clients, tx)?;
+        }
     }
     Ok(())
 }
@@ -181,10 +300,37 @@ async fn handle_join(
     clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
     tx: &Tx,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // A peer coming back within the grace period keeps its transports, producers and consumers
+    if let Some(token) = data.resume_token.as_deref().filter(|t| !t.is_empty()) {
+        let existing = manager
+            .get_room(&data.room_id)
+            .and_then(|room| room.get_peer(&data.peer_id).map(|peer| (room, peer)));
+        if let Some((room, peer)) = existing {
+            if !auth::constant_time_eq(token.as_bytes(), peer.resume_token.as_bytes()) {
+                send_error(tx, "Invalid resume token");
+                return Ok(());
+            }
+            resume_peer(addr, &room, &peer, clients, tx);
+            return Ok(());
+        }
+        // Grace period over: join again from scratch with the auth sent alongside the token
+    }
+
+    // Verify before creating anything; the role comes from the token, not the client
+    let is_teacher = match manager.room_auth().authorize(&data, auth::now_secs()) {
+        Ok(is_teacher) => is_teacher,
+        Err(e) => {
+            tracing::warn!("Join to room {} rejected for {}: {}", data.room_id, addr, e);
+            send_error(tx, &e);
+            return Ok(());
+        }
+    };
+
     let room = manager.get_or_create_room(&data.room_id).await?;
+    watch_speakers(&room, manager, clients);
 
     // Check if room already has teacher
-    if data.is_teacher && room.has_teacher() {
+    if is_teacher && room.has_teacher() {
         send_error(tx, "Room already has a teacher");
         return Ok(());
     }
@@ -195,7 +341,14 @@ async fn handle_join(
         return Ok(());
     }
 
-    room.add_peer(data.peer_id.clone(), data.name.clone(), data.is_teacher);
+    // Don't let a second connection take over an existing peer without its resume token
+    if room.get_peer(&data.peer_id).is_some() {
+        send_error(tx, "Peer id already in use");
+        return Ok(());
+    }
+
+    let router = manager.router_for_peer(&room, is_teacher).await?;
+    let peer = room.add_peer(data.peer_id.clone(), data.name.clone(), is_teacher, router);
 
     // Store client info
     clients.write().insert(
@@ -213,8 +366,11 @@ async fn handle_join(
         &ServerMessage::Joined(JoinedData {
             room_id: room.id.clone(),
             peer_id: data.peer_id.clone(),
-            is_teacher: data.is_teacher,
+            is_teacher,
             rtp_capabilities: room.rtp_capabilities(),
+            resume_token: peer.resume_token.clone(),
+            resumed: false,
+            chat_history: room.chat.lock().visible_to(&peer.id),
         }),
     );
 
@@ -225,7 +381,7 @@ async fn handle_join(
         &ServerMessage::PeerJoined(PeerJoinedData {
             peer_id: data.peer_id.clone(),
             name: data.name.clone(),
-            is_teacher: data.is_teacher,
+            is_teacher,
         }),
         Some(addr),
     );
@@ -234,12 +390,61 @@ async fn handle_join(
         "Peer {} joined room {} as {}",
         data.name,
         data.room_id,
-        if data.is_teacher { "Teacher" } else { "Student" }
+        if is_teacher { "Teacher" } else { "Student" }
     );
 
     Ok(())
 }
 
+/// Attach a new connection to a peer that kept its media during the grace period
+fn resume_peer(
+    addr: SocketAddr,
+    room: &Room,
+    peer: &Peer,
+    clients: &RwLock<HashMap<SocketAddr, ClientInfo>>,
+    tx: &Tx,
+) {
+    {
+        let mut clients = clients.write();
+        // The old socket may not have noticed the drop yet
+        let stale: Vec<SocketAddr> = clients
+            .iter()
+            .filter(|(_, info)| info.room_id == room.id && info.peer_id == peer.id)
+            .map(|(addr, _)| *addr)
+            .collect();
+        for stale_addr in stale {
+            if let Some(info) = clients.remove(&stale_addr) {
+                let _ = info.tx.send(Message::Close(None));
+            }
+        }
+
+        clients.insert(
+            addr,
+            ClientInfo {
+                peer_id: peer.id.clone(),
+                room_id: room.id.clone(),
+                tx: tx.clone(),
+            },
+        );
+    }
+    peer.mark_reconnected();
+
+    send_message(
+        tx,
+        &ServerMessage::Joined(JoinedData {
+            room_id: room.id.clone(),
+            peer_id: peer.id.clone(),
+            is_teacher: peer.is_teacher,
+            rtp_capabilities: room.rtp_capabilities(),
+            resume_token: peer.resume_token.clone(),
+            resumed: true,
+            chat_history: room.chat.lock().visible_to(&peer.id),
+        }),
+    );
+
+    tracing::info!("Peer {} resumed its session in room {}", peer.name, room.id);
+}
+
 fn handle_get_router_rtp_capabilities(
     addr: SocketAddr,
     manager: &Arc<MediasoupManager>,
@@ -275,7 +480,10 @@ async fn handle_create_transport(
     let room = manager.get_room(&room_id).ok_or("Room not found")?;
     let peer = room.get_peer(&peer_id).ok_or("Peer not found")?;
 
-    let (transport, params) = manager.create_webrtc_transport(&room).await?;
+    let worker_index = room.worker_index_of(&peer.router);
+    let (transport, params) = manager
+        .create_webrtc_transport(&peer.router, worker_index)
+        .await?;
 
     match data.direction {
         TransportDirection::Send => {
@@ -294,6 +502,7 @@ async fn handle_create_transport(
             ice_parameters: params.ice_parameters,
             ice_candidates: params.ice_candidates,
             dtls_parameters: params.dtls_parameters,
+            sctp_parameters: params.sctp_parameters,
         }),
     );
 
@@ -350,9 +559,13 @@ async fn handle_produce(
     let room = manager.get_room(&room_id).ok_or("Room not found")?;
     let peer = room.get_peer(&peer_id).ok_or("Peer not found")?;
 
-    // Only teacher can produce video
-    if !peer.is_teacher && data.kind == MediaKind::Video {
-        send_error(tx, "Only teacher can share screen");
+    // Students need the teacher's permission for each kind of media
+    if !peer.can_produce(data.kind) {
+        let message = match data.kind {
+            MediaKind::Video => "Screen sharing needs teacher approval",
+            MediaKind::Audio => "Microphone muted by teacher",
+        };
+        send_error(tx, message);
         return Ok(());
     }
 
@@ -365,6 +578,21 @@ async fn handle_produce(
     let producer =
         MediasoupManager::create_producer(&transport, data.kind, data.rtp_parameters).await?;
 
+    // Let the teacher see which students are talking. The observers are on the main
+    // router, so audio from students on other routers is piped there first.
+    if !peer.is_teacher && data.kind == MediaKind::Audio {
+        let observed = match room
+            .pipe_producer(producer.id(), &peer.router, &room.router)
+            .await
+        {
+            Ok(()) => room.speakers.add_producer(&producer).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = observed {
+            tracing::warn!("Failed to observe audio of {}: {}", peer.name, e);
+        }
+    }
+
     let producer_id = producer.id().to_string();
     peer.producers.write().insert(producer.id(), producer);
 
@@ -387,6 +615,16 @@ async fn handle_produce(
         // Teacher produced - notify all students
         broadcast_to_room(clients, &room_id, &new_producer_msg, Some(addr));
         tracing::info!("Teacher produced {:?}: {}", data.kind, producer_id);
+
+        if room.is_recording() {
+            let manager = Arc::clone(manager);
+            let clients = Arc::clone(clients);
+            tokio::spawn(async move { restart_recording(&room, &manager, &clients).await });
+        }
+    } else if data.kind == MediaKind::Video {
+        // Approved student presentation - the whole room watches
+        broadcast_to_room(clients, &room_id, &new_producer_msg, Some(addr));
+        tracing::info!("Student {} is presenting: {}", peer.name, producer_id);
     } else if data.kind == MediaKind::Audio {
         // Student produced audio - notify teacher only
         let clients_read = clients.read();
@@ -429,34 +667,48 @@ async fn handle_consume(
         .find_producer(&producer_id)
         .ok_or("Producer not found")?;
 
-    // Students can only consume from teacher
-    if !peer.is_teacher && !producer_peer.is_teacher {
+    // Students can only consume from teacher, or the screen of another student presenting.
+    // Student video only exists while the teacher allows it.
+    let is_presentation = !producer_peer.is_teacher && producer.kind() == MediaKind::Video;
+    if !peer.is_teacher
+        && !producer_peer.is_teacher
+        && (!is_presentation || producer_peer.id == peer.id)
+    {
         send_error(tx, "Students can only consume from teacher");
         return Ok(());
     }
 
-    // Teacher can only consume audio from students
+    // Teacher consumes student audio and presentations
     if peer.is_teacher && producer_peer.is_teacher {
         send_error(tx, "Teacher cannot consume from self");
         return Ok(());
     }
 
-    if peer.is_teacher && producer.kind() != MediaKind::Audio {
-        send_error(tx, "Teacher can only consume audio from students");
-        return Ok(());
-    }
-
     let recv_transport = peer
         .recv_transport
         .read()
         .clone()
         .ok_or("Recv transport not found")?;
 
+    // Simulcast/SVC video: start at the layer the client asked for and adapt to its link
+    let controller = layers::producer_layers(&producer).map(|(spatial, temporal)| {
+        let mut controller = LayerController::new(spatial, temporal);
+        if let Some(layer) = data.preferred_spatial_layer {
+            controller.request(layer, None);
+        }
+        controller
+    });
+
+    // Large classes: the producer may live on another router of the room
+    room.pipe_producer(producer.id(), &producer_peer.router, &peer.router)
+        .await?;
+
     let consumer = MediasoupManager::create_consumer(
-        &room,
+        &peer.router,
         &recv_transport,
         &producer,
         &data.rtp_capabilities,
+        controller.as_ref().map(|c| c.current()),
     )
     .await?;
 
@@ -465,6 +717,30 @@ async fn handle_consume(
         let rtp_parameters = consumer.rtp_parameters().clone();
         let kind = consumer.kind();
 
+        if let Some(controller) = controller {
+            let controller = Arc::new(parking_lot::Mutex::new(controller));
+            let notify_tx = tx.clone();
+            let notify_id = consumer_id.clone();
+            layers::watch_consumer(
+                &consumer,
+                Arc::clone(&controller),
+                manager.layer_policy(),
+                move |layers| {
+                    send_message(
+                        &notify_tx,
+                        &ServerMessage::ConsumerLayersChanged(ConsumerLayersData {
+                            consumer_id: notify_id.clone(),
+                            spatial_layer: layers.spatial_layer,
+                            temporal_layer: layers.temporal_layer,
+                            automatic: true,
+                        }),
+                    );
+                },
+            );
+            peer.consumer_layers
+                .write()
+                .insert(consumer.id(), controller);
+        }
         peer.consumers.write().insert(consumer.id(), consumer);
 
         send_message(
@@ -519,6 +795,85 @@ async fn handle_resume_consumer(
     Ok(())
 }
 
+async fn handle_set_preferred_layers(
+    addr: SocketAddr,
+    data: SetPreferredLayersData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+    tx: &Tx,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (_room, peer) = joined_peer(addr, manager, clients)?;
+
+    let consumer_id: ConsumerId = data.consumer_id.parse()?;
+    let consumer = peer
+        .consumers
+        .read()
+        .get(&consumer_id)
+        .cloned()
+        .ok_or("Consumer not found")?;
+    let controller = peer
+        .consumer_layers
+        .read()
+        .get(&consumer_id)
+        .cloned()
+        .ok_or("Consumer has a single layer")?;
+
+    // Clamped to the layers the producer actually sends
+    let layers = controller
+        .lock()
+        .request(data.spatial_layer, data.temporal_layer);
+    consumer.set_preferred_layers(layers).await?;
+
+    send_message(
+        tx,
+        &ServerMessage::ConsumerLayersChanged(ConsumerLayersData {
+            consumer_id: data.consumer_id,
+            spatial_layer: layers.spatial_layer,
+            temporal_layer: layers.temporal_layer,
+            automatic: false,
+        }),
+    );
+
+    Ok(())
+}
+
+/// Unpause the sender's own producer, e.g. a microphone paused after a long silence
+async fn handle_resume_producer(
+    addr: SocketAddr,
+    data: ResumeProducerData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, peer) = joined_peer(addr, manager, clients)?;
+
+    let producer_id: ProducerId = data.producer_id.parse()?;
+    let producer = peer
+        .producers
+        .read()
+        .get(&producer_id)
+        .cloned()
+        .ok_or("Producer not found")?;
+
+    // A mic the teacher muted stays paused until the floor is given back
+    if !peer.can_produce(producer.kind()) {
+        return Err("Microphone muted by teacher".into());
+    }
+
+    producer.resume().await?;
+    room.speakers.mark_heard(producer_id);
+
+    let message = ServerMessage::ProducerResumed(ProducerResumedData {
+        producer_id: data.producer_id,
+        peer_id: peer.id.clone(),
+    });
+    send_to_peer(clients, &room.id, &peer.id, &message);
+    if let Some(teacher) = room.get_teacher().filter(|t| t.id != peer.id) {
+        send_to_peer(clients, &room.id, &teacher.id, &message);
+    }
+
+    Ok(())
+}
+
 fn handle_get_producers(
     addr: SocketAddr,
     manager: &Arc<MediasoupManager>,
@@ -537,7 +892,7 @@ fn handle_get_producers(
     let mut producers = Vec::new();
 
     if peer.is_teacher {
-        // Teacher gets teacher's own + student audio
+        // Teacher gets teacher's own + student audio and presentations
         for p in room.teacher_producers() {
             producers.push(ProducerInfo {
                 producer_id: p.id().to_string(),
@@ -545,20 +900,18 @@ fn handle_get_producers(
                 peer_id: peer_id.clone(),
             });
         }
-        // Add student audio producers
+        // Add student audio and presentation producers
         for student in room.get_students() {
             for p in student.producers.read().values() {
-                if p.kind() == MediaKind::Audio {
-                    producers.push(ProducerInfo {
-                        producer_id: p.id().to_string(),
-                        kind: p.kind(),
-                        peer_id: student.id.clone(),
-                    });
-                }
+                producers.push(ProducerInfo {
+                    producer_id: p.id().to_string(),
+                    kind: p.kind(),
+                    peer_id: student.id.clone(),
+                });
             }
         }
     } else {
-        // Students only get teacher's producers
+        // Students get teacher's producers
         if let Some(teacher) = room.get_teacher() {
             for p in room.teacher_producers() {
                 producers.push(ProducerInfo {
@@ -568,6 +921,16 @@ fn handle_get_producers(
                 });
             }
         }
+        // ...and the screen of the student presenting
+        if let Some(presenter) = room.presenter().filter(|p| p.id != peer_id) {
+            for p in presenter.producers_of_kind(MediaKind::Video) {
+                producers.push(ProducerInfo {
+                    producer_id: p.id().to_string(),
+                    kind: p.kind(),
+                    peer_id: presenter.id.clone(),
+                });
+            }
+        }
     }
 
     send_message(tx, &ServerMessage::Producers(producers));
@@ -590,55 +953,932 @@ fn handle_chat_message(
     let room = manager.get_room(&room_id).ok_or("Room not found")?;
     let peer = room.get_peer(&peer_id).ok_or("Peer not found")?;
 
-    broadcast_to_room(
-        clients,
-        &room_id,
-        &ServerMessage::ChatMessage(ChatMessageBroadcast {
+    // Private messages go between the teacher and one student
+    let receiver = match data.to.as_deref().filter(|to| !to.is_empty()) {
+        Some(to) => {
+            let receiver = room.get_peer(to).ok_or("Peer not found")?;
+            if receiver.id == peer.id {
+                return Err("Cannot message yourself".into());
+            }
+            if !peer.is_teacher && !receiver.is_teacher {
+                return Err("Students can only message the teacher privately".into());
+            }
+            Some(receiver)
+        }
+        None => None,
+    };
+
+    let message = {
+        let mut chat = room.chat.lock();
+        let message = ChatMessageBroadcast {
+            id: chat.next_id(),
             sender_id: peer_id,
             sender_name: peer.name.clone(),
-            content: data.content.clone(),
+            content: data.content,
             timestamp: data.timestamp,
             is_teacher: peer.is_teacher,
-        }),
-        Some(addr),
-    );
+            message_type: if receiver.is_some() {
+                ChatMessageType::Direct
+            } else {
+                ChatMessageType::All
+            },
+            receiver_id: receiver.as_ref().map(|r| r.id.clone()),
+        };
+        chat.push(message.clone());
+        message
+    };
+
+    match &receiver {
+        // Kept in history if the receiver is between connections
+        Some(receiver) => send_to_peer(
+            clients,
+            &room_id,
+            &receiver.id,
+            &ServerMessage::ChatMessage(message.clone()),
+        ),
+        None => broadcast_to_room(
+            clients,
+            &room_id,
+            &ServerMessage::ChatMessage(message.clone()),
+            Some(addr),
+        ),
+    }
 
-    tracing::info!("[Chat] {}: {}", peer.name, data.content);
+    if let Some(sink) = manager.chat_sink() {
+        sink.store(&room_id, &message);
+    }
+
+    match &receiver {
+        // Private questions stay out of the server log
+        Some(receiver) => tracing::info!("[Chat] {} -> {} (private)", peer.name, receiver.name),
+        None => tracing::info!("[Chat] {}: {}", peer.name, message.content),
+    }
 
     Ok(())
 }
 
-async fn handle_disconnect(
+/// Room and peer of a joined client
+fn joined_peer(
+    addr: SocketAddr,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(Arc<Room>, Arc<Peer>), Box<dyn std::error::Error + Send + Sync>> {
+    let (room_id, peer_id) = {
+        let clients = clients.read();
+        let info = clients.get(&addr).ok_or("Not joined")?;
+        (info.room_id.clone(), info.peer_id.clone())
+    };
+
+    let room = manager.get_room(&room_id).ok_or("Room not found")?;
+    let peer = room.get_peer(&peer_id).ok_or("Peer not found")?;
+    Ok((room, peer))
+}
+
+/// Room of a teacher's moderation request and the student it targets
+fn moderation_target(
     addr: SocketAddr,
+    target_id: &str,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(Arc<Room>, Arc<Peer>), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, peer) = joined_peer(addr, manager, clients)?;
+    if !peer.is_teacher {
+        return Err("Only teacher can moderate".into());
+    }
+
+    let target = room.get_peer(target_id).ok_or("Peer not found")?;
+    if target.is_teacher {
+        return Err("Cannot moderate the teacher".into());
+    }
+    Ok((room, target))
+}
+
+/// Send a message to one peer of a room
+fn send_to_peer(
+    clients: &RwLock<HashMap<SocketAddr, ClientInfo>>,
+    room_id: &str,
+    peer_id: &str,
+    message: &ServerMessage,
+) {
+    let clients = clients.read();
+    if let Some(info) = clients
+        .values()
+        .find(|info| info.room_id == room_id && info.peer_id == peer_id)
+    {
+        send_message(&info.tx, message);
+    }
+}
+
+/// Send a message to the teacher of a room, if there is one
+fn send_to_teacher(
+    clients: &RwLock<HashMap<SocketAddr, ClientInfo>>,
+    room: &Room,
+    message: &ServerMessage,
+) {
+    if let Some(teacher) = room.get_teacher() {
+        send_to_peer(clients, &room.id, &teacher.id, message);
+    }
+}
+
+/// Forward the room's speaker events to the teacher and start pausing silent
+/// microphones when configured. Does nothing after the first call for a room.
+fn watch_speakers(
+    room: &Arc<Room>,
     manager: &Arc<MediasoupManager>,
     clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
 ) {
-    let info = clients.write().remove(&addr);
+    if !room.speakers.start_watching() {
+        return;
+    }
+
+    // The observers belong to the room, so their callbacks only hold it weakly
+    let (weak_room, clients_ref) = (Arc::downgrade(room), Arc::clone(clients));
+    room.speakers.on_volumes(move |volumes| {
+        let Some(room) = weak_room.upgrade() else {
+            return;
+        };
+        let levels = volumes
+            .iter()
+            .filter_map(|(producer_id, volume)| {
+                room.speakers.mark_heard(*producer_id);
+                let (_, peer) = room.find_producer(producer_id)?;
+                Some(AudioLevel {
+                    peer_id: peer.id.clone(),
+                    producer_id: producer_id.to_string(),
+                    volume: *volume,
+                })
+            })
+            .collect();
+        send_to_teacher(
+            &clients_ref,
+            &room,
+            &ServerMessage::AudioLevels(AudioLevelsData { levels }),
+        );
+    });
+
+    let (weak_room, clients_ref) = (Arc::downgrade(room), Arc::clone(clients));
+    room.speakers.on_silence(move || {
+        if let Some(room) = weak_room.upgrade() {
+            send_to_teacher(
+                &clients_ref,
+                &room,
+                &ServerMessage::AudioLevels(AudioLevelsData { levels: Vec::new() }),
+            );
+        }
+    });
+
+    let (weak_room, clients_ref) = (Arc::downgrade(room), Arc::clone(clients));
+    room.speakers.on_dominant_speaker(move |producer_id| {
+        let Some(room) = weak_room.upgrade() else {
+            return;
+        };
+        if let Some((_, peer)) = room.find_producer(&producer_id) {
+            send_to_teacher(
+                &clients_ref,
+                &room,
+                &ServerMessage::ActiveSpeaker(ActiveSpeakerData {
+                    peer_id: peer.id.clone(),
+                    producer_id: producer_id.to_string(),
+                }),
+            );
+        }
+    });
+
+    if let Some(after) = manager.auto_pause_silent() {
+        tokio::spawn(pause_silent_microphones(
+            Arc::downgrade(room),
+            Arc::clone(clients),
+            after,
+        ));
+    }
+}
+
+/// Pause student microphones nobody has heard for `after`, until the room closes.
+/// The student gets `producerPaused` and resumes with `resumeProducer`.
+async fn pause_silent_microphones(
+    room: Weak<Room>,
+    clients: Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+    after: Duration,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    loop {
+        interval.tick().await;
+        let Some(room) = room.upgrade() else {
+            return;
+        };
+
+        for student in room.get_students() {
+            for producer in student.producers_of_kind(MediaKind::Audio) {
+                let silent = room
+                    .speakers
+                    .silent_for(&producer.id())
+                    .is_some_and(|silent| silent >= after);
+                if producer.paused() || !silent {
+                    continue;
+                }
+                if let Err(e) = producer.pause().await {
+                    tracing::warn!(
+                        "Failed to pause silent microphone of {}: {}",
+                        student.name,
+                        e
+                    );
+                    continue;
+                }
+
+                let message = ServerMessage::ProducerPaused(ProducerPausedData {
+                    producer_id: producer.id().to_string(),
+                    peer_id: student.id.clone(),
+                    reason: "silence".to_string(),
+                });
+                send_to_peer(&clients, &room.id, &student.id, &message);
+                send_to_teacher(&clients, &room, &message);
+                tracing::info!(
+                    "[Room {}] Paused microphone of {} after {:?} of silence",
+                    room.id,
+                    student.name,
+                    after
+                );
+            }
+        }
+    }
+}
+
+/// Tell a student and the teacher what the student may publish now
+fn notify_permissions(clients: &RwLock<HashMap<SocketAddr, ClientInfo>>, room: &Room, peer: &Peer) {
+    let permissions = *peer.permissions.read();
+    let message = ServerMessage::PermissionsChanged(PermissionsData {
+        peer_id: peer.id.clone(),
+        audio: permissions.audio,
+        video: permissions.video,
+    });
+
+    send_to_peer(clients, &room.id, &peer.id, &message);
+    if let Some(teacher) = room.get_teacher() {
+        send_to_peer(clients, &room.id, &teacher.id, &message);
+    }
+}
 
-    if let Some(info) = info {
-        if let Some(room) = manager.get_room(&info.room_id) {
-            let peer = room.get_peer(&info.peer_id);
-            let was_teacher = peer.as_ref().map(|p| p.is_teacher).unwrap_or(false);
+fn handle_raise_hand(
+    addr: SocketAddr,
+    data: RaiseHandData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, peer) = joined_peer(addr, manager, clients)?;
+    if peer.is_teacher {
+        return Err("Teacher cannot raise hand".into());
+    }
+
+    *peer.hand_raised.write() = data.raised;
+
+    if let Some(teacher) = room.get_teacher() {
+        send_to_peer(
+            clients,
+            &room.id,
+            &teacher.id,
+            &ServerMessage::HandRaised(HandRaisedData {
+                peer_id: peer.id.clone(),
+                name: peer.name.clone(),
+                raised: data.raised,
+            }),
+        );
+    }
+
+    tracing::info!(
+        "[Room {}] {} {} hand",
+        room.id,
+        peer.name,
+        if data.raised { "raised" } else { "lowered" }
+    );
+
+    Ok(())
+}
 
-            room.remove_peer(&info.peer_id);
+async fn handle_mute_peer(
+    addr: SocketAddr,
+    data: MutePeerData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, target) = moderation_target(addr, &data.peer_id, manager, clients)?;
 
-            // Notify others
+    // Revoke first so the student cannot produce a new mic while we close the old one
+    target.permissions.write().audio = false;
+
+    if data.close {
+        for producer in target.take_producers(MediaKind::Audio) {
+            room.speakers.forget(&producer.id());
             broadcast_to_room(
                 clients,
-                &info.room_id,
-                &ServerMessage::PeerLeft(PeerLeftData {
-                    peer_id: info.peer_id.clone(),
-                    was_teacher,
+                &room.id,
+                &ServerMessage::ProducerClosed(ProducerClosedData {
+                    producer_id: producer.id().to_string(),
+                    peer_id: target.id.clone(),
                 }),
                 None,
             );
-
-            // Clean up empty room
-            if room.is_empty() {
-                manager.remove_room(&info.room_id);
-            }
         }
+    } else {
+        for producer in target.producers_of_kind(MediaKind::Audio) {
+            producer.pause().await?;
+        }
+    }
+
+    notify_permissions(clients, &room, &target);
+    tracing::info!("[Room {}] Teacher muted {}", room.id, target.name);
+
+    Ok(())
+}
+
+async fn handle_grant_floor(
+    addr: SocketAddr,
+    data: PeerTargetData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, target) = moderation_target(addr, &data.peer_id, manager, clients)?;
+
+    target.permissions.write().audio = true;
+    *target.hand_raised.write() = false;
+
+    // A mic paused by an earlier mute comes back; a closed one is produced again by the client
+    for producer in target.producers_of_kind(MediaKind::Audio) {
+        producer.resume().await?;
+        room.speakers.mark_heard(producer.id());
+    }
+
+    notify_permissions(clients, &room, &target);
+    tracing::info!(
+        "[Room {}] Teacher gave the floor to {}",
+        room.id,
+        target.name
+    );
+
+    Ok(())
+}
+
+async fn handle_kick_peer(
+    addr: SocketAddr,
+    data: KickPeerData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, target) = moderation_target(addr, &data.peer_id, manager, clients)?;
+
+    disconnect_kicked(clients, &room.id, &target.id, data.reason);
+    tracing::info!("[Room {}] Teacher removed {}", room.id, target.name);
+
+    // No grace period: clean up now, even if the student is between connections
+    leave_room(&room.id, &target.id, manager, clients).await;
+
+    Ok(())
+}
+
+/// Tell a peer it was removed and close its connection. Dropping it from `clients`
+/// first keeps the disconnect from starting a grace period.
+fn disconnect_kicked(
+    clients: &RwLock<HashMap<SocketAddr, ClientInfo>>,
+    room_id: &str,
+    peer_id: &str,
+    reason: Option<String>,
+) {
+    let mut clients = clients.write();
+    let addr = clients
+        .iter()
+        .find(|(_, info)| info.room_id == room_id && info.peer_id == peer_id)
+        .map(|(addr, _)| *addr);
+    if let Some(info) = addr.and_then(|addr| clients.remove(&addr)) {
+        send_message(&info.tx, &ServerMessage::Kicked(KickedData { reason }));
+        let _ = info.tx.send(Message::Close(None));
+    }
+}
+
+fn handle_request_present(
+    addr: SocketAddr,
+    data: RequestPresentData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, peer) = joined_peer(addr, manager, clients)?;
+    if peer.is_teacher {
+        return Err("Teacher can always present".into());
+    }
+
+    *peer.present_requested.write() = data.requested;
+
+    if let Some(teacher) = room.get_teacher() {
+        send_to_peer(
+            clients,
+            &room.id,
+            &teacher.id,
+            &ServerMessage::PresentRequested(PresentRequestedData {
+                peer_id: peer.id.clone(),
+                name: peer.name.clone(),
+                requested: data.requested,
+            }),
+        );
+    }
+
+    tracing::info!(
+        "[Room {}] {} {} to present",
+        room.id,
+        peer.name,
+        if data.requested {
+            "asked"
+        } else {
+            "no longer asks"
+        }
+    );
+
+    Ok(())
+}
+
+fn handle_approve_present(
+    addr: SocketAddr,
+    data: PeerTargetData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, target) = moderation_target(addr, &data.peer_id, manager, clients)?;
+
+    // One student presents at a time
+    if let Some(previous) = room
+        .set_presenter(&target.id)
+        .and_then(|id| room.get_peer(&id))
+    {
+        end_presentation(clients, &room, &previous);
+    }
+
+    target.permissions.write().video = true;
+    *target.present_requested.write() = false;
+    notify_permissions(clients, &room, &target);
+
+    broadcast_to_room(
+        clients,
+        &room.id,
+        &ServerMessage::PresenterChanged(PresenterChangedData {
+            peer_id: Some(target.id.clone()),
+            name: Some(target.name.clone()),
+        }),
+        None,
+    );
+    tracing::info!("[Room {}] {} may present", room.id, target.name);
+
+    Ok(())
+}
+
+/// Revoke a presentation, or decline a pending request
+fn handle_revoke_present(
+    addr: SocketAddr,
+    data: PeerTargetData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, target) = moderation_target(addr, &data.peer_id, manager, clients)?;
+
+    end_presentation(clients, &room, &target);
+    if room.clear_presenter(&target.id) {
+        broadcast_to_room(
+            clients,
+            &room.id,
+            &ServerMessage::PresenterChanged(PresenterChangedData {
+                peer_id: None,
+                name: None,
+            }),
+            None,
+        );
+    }
+    tracing::info!("[Room {}] {} stopped presenting", room.id, target.name);
+
+    Ok(())
+}
+
+/// Take back a student's video permission and close their screen producers
+fn end_presentation(clients: &RwLock<HashMap<SocketAddr, ClientInfo>>, room: &Room, peer: &Peer) {
+    peer.permissions.write().video = false;
+    *peer.present_requested.write() = false;
+
+    for producer in peer.take_producers(MediaKind::Video) {
+        broadcast_to_room(
+            clients,
+            &room.id,
+            &ServerMessage::ProducerClosed(ProducerClosedData {
+                producer_id: producer.id().to_string(),
+                peer_id: peer.id.clone(),
+            }),
+            None,
+        );
+    }
+
+    notify_permissions(clients, room, peer);
+}
+
+async fn handle_start_recording(
+    addr: SocketAddr,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, peer) = joined_peer(addr, manager, clients)?;
+    if !peer.is_teacher {
+        return Err("Only teacher can record".into());
+    }
+
+    let mut recording = room.recording.lock().await;
+    if recording.is_some() {
+        return Err("Recording already in progress".into());
+    }
+
+    let started = Recording::start(
+        &room.router,
+        &room.teacher_producers(),
+        manager.recording_config(),
+        &room.id,
+    )
+    .await?;
+
+    broadcast_to_room(
+        clients,
+        &room.id,
+        &ServerMessage::RecordingStarted(RecordingStartedData {
+            path: started.path.display().to_string(),
+            format: started.format.as_str().to_string(),
+        }),
+        None,
+    );
+    *recording = Some(started);
+
+    Ok(())
+}
+
+async fn handle_stop_recording(
+    addr: SocketAddr,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, peer) = joined_peer(addr, manager, clients)?;
+    if !peer.is_teacher {
+        return Err("Only teacher can record".into());
+    }
+
+    if !stop_recording(&room, clients).await {
+        return Err("No recording in progress".into());
+    }
+    Ok(())
+}
+
+/// Finish the room's recording, if any, and tell the room; returns whether one was running
+async fn stop_recording(room: &Room, clients: &RwLock<HashMap<SocketAddr, ClientInfo>>) -> bool {
+    let Some(recording) = room.recording.lock().await.take() else {
+        return false;
+    };
+
+    let path = recording.path.display().to_string();
+    let duration = recording.stop().await;
+    broadcast_to_room(
+        clients,
+        &room.id,
+        &ServerMessage::RecordingStopped(RecordingStoppedData {
+            path,
+            duration_secs: duration.as_secs(),
+        }),
+        None,
+    );
+    true
+}
+
+/// A running recording only has the teacher media it started with (ffmpeg reads its
+/// tracks from the SDP once), so a new teacher producer ends the current file and
+/// recording goes on in a new one with all of the teacher's media
+async fn restart_recording(
+    room: &Room,
+    manager: &MediasoupManager,
+    clients: &RwLock<HashMap<SocketAddr, ClientInfo>>,
+) {
+    let mut recording = room.recording.lock().await;
+    let Some(current) = recording.take() else {
+        return;
+    };
+
+    let path = current.path.display().to_string();
+    let duration = current.stop().await;
+    broadcast_to_room(
+        clients,
+        &room.id,
+        &ServerMessage::RecordingStopped(RecordingStoppedData {
+            path,
+            duration_secs: duration.as_secs(),
+        }),
+        None,
+    );
+
+    match Recording::start(
+        &room.router,
+        &room.teacher_producers(),
+        manager.recording_config(),
+        &room.id,
+    )
+    .await
+    {
+        Ok(next) => {
+            broadcast_to_room(
+                clients,
+                &room.id,
+                &ServerMessage::RecordingStarted(RecordingStartedData {
+                    path: next.path.display().to_string(),
+                    format: next.format.as_str().to_string(),
+                }),
+                None,
+            );
+            *recording = Some(next);
+        }
+        Err(e) => tracing::warn!("[Room {}] Could not continue recording: {}", room.id, e),
+    }
+}
+
+/// Teacher opens a data channel (pointer, whiteboard strokes, polls) for the students
+async fn handle_produce_data(
+    addr: SocketAddr,
+    data: ProduceDataChannelData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+    tx: &Tx,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, peer) = joined_peer(addr, manager, clients)?;
+    if !peer.is_teacher {
+        return Err("Only teacher can open data channels".into());
+    }
+
+    let transport = peer
+        .send_transport
+        .read()
+        .clone()
+        .ok_or("Send transport not found")?;
+
+    let data_producer = MediasoupManager::create_data_producer(
+        &transport,
+        data.sctp_stream_parameters,
+        data.label,
+        data.protocol,
+    )
+    .await?;
+
+    let data_producer_id = data_producer.id().to_string();
+    let label = data_producer.label().clone();
+    peer.data_producers
+        .write()
+        .insert(data_producer.id(), data_producer);
+
+    send_message(
+        tx,
+        &ServerMessage::DataProduced(DataProducedData {
+            data_producer_id: data_producer_id.clone(),
+            label: label.clone(),
+        }),
+    );
+
+    broadcast_to_room(
+        clients,
+        &room.id,
+        &ServerMessage::NewDataProducer(DataProducerInfo {
+            data_producer_id,
+            label,
+            peer_id: peer.id.clone(),
+        }),
+        Some(addr),
+    );
+
+    Ok(())
+}
+
+async fn handle_consume_data(
+    addr: SocketAddr,
+    data: ConsumeDataChannelData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+    tx: &Tx,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, peer) = joined_peer(addr, manager, clients)?;
+
+    let data_producer_id: DataProducerId = data.data_producer_id.parse()?;
+    let (data_producer, producer_peer) = room
+        .find_data_producer(&data_producer_id)
+        .ok_or("Data producer not found")?;
+    if producer_peer.id == peer.id {
+        return Err("Cannot consume own data channel".into());
+    }
+
+    let recv_transport = peer
+        .recv_transport
+        .read()
+        .clone()
+        .ok_or("Recv transport not found")?;
+
+    // Large classes: the teacher's channel lives on the main router
+    room.pipe_data_producer(data_producer.id(), &producer_peer.router, &peer.router)
+        .await?;
+
+    let data_consumer =
+        MediasoupManager::create_data_consumer(&recv_transport, data_producer.id()).await?;
+
+    let consumed = DataConsumedData {
+        data_consumer_id: data_consumer.id().to_string(),
+        data_producer_id: data.data_producer_id,
+        sctp_stream_parameters: data_consumer.sctp_stream_parameters(),
+        label: data_consumer.label().clone(),
+        protocol: data_consumer.protocol().clone(),
+    };
+    peer.data_consumers
+        .write()
+        .insert(data_consumer.id(), data_consumer);
+
+    send_message(tx, &ServerMessage::DataConsumed(consumed));
+
+    Ok(())
+}
+
+/// Teacher closes one of its data channels; students' consumers of it close with it
+fn handle_close_data_producer(
+    addr: SocketAddr,
+    data: CloseDataProducerData,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, peer) = joined_peer(addr, manager, clients)?;
+
+    let data_producer_id: DataProducerId = data.data_producer_id.parse()?;
+    peer.data_producers
+        .write()
+        .remove(&data_producer_id)
+        .ok_or("Data producer not found")?;
+
+    for other in room.get_all_peers() {
+        other
+            .data_consumers
+            .write()
+            .retain(|_, consumer| consumer.data_producer_id() != data_producer_id);
+    }
+
+    broadcast_to_room(
+        clients,
+        &room.id,
+        &ServerMessage::DataProducerClosed(DataProducerClosedData {
+            data_producer_id: data.data_producer_id,
+            peer_id: peer.id.clone(),
+        }),
+        Some(addr),
+    );
+
+    Ok(())
+}
+
+/// Data channels of the other peers, for clients that joined after they opened
+fn handle_get_data_producers(
+    addr: SocketAddr,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+    tx: &Tx,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (room, peer) = joined_peer(addr, manager, clients)?;
+
+    let mut data_producers = Vec::new();
+    for other in room.get_all_peers() {
+        if other.id == peer.id {
+            continue;
+        }
+        for data_producer in other.data_producers.read().values() {
+            data_producers.push(DataProducerInfo {
+                data_producer_id: data_producer.id().to_string(),
+                label: data_producer.label().clone(),
+                peer_id: other.id.clone(),
+            });
+        }
+    }
+
+    send_message(tx, &ServerMessage::DataProducers(data_producers));
+
+    Ok(())
+}
+
+/// Socket closed: keep the peer for the grace period so it can resume, then remove it
+async fn handle_disconnect(
+    addr: SocketAddr,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) {
+    let Some(info) = clients.write().remove(&addr) else {
+        return;
+    };
+    tracing::info!("Peer {} disconnected", info.peer_id);
+
+    let grace = manager.reconnect_grace();
+    let peer = manager
+        .get_room(&info.room_id)
+        .and_then(|room| room.get_peer(&info.peer_id));
+    match peer {
+        Some(peer) if !grace.is_zero() => {
+            let epoch = peer.mark_disconnected();
+            let manager = Arc::clone(manager);
+            let clients = Arc::clone(clients);
+            tokio::spawn(async move {
+                tokio::time::sleep(grace).await;
+                // The room may have been reset and the peer joined a new one meanwhile
+                let current = manager
+                    .get_room(&info.room_id)
+                    .and_then(|room| room.get_peer(&info.peer_id));
+                let same_peer = current.is_some_and(|current| Arc::ptr_eq(&current, &peer));
+                if same_peer && peer.still_disconnected(epoch) {
+                    tracing::info!("Peer {} did not come back within {:?}", peer.id, grace);
+                    leave_room(&info.room_id, &info.peer_id, &manager, &clients).await;
+                }
+            });
+        }
+        _ => leave_room(&info.room_id, &info.peer_id, manager, clients).await,
+    }
+}
+
+/// Replace workers that die. Their rooms are closed and everyone in them is told to
+/// join again, which puts the room on a working worker.
+async fn recover_workers(
+    manager: Arc<MediasoupManager>,
+    clients: Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+    mut deaths: mpsc::UnboundedReceiver<usize>,
+) {
+    while let Some(index) = deaths.recv().await {
+        let rooms = manager.close_rooms_on_worker(index);
+        tracing::warn!(
+            "Worker {} died; resetting {} room(s) that used it",
+            index,
+            rooms.len()
+        );
+
+        for room in rooms {
+            // Keep what was recorded before the crash
+            stop_recording(&room, &clients).await;
+            broadcast_to_room(
+                &clients,
+                &room.id,
+                &ServerMessage::RoomReset(RoomResetData {
+                    room_id: room.id.clone(),
+                    reason: "Media server restarted, please rejoin".to_string(),
+                }),
+                None,
+            );
+        }
+
+        // Several workers may go down together; don't hold the others up on retries
+        let manager = Arc::clone(&manager);
+        tokio::spawn(async move { manager.respawn_worker(index).await });
+    }
+}
+
+/// Remove a peer from its room for good and tell the others
+async fn leave_room(
+    room_id: &str,
+    peer_id: &str,
+    manager: &Arc<MediasoupManager>,
+    clients: &Arc<RwLock<HashMap<SocketAddr, ClientInfo>>>,
+) {
+    let Some(room) = manager.get_room(room_id) else {
+        return;
+    };
+    let Some(peer) = room.get_peer(peer_id) else {
+        return;
+    };
+    let was_presenter = room.presenter().map(|p| p.id == peer.id).unwrap_or(false);
+
+    // Finish the file while the teacher's producers are still open
+    if peer.is_teacher {
+        stop_recording(&room, clients).await;
+    }
+
+    room.remove_peer(peer_id);
+    room.chat.lock().forget_private(peer_id);
+
+    if was_presenter {
+        broadcast_to_room(
+            clients,
+            room_id,
+            &ServerMessage::PresenterChanged(PresenterChangedData {
+                peer_id: None,
+                name: None,
+            }),
+            None,
+        );
+    }
+
+    // Notify others
+    broadcast_to_room(
+        clients,
+        room_id,
+        &ServerMessage::PeerLeft(PeerLeftData {
+            peer_id: peer_id.to_string(),
+            was_teacher: peer.is_teacher,
+        }),
+        None,
+    );
 
-        tracing::info!("Peer {} disconnected", info.peer_id);
+    // Clean up empty room
+    if room.is_empty() {
+        manager.remove_room(room_id);
     }
 }
@@ -0,0 +1,101 @@
+use mediasoup::prelude::*;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::num::NonZeroU16;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Audio observers on a room's router, fed with the students' microphones
+pub struct SpeakerObservers {
+    audio_levels: AudioLevelObserver,
+    active_speaker: ActiveSpeakerObserver,
+    /// When each observed producer was last loud enough to be reported
+    last_heard: Mutex<HashMap<ProducerId, Instant>>,
+    watching: AtomicBool,
+}
+
+impl SpeakerObservers {
+    pub async fn new(router: &Router) -> Result<Self, BoxError> {
+        let mut options = AudioLevelObserverOptions::default();
+        options.max_entries = NonZeroU16::new(8).unwrap();
+        options.threshold = -70; // dBvo, quieter is silence
+        options.interval = 1000;
+        let audio_levels = router.create_audio_level_observer(options).await?;
+
+        let mut options = ActiveSpeakerObserverOptions::default();
+        options.interval = 300;
+        let active_speaker = router.create_active_speaker_observer(options).await?;
+
+        Ok(Self {
+            audio_levels,
+            active_speaker,
+            last_heard: Mutex::new(HashMap::new()),
+            watching: AtomicBool::new(false),
+        })
+    }
+
+    /// Observe a microphone; closed producers leave the observers on their own
+    pub async fn add_producer(&self, producer: &Producer) -> Result<(), BoxError> {
+        self.audio_levels
+            .add_producer(RtpObserverAddProducerOptions::new(producer.id()))
+            .await?;
+        self.active_speaker
+            .add_producer(RtpObserverAddProducerOptions::new(producer.id()))
+            .await?;
+        self.mark_heard(producer.id());
+        Ok(())
+    }
+
+    /// Reset a producer's silence timer
+    pub fn mark_heard(&self, producer_id: ProducerId) {
+        self.last_heard.lock().insert(producer_id, Instant::now());
+    }
+
+    pub fn silent_for(&self, producer_id: &ProducerId) -> Option<Duration> {
+        self.last_heard.lock().get(producer_id).map(|t| t.elapsed())
+    }
+
+    pub fn forget(&self, producer_id: &ProducerId) {
+        self.last_heard.lock().remove(producer_id);
+    }
+
+    /// True for the first caller only, so event handlers are attached once per room
+    pub fn start_watching(&self) -> bool {
+        !self.watching.swap(true, Ordering::SeqCst)
+    }
+
+    /// Producers above the threshold and their volume in dBvo, every interval
+    pub fn on_volumes<F>(&self, callback: F)
+    where
+        F: Fn(&[(ProducerId, i8)]) + Send + Sync + 'static,
+    {
+        self.audio_levels
+            .on_volumes(move |volumes| {
+                let volumes: Vec<(ProducerId, i8)> = volumes
+                    .iter()
+                    .map(|v| (v.producer.id(), v.volume))
+                    .collect();
+                callback(&volumes);
+            })
+            .detach();
+    }
+
+    /// Everyone went quiet
+    pub fn on_silence<F>(&self, callback: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.audio_levels.on_silence(callback).detach();
+    }
+
+    pub fn on_dominant_speaker<F>(&self, callback: F)
+    where
+        F: Fn(ProducerId) + Send + Sync + 'static,
+    {
+        self.active_speaker
+            .on_dominant_speaker(move |speaker| callback(speaker.producer.id()))
+            .detach();
+    }
+}
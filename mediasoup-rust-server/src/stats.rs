@@ -0,0 +1,576 @@
+use crate::manager::MediasoupManager;
+use crate::room::{Peer, Room};
+use mediasoup::prelude::*;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Snapshot of the server for the `/stats` and `/metrics` endpoints
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStats {
+    pub uptime_secs: u64,
+    pub workers: Vec<WorkerStats>,
+    pub rooms: Vec<RoomStats>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerStats {
+    pub index: usize,
+    pub worker_id: String,
+    pub closed: bool,
+    /// CPU time in milliseconds; None when the worker did not answer
+    pub cpu_user_ms: Option<u64>,
+    pub cpu_system_ms: Option<u64>,
+    /// Peak resident memory in KiB
+    pub max_rss_kb: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomStats {
+    pub room_id: String,
+    pub worker_index: usize,
+    /// Main router plus the ones on other workers for a large class
+    pub routers: usize,
+    pub recording: bool,
+    pub peers: Vec<PeerStats>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerStats {
+    pub peer_id: String,
+    pub is_teacher: bool,
+    pub transports: Vec<TransportStats>,
+    pub producers: Vec<ProducerStats>,
+    pub consumers: Vec<ConsumerStats>,
+    /// Lowest producer or consumer score (0-10), a rough "network health" for the peer
+    pub health: Option<u8>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransportStats {
+    pub transport_id: String,
+    /// "send" or "recv", from the peer's point of view
+    pub direction: &'static str,
+    /// Bits per second
+    pub recv_bitrate: u32,
+    pub send_bitrate: u32,
+    /// Fraction of RTP packets lost, 0-1
+    pub packet_loss_received: Option<f64>,
+    pub packet_loss_sent: Option<f64>,
+    pub ice_state: String,
+    pub dtls_state: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProducerStats {
+    pub producer_id: String,
+    pub kind: MediaKind,
+    pub paused: bool,
+    /// Lowest score across the producer's RTP streams
+    pub score: Option<u8>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumerStats {
+    pub consumer_id: String,
+    pub producer_id: String,
+    pub kind: MediaKind,
+    pub paused: bool,
+    pub score: u8,
+    pub producer_score: u8,
+}
+
+/// Gather stats from every worker and room. Workers and transports that fail to
+/// answer are reported without their numbers instead of failing the whole snapshot.
+pub async fn collect(manager: &MediasoupManager) -> ServerStats {
+    let mut workers = Vec::new();
+    for (index, worker) in manager.workers().iter().enumerate() {
+        let usage = match worker.get_resource_usage().await {
+            Ok(usage) => Some(usage),
+            Err(e) => {
+                tracing::warn!("Failed to get resource usage of worker {}: {}", index, e);
+                None
+            }
+        };
+        workers.push(WorkerStats {
+            index,
+            worker_id: worker.id().to_string(),
+            closed: worker.closed(),
+            cpu_user_ms: usage.as_ref().map(|u| u.ru_utime),
+            cpu_system_ms: usage.as_ref().map(|u| u.ru_stime),
+            max_rss_kb: usage.as_ref().map(|u| u.ru_maxrss),
+        });
+    }
+
+    let mut rooms = Vec::new();
+    for room in manager.rooms() {
+        rooms.push(room_stats(&room).await);
+    }
+
+    ServerStats {
+        uptime_secs: manager.uptime().as_secs(),
+        workers,
+        rooms,
+    }
+}
+
+async fn room_stats(room: &Room) -> RoomStats {
+    let mut peers = Vec::new();
+    for peer in room.get_all_peers() {
+        peers.push(peer_stats(&peer).await);
+    }
+
+    RoomStats {
+        room_id: room.id.clone(),
+        worker_index: room.worker_index,
+        routers: room.router_count(),
+        recording: room.is_recording(),
+        peers,
+    }
+}
+
+async fn peer_stats(peer: &Peer) -> PeerStats {
+    let mut transports = Vec::new();
+    let send = peer.send_transport.read().clone();
+    let recv = peer.recv_transport.read().clone();
+    for (direction, transport) in [("send", send), ("recv", recv)] {
+        let Some(transport) = transport else {
+            continue;
+        };
+        match transport.get_stats().await {
+            Ok(stats) => transports.extend(stats.into_iter().map(|stat| TransportStats {
+                transport_id: stat.transport_id.to_string(),
+                direction,
+                recv_bitrate: stat.recv_bitrate,
+                send_bitrate: stat.send_bitrate,
+                packet_loss_received: stat.rtp_packet_loss_received,
+                packet_loss_sent: stat.rtp_packet_loss_sent,
+                ice_state: format!("{:?}", stat.ice_state).to_lowercase(),
+                dtls_state: format!("{:?}", stat.dtls_state).to_lowercase(),
+            })),
+            Err(e) => tracing::warn!("Failed to get stats of transport {}: {}", transport.id(), e),
+        }
+    }
+
+    let producers: Vec<ProducerStats> = peer
+        .producers
+        .read()
+        .values()
+        .map(|producer| ProducerStats {
+            producer_id: producer.id().to_string(),
+            kind: producer.kind(),
+            paused: producer.paused(),
+            score: producer.score().iter().map(|s| s.score).min(),
+        })
+        .collect();
+
+    let consumers: Vec<ConsumerStats> = peer
+        .consumers
+        .read()
+        .values()
+        .map(|consumer| {
+            let score = consumer.score();
+            ConsumerStats {
+                consumer_id: consumer.id().to_string(),
+                producer_id: consumer.producer_id().to_string(),
+                kind: consumer.kind(),
+                paused: consumer.paused(),
+                score: score.score,
+                producer_score: score.producer_score,
+            }
+        })
+        .collect();
+
+    // Paused media sends nothing, so its score says nothing about the network
+    let health = producers
+        .iter()
+        .filter(|p| !p.paused)
+        .filter_map(|p| p.score)
+        .chain(consumers.iter().filter(|c| !c.paused).map(|c| c.score))
+        .min();
+
+    PeerStats {
+        peer_id: peer.id.clone(),
+        is_teacher: peer.is_teacher,
+        transports,
+        producers,
+        consumers,
+        health,
+    }
+}
+
+/// Render stats in the Prometheus text exposition format
+pub fn render_prometheus(stats: &ServerStats) -> String {
+    let mut out = String::new();
+
+    metric(
+        &mut out,
+        "smartlab_sfu_uptime_seconds",
+        "gauge",
+        "Seconds since the server started",
+    );
+    sample(
+        &mut out,
+        "smartlab_sfu_uptime_seconds",
+        &[],
+        stats.uptime_secs,
+    );
+
+    metric(
+        &mut out,
+        "smartlab_sfu_worker_cpu_seconds_total",
+        "counter",
+        "CPU time used by a mediasoup worker",
+    );
+    for worker in &stats.workers {
+        let index = worker.index.to_string();
+        for (mode, ms) in [
+            ("user", worker.cpu_user_ms),
+            ("system", worker.cpu_system_ms),
+        ] {
+            if let Some(ms) = ms {
+                sample(
+                    &mut out,
+                    "smartlab_sfu_worker_cpu_seconds_total",
+                    &[("worker", &index), ("mode", mode)],
+                    ms as f64 / 1000.0,
+                );
+            }
+        }
+    }
+
+    metric(
+        &mut out,
+        "smartlab_sfu_worker_max_rss_bytes",
+        "gauge",
+        "Peak resident memory of a mediasoup worker",
+    );
+    for worker in &stats.workers {
+        if let Some(kb) = worker.max_rss_kb {
+            let index = worker.index.to_string();
+            sample(
+                &mut out,
+                "smartlab_sfu_worker_max_rss_bytes",
+                &[("worker", &index)],
+                kb * 1024,
+            );
+        }
+    }
+
+    metric(&mut out, "smartlab_sfu_rooms", "gauge", "Open rooms");
+    sample(&mut out, "smartlab_sfu_rooms", &[], stats.rooms.len());
+
+    metric(
+        &mut out,
+        "smartlab_sfu_room_peers",
+        "gauge",
+        "Peers in a room",
+    );
+    for room in &stats.rooms {
+        sample(
+            &mut out,
+            "smartlab_sfu_room_peers",
+            &[("room", &room.room_id)],
+            room.peers.len(),
+        );
+    }
+
+    metric(
+        &mut out,
+        "smartlab_sfu_room_recording",
+        "gauge",
+        "Whether a room is being recorded",
+    );
+    for room in &stats.rooms {
+        sample(
+            &mut out,
+            "smartlab_sfu_room_recording",
+            &[("room", &room.room_id)],
+            u8::from(room.recording),
+        );
+    }
+
+    metric(
+        &mut out,
+        "smartlab_sfu_transport_recv_bitrate_bps",
+        "gauge",
+        "Bitrate the server receives on a transport",
+    );
+    metric_per_transport(
+        &mut out,
+        stats,
+        "smartlab_sfu_transport_recv_bitrate_bps",
+        |t| Some(t.recv_bitrate as f64),
+    );
+
+    metric(
+        &mut out,
+        "smartlab_sfu_transport_send_bitrate_bps",
+        "gauge",
+        "Bitrate the server sends on a transport",
+    );
+    metric_per_transport(
+        &mut out,
+        stats,
+        "smartlab_sfu_transport_send_bitrate_bps",
+        |t| Some(t.send_bitrate as f64),
+    );
+
+    metric(
+        &mut out,
+        "smartlab_sfu_transport_packet_loss_received_ratio",
+        "gauge",
+        "Fraction of RTP packets lost on the way to the server",
+    );
+    metric_per_transport(
+        &mut out,
+        stats,
+        "smartlab_sfu_transport_packet_loss_received_ratio",
+        |t| t.packet_loss_received,
+    );
+
+    metric(
+        &mut out,
+        "smartlab_sfu_transport_packet_loss_sent_ratio",
+        "gauge",
+        "Fraction of RTP packets lost on the way to the peer",
+    );
+    metric_per_transport(
+        &mut out,
+        stats,
+        "smartlab_sfu_transport_packet_loss_sent_ratio",
+        |t| t.packet_loss_sent,
+    );
+
+    metric(
+        &mut out,
+        "smartlab_sfu_producer_score",
+        "gauge",
+        "Quality of the media a peer sends, 0-10",
+    );
+    for room in &stats.rooms {
+        for peer in &room.peers {
+            for producer in &peer.producers {
+                if let Some(score) = producer.score {
+                    sample(
+                        &mut out,
+                        "smartlab_sfu_producer_score",
+                        &[
+                            ("room", &room.room_id),
+                            ("peer", &peer.peer_id),
+                            ("producer", &producer.producer_id),
+                            ("kind", kind_label(producer.kind)),
+                        ],
+                        score,
+                    );
+                }
+            }
+        }
+    }
+
+    metric(
+        &mut out,
+        "smartlab_sfu_consumer_score",
+        "gauge",
+        "Quality of the media a peer receives, 0-10",
+    );
+    for room in &stats.rooms {
+        for peer in &room.peers {
+            for consumer in &peer.consumers {
+                sample(
+                    &mut out,
+                    "smartlab_sfu_consumer_score",
+                    &[
+                        ("room", &room.room_id),
+                        ("peer", &peer.peer_id),
+                        ("consumer", &consumer.consumer_id),
+                        ("kind", kind_label(consumer.kind)),
+                    ],
+                    consumer.score,
+                );
+            }
+        }
+    }
+
+    out
+}
+
+fn metric_per_transport<F>(out: &mut String, stats: &ServerStats, name: &str, value: F)
+where
+    F: Fn(&TransportStats) -> Option<f64>,
+{
+    for room in &stats.rooms {
+        for peer in &room.peers {
+            for transport in &peer.transports {
+                if let Some(v) = value(transport) {
+                    sample(
+                        out,
+                        name,
+                        &[
+                            ("room", &room.room_id),
+                            ("peer", &peer.peer_id),
+                            ("direction", transport.direction),
+                        ],
+                        v,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn kind_label(kind: MediaKind) -> &'static str {
+    match kind {
+        MediaKind::Audio => "audio",
+        MediaKind::Video => "video",
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        out.push('{');
+        for (i, (key, value)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}=\"{}\"", key, escape_label(value));
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+/// Room ids and peer ids come from clients, so quote them properly
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One room whose ids hold every character that needs escaping
+    fn stats() -> ServerStats {
+        ServerStats {
+            uptime_secs: 42,
+            workers: vec![WorkerStats {
+                index: 0,
+                worker_id: "w-0".to_string(),
+                closed: false,
+                cpu_user_ms: Some(1500),
+                cpu_system_ms: None,
+                max_rss_kb: Some(2048),
+            }],
+            rooms: vec![RoomStats {
+                room_id: "lab\"1\\\n".to_string(),
+                worker_index: 0,
+                routers: 1,
+                recording: true,
+                peers: vec![PeerStats {
+                    peer_id: "peer\\\"x\"".to_string(),
+                    is_teacher: false,
+                    transports: vec![TransportStats {
+                        transport_id: "t-1".to_string(),
+                        direction: "send",
+                        recv_bitrate: 250_000,
+                        send_bitrate: 0,
+                        packet_loss_received: Some(0.25),
+                        packet_loss_sent: None,
+                        ice_state: "completed".to_string(),
+                        dtls_state: "connected".to_string(),
+                    }],
+                    producers: vec![ProducerStats {
+                        producer_id: "p-1".to_string(),
+                        kind: MediaKind::Video,
+                        paused: false,
+                        score: Some(9),
+                    }],
+                    consumers: vec![ConsumerStats {
+                        consumer_id: "c-1".to_string(),
+                        producer_id: "p-0".to_string(),
+                        kind: MediaKind::Audio,
+                        paused: false,
+                        score: 10,
+                        producer_score: 10,
+                    }],
+                    health: Some(9),
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn escapes_client_ids_in_labels() {
+        assert_eq!(escape_label("plain-id"), "plain-id");
+        assert_eq!(escape_label("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+
+    #[test]
+    fn renders_prometheus_text() {
+        let out = render_prometheus(&stats());
+        let lines: Vec<&str> = out.lines().collect();
+        let room = r#"room="lab\"1\\\n""#;
+        let peer = r#"peer="peer\\\"x\"""#;
+
+        for expected in [
+            "smartlab_sfu_uptime_seconds 42".to_string(),
+            r#"smartlab_sfu_worker_cpu_seconds_total{worker="0",mode="user"} 1.5"#.to_string(),
+            r#"smartlab_sfu_worker_max_rss_bytes{worker="0"} 2097152"#.to_string(),
+            "smartlab_sfu_rooms 1".to_string(),
+            format!("smartlab_sfu_room_peers{{{}}} 1", room),
+            format!("smartlab_sfu_room_recording{{{}}} 1", room),
+            format!(
+                r#"smartlab_sfu_transport_recv_bitrate_bps{{{},{},direction="send"}} 250000"#,
+                room, peer
+            ),
+            format!(
+                r#"smartlab_sfu_transport_packet_loss_received_ratio{{{},{},direction="send"}} 0.25"#,
+                room, peer
+            ),
+            format!(
+                r#"smartlab_sfu_producer_score{{{},{},producer="p-1",kind="video"}} 9"#,
+                room, peer
+            ),
+            format!(
+                r#"smartlab_sfu_consumer_score{{{},{},consumer="c-1",kind="audio"}} 10"#,
+                room, peer
+            ),
+        ] {
+            assert!(lines.contains(&expected.as_str()), "missing {}", expected);
+        }
+
+        // Missing numbers are left out rather than reported as zero
+        assert!(!out.contains(r#"mode="system""#));
+        assert!(!out.contains("smartlab_sfu_transport_packet_loss_sent_ratio{"));
+
+        // Every line is a comment or one whole sample: the newline in the room id stayed escaped
+        for line in &lines {
+            if line.starts_with('#') {
+                continue;
+            }
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            assert!(series.starts_with("smartlab_sfu_"), "bad line {:?}", line);
+            assert!(value.parse::<f64>().is_ok(), "bad value in {:?}", line);
+        }
+        assert_eq!(
+            out.matches("# TYPE smartlab_sfu_room_peers gauge\n")
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Room management behind the admin HTTP API
+
+mod common;
+
+use common::{audio_rtp_parameters, TestServer};
+use mediasoup_rust_server::admin;
+
+#[tokio::test]
+async fn lists_rooms_with_teacher_first() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-2", "teacher").await;
+    let _student = server.join_student("lab-2", "student-1").await;
+    let _other_room = server.join_student("lab-1", "student-9").await;
+
+    teacher.create_transport("send").await;
+    let audio_id = teacher.produce("audio", audio_rtp_parameters(1111)).await;
+
+    let rooms = admin::rooms(&server.manager);
+    let ids: Vec<&str> = rooms.iter().map(|r| r.room_id.as_str()).collect();
+    assert_eq!(ids, ["lab-1", "lab-2"]);
+
+    let room = &rooms[1];
+    assert_eq!(room.teacher_id.as_deref(), Some("teacher"));
+    assert!(!room.recording);
+    assert_eq!(room.peers.len(), 2);
+    assert!(room.peers[0].is_teacher);
+    assert_eq!(room.peers[0].producers[0].producer_id, audio_id);
+    assert_eq!(room.peers[1].peer_id, "student-1");
+}
+
+#[tokio::test]
+async fn closing_a_room_disconnects_everyone() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut student = server.join_student("lab-1", "student-1").await;
+
+    assert!(
+        server
+            .rooms
+            .close_room("lab-1", Some("Lesson over".to_string()))
+            .await
+    );
+    assert_eq!(teacher.expect("kicked").await["reason"], "Lesson over");
+    assert_eq!(student.expect("kicked").await["reason"], "Lesson over");
+    assert!(server.manager.get_room("lab-1").is_none());
+
+    assert!(!server.rooms.close_room("lab-1", None).await);
+}
+
+#[tokio::test]
+async fn kicking_a_peer_tells_the_room() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut student = server.join_student("lab-1", "student-1").await;
+
+    assert!(server.rooms.kick_peer("lab-1", "student-1", None).await);
+    assert!(student.expect("kicked").await["reason"].is_null());
+    assert_eq!(teacher.expect("peerLeft").await["peerId"], "student-1");
+    server.wait_for_peer_count("lab-1", 1).await;
+
+    assert!(!server.rooms.kick_peer("lab-1", "student-1", None).await);
+    assert!(!server.rooms.kick_peer("lab-9", "teacher", None).await);
+}
@@ -0,0 +1,363 @@
+//! In-process SFU and a headless client that speaks its WebSocket protocol
+
+#![allow(dead_code)]
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use ed25519_dalek::{Signer, SigningKey};
+use futures_util::{SinkExt, StreamExt};
+use mediasoup::worker::WorkerId;
+use mediasoup_rust_server::auth::{self, RoomAuth};
+use mediasoup_rust_server::config::Config;
+use mediasoup_rust_server::manager::MediasoupManager;
+use mediasoup_rust_server::signaling::{RoomControl, SignalingServer};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+/// How long a client waits for a message it expects
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fixed teacher key, trusted by every test server
+fn teacher_key() -> SigningKey {
+    SigningKey::from_bytes(&[7; 32])
+}
+
+/// Join token for `room_id`, signed the way the Tauri app signs them
+pub fn join_token(room_id: &str, role: &str) -> String {
+    let claims = json!({
+        "roomId": room_id,
+        "role": role,
+        "expiresAt": auth::now_secs() + 3600,
+    });
+    let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+    let signature = teacher_key().sign(payload.as_bytes());
+    format!(
+        "{}.{}",
+        payload,
+        URL_SAFE_NO_PAD.encode(signature.to_bytes())
+    )
+}
+
+/// Opus as a browser would send it; `ssrc` must differ per producer
+pub fn audio_rtp_parameters(ssrc: u32) -> Value {
+    json!({
+        "mid": "0",
+        "codecs": [{
+            "mimeType": "audio/opus",
+            "payloadType": 111,
+            "clockRate": 48000,
+            "channels": 2,
+            "parameters": { "useinbandfec": 1, "minptime": 10 },
+            "rtcpFeedback": [],
+        }],
+        "headerExtensions": [],
+        "encodings": [{ "ssrc": ssrc }],
+        "rtcp": { "cname": "test", "reducedSize": true },
+    })
+}
+
+/// Single-layer VP8, e.g. a student's screen
+pub fn video_rtp_parameters(ssrc: u32) -> Value {
+    json!({
+        "mid": "1",
+        "codecs": [{
+            "mimeType": "video/VP8",
+            "payloadType": 96,
+            "clockRate": 90000,
+            "parameters": {},
+            "rtcpFeedback": [],
+        }],
+        "headerExtensions": [],
+        "encodings": [{ "ssrc": ssrc }],
+        "rtcp": { "cname": "test", "reducedSize": true },
+    })
+}
+
+/// Mediasoup workers and a signaling server on an ephemeral port
+pub struct TestServer {
+    pub manager: Arc<MediasoupManager>,
+    /// What the admin API calls
+    pub rooms: RoomControl,
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        Self::with_config(|_| {}).await
+    }
+
+    /// Start with the test defaults changed by `customize`
+    pub async fn with_config(customize: impl FnOnce(&mut Config)) -> Self {
+        let mut config = Config {
+            metrics_port: None,
+            num_workers: 1,
+            announced_ip: Some("127.0.0.1".to_string()),
+            reconnect_grace: Duration::ZERO,
+            room_auth: RoomAuth::new(vec![teacher_key().verifying_key()], None),
+            ..Config::default()
+        };
+        customize(&mut config);
+
+        let manager = Arc::new(
+            MediasoupManager::new(config)
+                .await
+                .expect("failed to start mediasoup"),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = SignalingServer::new(manager.clone());
+        let rooms = server.room_control();
+        let task = tokio::spawn(async move {
+            let _ = server.serve(listener).await;
+        });
+
+        Self {
+            manager,
+            rooms,
+            addr,
+            task,
+        }
+    }
+
+    pub async fn connect(&self) -> TestClient {
+        TestClient::connect(self.addr).await
+    }
+
+    pub async fn join_teacher(&self, room_id: &str, peer_id: &str) -> TestClient {
+        let mut client = self.connect().await;
+        client
+            .join(json!({
+                "roomId": room_id,
+                "peerId": peer_id,
+                "name": peer_id,
+                "isTeacher": true,
+                "token": join_token(room_id, "teacher"),
+            }))
+            .await
+            .expect("teacher join failed");
+        client
+    }
+
+    pub async fn join_student(&self, room_id: &str, peer_id: &str) -> TestClient {
+        let mut client = self.connect().await;
+        client
+            .join(json!({ "roomId": room_id, "peerId": peer_id, "name": peer_id }))
+            .await
+            .expect("student join failed");
+        client
+    }
+
+    /// Wait until the room has `count` peers, or is gone when `count` is 0
+    pub async fn wait_for_peer_count(&self, room_id: &str, count: usize) {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let peers = self.manager.get_room(room_id).map_or(0, |r| r.peer_count());
+            if peers == count {
+                return;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "room {} has {} peers, expected {}",
+                room_id,
+                peers,
+                count
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// Wait until the worker slot `index` holds a worker other than `dead`
+    pub async fn wait_for_worker_respawn(&self, index: usize, dead: WorkerId) {
+        let deadline = Instant::now() + TIMEOUT;
+        while self.manager.workers()[index].id() == dead {
+            assert!(
+                Instant::now() < deadline,
+                "worker {} was not respawned",
+                index
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Headless signaling client. Messages that arrive while it waits for another
+/// type are kept, so tests can expect them in any order.
+pub struct TestClient {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    backlog: VecDeque<(String, Value)>,
+    /// The `joined` reply, once joined
+    pub joined: Option<Value>,
+}
+
+impl TestClient {
+    pub async fn connect(addr: SocketAddr) -> Self {
+        let (ws, _) = connect_async(format!("ws://{}", addr))
+            .await
+            .expect("failed to connect to signaling server");
+        Self {
+            ws,
+            backlog: VecDeque::new(),
+            joined: None,
+        }
+    }
+
+    pub async fn send(&mut self, kind: &str, data: Value) {
+        self.send_raw(&json!({ "type": kind, "data": data }).to_string())
+            .await;
+    }
+
+    pub async fn send_raw(&mut self, text: &str) {
+        self.ws
+            .send(Message::Text(text.to_string()))
+            .await
+            .expect("failed to send");
+    }
+
+    /// Data of the next message of type `kind`
+    pub async fn expect(&mut self, kind: &str) -> Value {
+        self.expect_any(&[kind]).await.1
+    }
+
+    /// Next message whose type is one of `kinds`
+    pub async fn expect_any(&mut self, kinds: &[&str]) -> (String, Value) {
+        if let Some(index) = self
+            .backlog
+            .iter()
+            .position(|(kind, _)| kinds.contains(&kind.as_str()))
+        {
+            return self.backlog.remove(index).unwrap();
+        }
+
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let message = match self.next_message(deadline).await {
+                Some(message) => message,
+                None => panic!("timed out waiting for {:?}", kinds),
+            };
+            if kinds.contains(&message.0.as_str()) {
+                return message;
+            }
+            self.backlog.push_back(message);
+        }
+    }
+
+    /// Message of the `error` reply
+    pub async fn expect_error(&mut self) -> String {
+        let data = self.expect("error").await;
+        data["message"].as_str().unwrap_or_default().to_string()
+    }
+
+    /// Fail if a message of type `kind` arrives within `wait`
+    pub async fn expect_none(&mut self, kind: &str, wait: Duration) {
+        assert!(
+            !self.backlog.iter().any(|(k, _)| k == kind),
+            "unexpected {}",
+            kind
+        );
+        let deadline = Instant::now() + wait;
+        while let Some(message) = self.next_message(deadline).await {
+            assert_ne!(message.0, kind, "unexpected {}: {}", kind, message.1);
+            self.backlog.push_back(message);
+        }
+    }
+
+    /// Send a request and wait for its reply, failing on an error
+    pub async fn request(&mut self, kind: &str, data: Value, reply: &str) -> Value {
+        self.send(kind, data).await;
+        match self.expect_any(&[reply, "error"]).await {
+            (kind, data) if kind == reply => data,
+            (_, data) => panic!("{} failed: {}", kind, data["message"]),
+        }
+    }
+
+    /// Join with the given `join` data; the error message when refused
+    pub async fn join(&mut self, data: Value) -> Result<Value, String> {
+        self.send("join", data).await;
+        match self.expect_any(&["joined", "error"]).await {
+            (kind, data) if kind == "joined" => {
+                self.joined = Some(data.clone());
+                Ok(data)
+            }
+            (_, data) => Err(data["message"].as_str().unwrap_or_default().to_string()),
+        }
+    }
+
+    /// Create the send or recv transport; returns the `transportCreated` data
+    pub async fn create_transport(&mut self, direction: &str) -> Value {
+        self.request(
+            "createTransport",
+            json!({ "direction": direction }),
+            "transportCreated",
+        )
+        .await
+    }
+
+    /// Produce with fake RTP parameters; returns the producer id
+    pub async fn produce(&mut self, kind: &str, rtp_parameters: Value) -> String {
+        let produced = self
+            .request(
+                "produce",
+                json!({ "kind": kind, "rtpParameters": rtp_parameters }),
+                "produced",
+            )
+            .await;
+        produced["producerId"].as_str().unwrap().to_string()
+    }
+
+    /// Consume with the router's own capabilities, as a loaded Device would
+    pub async fn consume(&mut self, producer_id: &str) -> Result<Value, String> {
+        let rtp_capabilities = self.joined.as_ref().expect("not joined")["rtpCapabilities"].clone();
+        self.send(
+            "consume",
+            json!({ "producerId": producer_id, "rtpCapabilities": rtp_capabilities }),
+        )
+        .await;
+        match self.expect_any(&["consumed", "error"]).await {
+            (kind, data) if kind == "consumed" => Ok(data),
+            (_, data) => Err(data["message"].as_str().unwrap_or_default().to_string()),
+        }
+    }
+
+    pub async fn chat(&mut self, content: &str, to: Option<&str>) {
+        self.send(
+            "chatMessage",
+            json!({ "content": content, "timestamp": "2024-01-01T08:00:00Z", "to": to }),
+        )
+        .await;
+    }
+
+    /// Close the socket the way a browser tab does
+    pub async fn close(mut self) {
+        let _ = self.ws.close(None).await;
+    }
+
+    async fn next_message(&mut self, deadline: Instant) -> Option<(String, Value)> {
+        loop {
+            let message = tokio::time::timeout_at(deadline, self.ws.next())
+                .await
+                .ok()?
+                .expect("connection closed")
+                .expect("WebSocket error");
+            if let Message::Text(text) = message {
+                let mut value: Value = serde_json::from_str(&text).expect("invalid JSON");
+                let kind = value["type"].as_str().unwrap_or_default().to_string();
+                return Some((kind, value["data"].take()));
+            }
+        }
+    }
+}
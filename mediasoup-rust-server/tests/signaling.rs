@@ -0,0 +1,501 @@
+//! Protocol tests against an in-process SFU. They need the mediasoup worker
+//! binary, which the `mediasoup` crate builds along with the server.
+
+mod common;
+
+use common::{audio_rtp_parameters, join_token, video_rtp_parameters, TestServer};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn teacher_and_student_join() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let student = server.join_student("lab-1", "student-1").await;
+
+    let joined = teacher.joined.clone().unwrap();
+    assert_eq!(joined["isTeacher"], true);
+    assert_eq!(joined["resumed"], false);
+    assert!(joined["rtpCapabilities"]["codecs"].is_array());
+    assert_eq!(student.joined.as_ref().unwrap()["isTeacher"], false);
+
+    let peer_joined = teacher.expect("peerJoined").await;
+    assert_eq!(peer_joined["peerId"], "student-1");
+    assert_eq!(peer_joined["isTeacher"], false);
+    server.wait_for_peer_count("lab-1", 2).await;
+}
+
+#[tokio::test]
+async fn leaving_notifies_the_room_and_closes_it_when_empty() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let student = server.join_student("lab-1", "student-1").await;
+
+    student.close().await;
+    let left = teacher.expect("peerLeft").await;
+    assert_eq!(left["peerId"], "student-1");
+    assert_eq!(left["wasTeacher"], false);
+    server.wait_for_peer_count("lab-1", 1).await;
+
+    teacher.close().await;
+    server.wait_for_peer_count("lab-1", 0).await;
+    assert!(server.manager.get_room("lab-1").is_none());
+}
+
+#[tokio::test]
+async fn room_has_one_teacher() {
+    let server = TestServer::start().await;
+    let _teacher = server.join_teacher("lab-1", "teacher").await;
+
+    let mut second = server.connect().await;
+    let error = second
+        .join(json!({
+            "roomId": "lab-1",
+            "peerId": "teacher-2",
+            "name": "Second teacher",
+            "isTeacher": true,
+            "token": join_token("lab-1", "teacher"),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(error, "Room already has a teacher");
+}
+
+#[tokio::test]
+async fn teacher_role_needs_a_token() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let error = client
+        .join(json!({ "roomId": "lab-1", "peerId": "p1", "name": "P1", "isTeacher": true }))
+        .await
+        .unwrap_err();
+    assert_eq!(error, "Joining as teacher requires a signed join token");
+
+    // A student token does not make a teacher either
+    let error = client
+        .join(json!({
+            "roomId": "lab-1",
+            "peerId": "p1",
+            "name": "P1",
+            "isTeacher": true,
+            "token": join_token("lab-1", "student"),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(error, "Join token does not grant the teacher role");
+}
+
+#[tokio::test]
+async fn full_room_refuses_joins() {
+    let server = TestServer::with_config(|config| config.max_clients_per_room = 2).await;
+    let _teacher = server.join_teacher("lab-1", "teacher").await;
+    let _student = server.join_student("lab-1", "student-1").await;
+
+    let mut late = server.connect().await;
+    let error = late
+        .join(json!({ "roomId": "lab-1", "peerId": "student-2", "name": "Late" }))
+        .await
+        .unwrap_err();
+    assert_eq!(error, "Room is full");
+    server.wait_for_peer_count("lab-1", 2).await;
+}
+
+#[tokio::test]
+async fn peer_id_cannot_be_taken_over() {
+    let server = TestServer::start().await;
+    let _student = server.join_student("lab-1", "student-1").await;
+
+    let mut other = server.connect().await;
+    let error = other
+        .join(json!({ "roomId": "lab-1", "peerId": "student-1", "name": "Impostor" }))
+        .await
+        .unwrap_err();
+    assert_eq!(error, "Peer id already in use");
+}
+
+#[tokio::test]
+async fn requests_need_a_joined_peer() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    client
+        .send("createTransport", json!({ "direction": "send" }))
+        .await;
+    assert_eq!(client.expect_error().await, "Not joined");
+
+    client.send_raw("{\"type\":\"noSuchMessage\"}").await;
+    assert_eq!(client.expect_error().await, "Invalid message format");
+}
+
+#[tokio::test]
+async fn student_consumes_teacher_media() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut student = server.join_student("lab-1", "student-1").await;
+
+    let transport = teacher.create_transport("send").await;
+    assert_eq!(transport["direction"], "send");
+    assert!(transport["iceCandidates"]
+        .as_array()
+        .is_some_and(|c| !c.is_empty()));
+    let audio_id = teacher.produce("audio", audio_rtp_parameters(1111)).await;
+    let video_id = teacher.produce("video", video_rtp_parameters(2222)).await;
+
+    let new_producer = student.expect("newProducer").await;
+    assert_eq!(new_producer["producerId"], audio_id.as_str());
+    assert_eq!(new_producer["peerId"], "teacher");
+
+    let producers = student
+        .request("getProducers", json!({}), "producers")
+        .await;
+    let mut listed: Vec<&str> = producers
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["producerId"].as_str().unwrap())
+        .collect();
+    listed.sort();
+    let mut expected = vec![audio_id.as_str(), video_id.as_str()];
+    expected.sort();
+    assert_eq!(listed, expected);
+
+    student.create_transport("recv").await;
+    let consumed = student.consume(&audio_id).await.unwrap();
+    assert_eq!(consumed["producerId"], audio_id.as_str());
+    assert_eq!(consumed["kind"], "audio");
+    assert_eq!(
+        consumed["rtpParameters"]["codecs"][0]["mimeType"],
+        "audio/opus"
+    );
+
+    let consumer_id = consumed["consumerId"].as_str().unwrap();
+    let resumed = student
+        .request(
+            "resumeConsumer",
+            json!({ "consumerId": consumer_id }),
+            "consumerResumed",
+        )
+        .await;
+    assert_eq!(resumed["consumerId"], consumer_id);
+
+    let consumed = student.consume(&video_id).await.unwrap();
+    assert_eq!(consumed["kind"], "video");
+}
+
+#[tokio::test]
+async fn student_audio_goes_to_the_teacher_only() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut speaker = server.join_student("lab-1", "student-1").await;
+    let mut listener = server.join_student("lab-1", "student-2").await;
+
+    speaker.create_transport("send").await;
+    let audio_id = speaker.produce("audio", audio_rtp_parameters(3333)).await;
+
+    let new_producer = teacher.expect("newProducer").await;
+    assert_eq!(new_producer["producerId"], audio_id.as_str());
+    assert_eq!(new_producer["peerId"], "student-1");
+    listener
+        .expect_none("newProducer", Duration::from_millis(300))
+        .await;
+
+    listener.create_transport("recv").await;
+    let error = listener.consume(&audio_id).await.unwrap_err();
+    assert_eq!(error, "Students can only consume from teacher");
+
+    teacher.create_transport("recv").await;
+    let consumed = teacher.consume(&audio_id).await.unwrap();
+    assert_eq!(consumed["kind"], "audio");
+}
+
+#[tokio::test]
+async fn student_screen_needs_approval() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut student = server.join_student("lab-1", "student-1").await;
+
+    student.create_transport("send").await;
+    student
+        .send(
+            "produce",
+            json!({ "kind": "video", "rtpParameters": video_rtp_parameters(4444) }),
+        )
+        .await;
+    assert_eq!(
+        student.expect_error().await,
+        "Screen sharing needs teacher approval"
+    );
+
+    teacher
+        .send("approvePresent", json!({ "peerId": "student-1" }))
+        .await;
+    let permissions = student.expect("permissionsChanged").await;
+    assert_eq!(permissions["video"], true);
+
+    let video_id = student.produce("video", video_rtp_parameters(4444)).await;
+    let new_producer = teacher.expect("newProducer").await;
+    assert_eq!(new_producer["producerId"], video_id.as_str());
+}
+
+#[tokio::test]
+async fn chat_reaches_the_room_and_later_joiners() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut student = server.join_student("lab-1", "student-1").await;
+
+    teacher.chat("Mở bài 3", None).await;
+    let message = student.expect("chatMessage").await;
+    assert_eq!(message["content"], "Mở bài 3");
+    assert_eq!(message["senderId"], "teacher");
+    assert_eq!(message["isTeacher"], true);
+    assert_eq!(message["messageType"], "All");
+    // The sender does not get its own message back
+    teacher
+        .expect_none("chatMessage", Duration::from_millis(300))
+        .await;
+
+    let late = server.join_student("lab-1", "student-2").await;
+    let history = &late.joined.as_ref().unwrap()["chatHistory"];
+    assert_eq!(history[0]["content"], "Mở bài 3");
+}
+
+#[tokio::test]
+async fn private_chat_reaches_one_student() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut asked = server.join_student("lab-1", "student-1").await;
+    let mut other = server.join_student("lab-1", "student-2").await;
+
+    teacher.chat("Em làm lại câu 2", Some("student-1")).await;
+    let message = asked.expect("chatMessage").await;
+    assert_eq!(message["messageType"], "Direct");
+    assert_eq!(message["receiverId"], "student-1");
+    other
+        .expect_none("chatMessage", Duration::from_millis(300))
+        .await;
+
+    // Students only whisper to the teacher
+    other.chat("Bạn ơi", Some("student-1")).await;
+    assert_eq!(
+        other.expect_error().await,
+        "Students can only message the teacher privately"
+    );
+}
+
+#[tokio::test]
+async fn private_chat_is_not_handed_to_a_reused_peer_id() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut asked = server.join_student("lab-1", "student-1").await;
+
+    teacher.chat("Cả lớp làm bài 3", None).await;
+    asked.expect("chatMessage").await;
+    asked.chat("Em chưa hiểu câu 2", Some("teacher")).await;
+    teacher.expect("chatMessage").await;
+    teacher
+        .chat("Em ở lại sau giờ nhé", Some("student-1"))
+        .await;
+    asked.expect("chatMessage").await;
+
+    asked.close().await;
+    server.wait_for_peer_count("lab-1", 1).await;
+
+    // A classmate who saw the id in peerJoined takes it over
+    let impostor = server.join_student("lab-1", "student-1").await;
+    let history = impostor.joined.as_ref().unwrap()["chatHistory"]
+        .as_array()
+        .unwrap()
+        .clone();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["content"], "Cả lớp làm bài 3");
+}
+
+#[tokio::test]
+async fn disconnect_closes_the_peers_media() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut student = server.join_student("lab-1", "student-1").await;
+
+    student.create_transport("send").await;
+    let audio_id = student.produce("audio", audio_rtp_parameters(5555)).await;
+    teacher.expect("newProducer").await;
+
+    student.close().await;
+    teacher.expect("peerLeft").await;
+    let room = server.manager.get_room("lab-1").unwrap();
+    assert!(room.find_producer(&audio_id.parse().unwrap()).is_none());
+
+    let producers = teacher
+        .request("getProducers", json!({}), "producers")
+        .await;
+    assert_eq!(producers, json!([]));
+
+    teacher.create_transport("recv").await;
+    assert_eq!(
+        teacher.consume(&audio_id).await.unwrap_err(),
+        "Producer not found"
+    );
+}
+
+#[tokio::test]
+async fn teacher_leaving_is_announced() {
+    let server = TestServer::start().await;
+    let teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut student = server.join_student("lab-1", "student-1").await;
+
+    teacher.close().await;
+    let left = student.expect("peerLeft").await;
+    assert_eq!(left["wasTeacher"], true);
+
+    // The seat is free for the next teacher
+    let _teacher = server.join_teacher("lab-1", "teacher-2").await;
+}
+
+#[tokio::test]
+async fn dropped_peer_resumes_within_grace_period() {
+    let server =
+        TestServer::with_config(|config| config.reconnect_grace = Duration::from_secs(30)).await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let student = server.join_student("lab-1", "student-1").await;
+    let resume_token = student.joined.as_ref().unwrap()["resumeToken"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    student.close().await;
+    teacher
+        .expect_none("peerLeft", Duration::from_millis(300))
+        .await;
+    server.wait_for_peer_count("lab-1", 2).await;
+
+    let mut back = server.connect().await;
+    let error = back
+        .join(json!({
+            "roomId": "lab-1",
+            "peerId": "student-1",
+            "name": "student-1",
+            "resumeToken": "not-the-token",
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(error, "Invalid resume token");
+
+    let joined = back
+        .join(json!({
+            "roomId": "lab-1",
+            "peerId": "student-1",
+            "name": "student-1",
+            "resumeToken": resume_token,
+        }))
+        .await
+        .unwrap();
+    assert_eq!(joined["resumed"], true);
+}
+
+#[tokio::test]
+async fn expired_resume_joins_again_with_its_auth() {
+    let server = TestServer::start().await;
+    let teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut student = server.join_student("lab-1", "student-1").await;
+    let resume_token = teacher.joined.as_ref().unwrap()["resumeToken"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    teacher.close().await;
+    student.expect("peerLeft").await;
+
+    let mut back = server.connect().await;
+    let joined = back
+        .join(json!({
+            "roomId": "lab-1",
+            "peerId": "teacher",
+            "name": "teacher",
+            "isTeacher": true,
+            "token": join_token("lab-1", "teacher"),
+            "resumeToken": resume_token,
+        }))
+        .await
+        .unwrap();
+    assert_eq!(joined["resumed"], false);
+    assert_eq!(joined["isTeacher"], true);
+
+    let announced = student.expect("peerJoined").await;
+    assert_eq!(announced["isTeacher"], true);
+}
+
+#[tokio::test]
+async fn recording_continues_with_new_teacher_media() {
+    let dir = std::env::temp_dir().join(format!("smartlab-recordings-{}", uuid::Uuid::new_v4()));
+    let recording_dir = dir.clone();
+    let server = TestServer::with_config(|config| {
+        config.recording.dir = recording_dir;
+        config.recording.ffmpeg = None;
+    })
+    .await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut student = server.join_student("lab-1", "student-1").await;
+
+    teacher.create_transport("send").await;
+    teacher.produce("audio", audio_rtp_parameters(1111)).await;
+    teacher.send("startRecording", json!({})).await;
+    let first = student.expect("recordingStarted").await;
+
+    // Sharing the screen after pressing record moves on to a file that has it
+    teacher.produce("video", video_rtp_parameters(2222)).await;
+    let stopped = student.expect("recordingStopped").await;
+    assert_eq!(stopped["path"], first["path"]);
+    let second = student.expect("recordingStarted").await;
+    assert_ne!(second["path"], first["path"]);
+
+    teacher.send("stopRecording", json!({})).await;
+    let stopped = student.expect("recordingStopped").await;
+    assert_eq!(stopped["path"], second["path"]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn worker_death_resets_its_rooms() {
+    let server = TestServer::start().await;
+    let mut teacher = server.join_teacher("lab-1", "teacher").await;
+    let mut student = server.join_student("lab-1", "student-1").await;
+    let room = server.manager.get_room("lab-1").unwrap();
+    let dead = server.manager.workers()[room.worker_index].id();
+
+    server.manager.report_worker_death(room.worker_index);
+
+    for client in [&mut teacher, &mut student] {
+        let reset = client.expect("roomReset").await;
+        assert_eq!(reset["roomId"], "lab-1");
+    }
+    assert!(server.manager.rooms().is_empty());
+    server
+        .wait_for_worker_respawn(room.worker_index, dead)
+        .await;
+
+    // Both join again on the same socket, as the browser client does
+    teacher
+        .join(json!({
+            "roomId": "lab-1",
+            "peerId": "teacher",
+            "name": "teacher",
+            "isTeacher": true,
+            "token": join_token("lab-1", "teacher"),
+        }))
+        .await
+        .unwrap();
+    student
+        .join(json!({ "roomId": "lab-1", "peerId": "student-1", "name": "student-1" }))
+        .await
+        .unwrap();
+
+    let fresh = server.manager.get_room("lab-1").unwrap();
+    assert!(!Arc::ptr_eq(&fresh, &room));
+    assert_eq!(fresh.peer_count(), 2);
+    let worker = &server.manager.workers()[fresh.worker_index];
+    assert!(!worker.closed());
+    assert_ne!(worker.id(), dead);
+}
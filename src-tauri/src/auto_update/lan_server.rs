@@ -402,8 +402,11 @@ async fn serve_partial_content(
 /// # Returns
 /// * `Some((start, end))` - Valid range (inclusive)
 /// * `None` - Invalid range
-fn parse_range_header(range: &str, file_size: u64) -> Option<(u64, u64)> {
+pub(crate) fn parse_range_header(range: &str, file_size: u64) -> Option<(u64, u64)> {
     let range = range.strip_prefix("bytes=")?;
+    if file_size == 0 {
+        return None;
+    }
     
     if let Some(suffix_len) = range.strip_prefix('-') {
         // Suffix range: -500 means last 500 bytes
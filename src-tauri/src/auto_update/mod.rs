@@ -43,6 +43,7 @@ pub use installer::InstallerRunner;
 
 // Re-export LAN server
 pub use lan_server::LanDistributionServer;
+pub(crate) use lan_server::parse_range_header;
 
 // Re-export student coordinator
 pub use student_coordinator::{StudentUpdateCoordinator, StudentUpdateState};
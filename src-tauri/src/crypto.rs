@@ -1,7 +1,10 @@
 //! Cryptographic utilities for View Client authentication
 //! Supports both Ed25519 PKI and LDAP/AD authentication
 
-use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD},
+    Engine as _,
+};
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
@@ -173,6 +176,47 @@ pub fn generate_challenge() -> Vec<u8> {
     challenge.to_vec()
 }
 
+/// Claims of a join token for the classroom (mediasoup) signaling server
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinTokenClaims {
+    pub room_id: String,
+    /// "teacher" or "student"
+    pub role: String,
+    /// Bind the token to one peer id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_id: Option<String>,
+    /// Unix seconds
+    pub expires_at: u64,
+}
+
+/// Sign a join token: base64url(claims JSON) "." base64url(signature of the first part)
+pub fn sign_join_token(
+    private_key_base64: &str,
+    claims: &JoinTokenClaims,
+) -> Result<String, String> {
+    let payload =
+        serde_json::to_vec(claims).map_err(|e| format!("Failed to serialize claims: {}", e))?;
+    let payload = URL_SAFE_NO_PAD.encode(payload);
+
+    let signature = sign_challenge(private_key_base64, payload.as_bytes())?;
+    let signature = BASE64
+        .decode(signature)
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+
+    Ok(format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature)))
+}
+
+/// Load the teacher keypair, creating and saving one on first use
+pub fn ensure_keypair() -> Result<KeyPairInfo, String> {
+    if has_keypair() {
+        return load_keypair();
+    }
+    let keypair = generate_keypair();
+    save_keypair(&keypair)?;
+    Ok(keypair)
+}
+
 /// Get the default key storage directory
 pub fn get_key_storage_dir() -> Result<PathBuf, String> {
     let home = dirs::home_dir().ok_or("Could not find home directory")?;
@@ -347,6 +391,28 @@ mod tests {
         assert!(!result.valid);
     }
 
+    #[test]
+    fn test_join_token_signature() {
+        let keypair = generate_keypair();
+        let claims = JoinTokenClaims {
+            room_id: "lop-10a1".to_string(),
+            role: "teacher".to_string(),
+            peer_id: None,
+            expires_at: 1_700_000_000,
+        };
+        let token = sign_join_token(&keypair.private_key, &claims).unwrap();
+
+        let (payload, signature) = token.split_once('.').unwrap();
+        let signature = BASE64.encode(URL_SAFE_NO_PAD.decode(signature).unwrap());
+        assert!(verify_signature(&keypair.public_key, payload.as_bytes(), &signature).valid);
+
+        let decoded: JoinTokenClaims =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+        assert_eq!(decoded, claims);
+        let json = String::from_utf8(URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+        assert!(!json.contains("peerId"));
+    }
+
     #[test]
     fn test_import_export_public_key() {
         let keypair = generate_keypair();
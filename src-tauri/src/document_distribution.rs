@@ -3,15 +3,45 @@
 //! Provides HTTP file server for teachers to distribute documents to students.
 //! Teachers can upload files, students can browse and download via HTTP.
 
+use axum::{
+    body::{Body, Bytes},
+    extract::{ConnectInfo, DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    response::{Html, IntoResponse, Json, Redirect, Response},
+    routing::{get, post},
+    Router,
+};
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD},
+    Engine as _,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use tokio::io::AsyncWriteExt;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+use crate::auto_update::parse_range_header;
+use crate::crypto::{self, KeyPairInfo};
+use crate::zip_stream::{self, ZipEntry, ZipStreamWriter};
 
 /// Default port for document server
 pub const DOCUMENT_SERVER_PORT: u16 = 8765;
 
+/// Maximum request body accepted for a submission upload (100MB)
+pub const MAX_SUBMISSION_SIZE: usize = 100 * 1024 * 1024;
+
+/// Default lifetime of a signed access link (2 hours)
+pub const DEFAULT_LINK_TTL_SECS: u64 = 2 * 60 * 60;
+
+/// Tells apart submissions that arrive in the same millisecond
+static SUBMISSION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
 /// Document metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Document {
@@ -22,23 +52,321 @@ pub struct Document {
     pub uploaded_at: u64,
     pub description: Option<String>,
     pub category: Option<String>,
+    /// Folder path such as "Toán/Chương 1"; `None` is the root
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// Who may see and download the document
+    #[serde(default)]
+    pub visibility: DocumentVisibility,
+    /// Hidden from everyone until this time (unix seconds), e.g. answers after a test
+    #[serde(default)]
+    pub available_from: Option<u64>,
+}
+
+impl Document {
+    /// Whether a visitor holding `claims` may see this document at `now`
+    pub fn is_visible_to(&self, claims: Option<&AccessClaims>, now: u64) -> bool {
+        if self.available_from.map(|t| now < t).unwrap_or(false) {
+            return false;
+        }
+        match claims {
+            Some(claims) => claims.covers(self),
+            None => self.visibility == DocumentVisibility::Public,
+        }
+    }
+}
+
+/// Audience of a document
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "scope", rename_all = "lowercase")]
+pub enum DocumentVisibility {
+    /// Anyone on the LAN
+    #[default]
+    Public,
+    /// Students holding a link for this practice session
+    Session { session_id: String },
+    /// Students holding a link for this group (class)
+    Group { group: String },
+}
+
+/// Scope carried by a signed access link
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessClaims {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Restrict the link to a single document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_id: Option<String>,
+    /// Expiry (unix seconds)
+    pub expires_at: u64,
+}
+
+impl AccessClaims {
+    /// Whether these claims grant access to `doc` (ignores `available_from`)
+    fn covers(&self, doc: &Document) -> bool {
+        if self.document_id.as_deref().map(|id| id != doc.id).unwrap_or(false) {
+            return false;
+        }
+        match &doc.visibility {
+            DocumentVisibility::Public => true,
+            DocumentVisibility::Session { session_id } => {
+                self.session_id.as_ref() == Some(session_id)
+            }
+            DocumentVisibility::Group { group } => self.group.as_ref() == Some(group),
+        }
+    }
+}
+
+/// Sort key for document listings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentSort {
+    Name,
+    #[default]
+    Date,
+    Size,
+}
+
+/// Sort direction for document listings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Filters shared by `/api/documents` and the HTML page
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DocumentQuery {
+    /// Folder path; "" is the root. Unset means every folder.
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// Include documents in subfolders of `folder`
+    #[serde(default)]
+    pub recursive: bool,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Search terms matched against name and description
+    #[serde(default)]
+    pub q: Option<String>,
+    #[serde(default)]
+    pub sort: DocumentSort,
+    /// Defaults to ascending for names, descending otherwise
+    #[serde(default)]
+    pub order: Option<SortOrder>,
+}
+
+impl DocumentQuery {
+    fn search_terms(&self) -> Vec<String> {
+        self.q
+            .as_deref()
+            .unwrap_or("")
+            .split_whitespace()
+            .map(fold_search_text)
+            .collect()
+    }
+
+    fn category_filter(&self) -> Option<&str> {
+        self.category.as_deref().map(str::trim).filter(|c| !c.is_empty())
+    }
+
+    /// Whether the query searches or filters rather than browsing a folder
+    pub fn is_search(&self) -> bool {
+        !self.search_terms().is_empty() || self.category_filter().is_some()
+    }
+
+    /// Filter and sort `docs`
+    pub fn apply(&self, docs: Vec<Document>) -> Vec<Document> {
+        let folder = self.folder.as_deref().map(normalize_folder_path);
+        let terms = self.search_terms();
+        let category = self.category_filter();
+
+        let mut docs: Vec<Document> = docs
+            .into_iter()
+            .filter(|d| {
+                folder
+                    .as_ref()
+                    .map(|f| folder_contains(f.as_deref(), d.folder.as_deref(), self.recursive))
+                    .unwrap_or(true)
+            })
+            .filter(|d| category.map(|c| d.category.as_deref() == Some(c)).unwrap_or(true))
+            .filter(|d| {
+                if terms.is_empty() {
+                    return true;
+                }
+                let haystack = fold_search_text(&format!(
+                    "{} {}",
+                    d.name,
+                    d.description.as_deref().unwrap_or("")
+                ));
+                terms.iter().all(|t| haystack.contains(t.as_str()))
+            })
+            .collect();
+
+        let by_name = |a: &Document, b: &Document| {
+            fold_search_text(&a.name).cmp(&fold_search_text(&b.name))
+        };
+        docs.sort_by(|a, b| match self.sort {
+            DocumentSort::Name => by_name(a, b),
+            DocumentSort::Date => a.uploaded_at.cmp(&b.uploaded_at).then_with(|| by_name(a, b)),
+            DocumentSort::Size => a.size.cmp(&b.size).then_with(|| by_name(a, b)),
+        });
+
+        let default_order = match self.sort {
+            DocumentSort::Name => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        };
+        if self.order.unwrap_or(default_order) == SortOrder::Desc {
+            docs.reverse();
+        }
+        docs
+    }
+}
+
+/// A folder with the number of documents inside it (including subfolders)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FolderSummary {
+    pub path: String,
+    pub name: String,
+    pub document_count: usize,
+}
+
+/// Clean a folder path: `/` or `\` separated, no empty, `.` or `..` segments.
+/// Returns `None` for the root.
+pub fn normalize_folder_path(path: &str) -> Option<String> {
+    let segments: Vec<&str> = path
+        .split(['/', '\\'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty() && *s != "." && *s != "..")
+        .collect();
+    if segments.is_empty() {
+        None
+    } else {
+        Some(segments.join("/"))
+    }
+}
+
+/// Whether a document in `doc_folder` belongs to `folder` (`None` = root)
+fn folder_contains(folder: Option<&str>, doc_folder: Option<&str>, recursive: bool) -> bool {
+    match (folder, doc_folder) {
+        (None, None) => true,
+        (None, Some(_)) => recursive,
+        (Some(_), None) => false,
+        (Some(f), Some(d)) => {
+            d == f || (recursive && d.strip_prefix(f).map(|rest| rest.starts_with('/')).unwrap_or(false))
+        }
+    }
+}
+
+/// Every folder, intermediate levels included, sorted by path
+pub fn list_folders(docs: &[Document]) -> Vec<FolderSummary> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for folder in docs.iter().filter_map(|d| d.folder.as_deref()) {
+        let mut path = String::new();
+        for segment in folder.split('/') {
+            if !path.is_empty() {
+                path.push('/');
+            }
+            path.push_str(segment);
+            *counts.entry(path.clone()).or_default() += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .map(|(path, document_count)| FolderSummary {
+            name: path.rsplit('/').next().unwrap_or(&path).to_string(),
+            path,
+            document_count,
+        })
+        .collect()
+}
+
+/// Distinct categories in use, sorted
+pub fn list_categories(docs: &[Document]) -> Vec<String> {
+    docs.iter()
+        .filter_map(|d| d.category.clone())
+        .filter(|c| !c.trim().is_empty())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Lowercase and strip Vietnamese diacritics so "bai giang" finds "Bài giảng"
+fn fold_search_text(text: &str) -> String {
+    const GROUPS: [(&str, char); 7] = [
+        ("àáảãạăằắẳẵặâầấẩẫậ", 'a'),
+        ("èéẻẽẹêềếểễệ", 'e'),
+        ("ìíỉĩị", 'i'),
+        ("òóỏõọôồốổỗộơờớởỡợ", 'o'),
+        ("ùúủũụưừứửữự", 'u'),
+        ("ỳýỷỹỵ", 'y'),
+        ("đ", 'd'),
+    ];
+    text.to_lowercase()
+        .chars()
+        .map(|c| {
+            GROUPS
+                .iter()
+                .find(|(accented, _)| accented.contains(c))
+                .map(|(_, base)| *base)
+                .unwrap_or(c)
+        })
+        .collect()
+}
+
+/// A file inside a student submission
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmittedFile {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Assignment submission uploaded by a student
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Submission {
+    pub id: String,
+    pub student_name: String,
+    /// Machine name reported by the agent, or the client IP for browsers
+    pub machine_name: String,
+    pub client_ip: String,
+    pub submitted_at: u64,
+    pub note: Option<String>,
+    pub files: Vec<SubmittedFile>,
+}
+
+/// One part of a multipart/form-data body
+#[derive(Debug, Clone)]
+pub struct MultipartField {
+    pub name: String,
+    pub file_name: Option<String>,
+    pub data: Vec<u8>,
 }
 
 /// Document server state
 pub struct DocumentServerState {
     pub documents: Mutex<HashMap<String, Document>>,
+    pub submissions: Mutex<HashMap<String, Submission>>,
     pub storage_path: Mutex<Option<PathBuf>>,
     pub is_running: Mutex<bool>,
     pub server_port: Mutex<u16>,
+    shutdown_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    /// Key used to sign access links, loaded on first use
+    link_key: Mutex<Option<KeyPairInfo>>,
 }
 
 impl Default for DocumentServerState {
     fn default() -> Self {
         Self {
             documents: Mutex::new(HashMap::new()),
+            submissions: Mutex::new(HashMap::new()),
             storage_path: Mutex::new(None),
             is_running: Mutex::new(false),
             server_port: Mutex::new(DOCUMENT_SERVER_PORT),
+            shutdown_tx: Mutex::new(None),
+            link_key: Mutex::new(None),
         }
     }
 }
@@ -74,6 +402,51 @@ impl DocumentServerState {
         }
         Vec::new()
     }
+
+    /// List documents a visitor holding `claims` may see right now
+    pub fn list_visible_documents(&self, claims: Option<&AccessClaims>) -> Vec<Document> {
+        let now = now_secs();
+        self.list_documents()
+            .into_iter()
+            .filter(|d| d.is_visible_to(claims, now))
+            .collect()
+    }
+
+    /// Signing key for access links: the teacher's key pair, created and saved on
+    /// first use so links survive an app restart
+    fn link_key(&self) -> Result<KeyPairInfo, String> {
+        let mut key = self.link_key.lock().map_err(|e| e.to_string())?;
+        if let Some(key) = key.as_ref() {
+            return Ok(key.clone());
+        }
+        let keypair = crypto::ensure_keypair()?;
+        *key = Some(keypair.clone());
+        Ok(keypair)
+    }
+
+    pub fn add_submission(&self, submission: Submission) {
+        if let Ok(mut subs) = self.submissions.lock() {
+            subs.insert(submission.id.clone(), submission);
+        }
+    }
+
+    pub fn remove_submission(&self, id: &str) -> Option<Submission> {
+        self.submissions.lock().ok()?.remove(id)
+    }
+
+    pub fn get_submission(&self, id: &str) -> Option<Submission> {
+        self.submissions.lock().ok()?.get(id).cloned()
+    }
+
+    /// List submissions, newest first
+    pub fn list_submissions(&self) -> Vec<Submission> {
+        let mut subs: Vec<Submission> = match self.submissions.lock() {
+            Ok(subs) => subs.values().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        subs.sort_by_key(|s| std::cmp::Reverse(s.submitted_at));
+        subs
+    }
 }
 
 /// Get storage directory for documents
@@ -90,16 +463,45 @@ fn get_storage_dir() -> Result<PathBuf, String> {
     Ok(storage_dir)
 }
 
+/// Get storage directory for student submissions
+fn get_submissions_dir() -> Result<PathBuf, String> {
+    let dir = get_storage_dir()?.join("submissions");
+    if !dir.exists() {
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create submissions directory: {}", e))?;
+    }
+    Ok(dir)
+}
+
+/// Reduce an uploaded name to a single safe path component
+pub fn sanitize_file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control() && !matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*'))
+        .collect();
+    let cleaned = cleaned.trim().trim_matches('.').trim();
+
+    if cleaned.is_empty() {
+        "file".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
 /// Get MIME type from file extension
 fn get_mime_type(filename: &str) -> String {
     let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
     match ext.as_str() {
         "pdf" => "application/pdf",
-        "doc" | "docx" => "application/msword",
-        "xls" | "xlsx" => "application/vnd.ms-excel",
-        "ppt" | "pptx" => "application/vnd.ms-powerpoint",
-        "txt" => "text/plain",
-        "html" | "htm" => "text/html",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "txt" => "text/plain; charset=utf-8",
+        "html" | "htm" => "text/html; charset=utf-8",
         "css" => "text/css",
         "js" => "application/javascript",
         "json" => "application/json",
@@ -116,6 +518,7 @@ fn get_mime_type(filename: &str) -> String {
         "mp3" => "audio/mpeg",
         "wav" => "audio/wav",
         "mp4" => "video/mp4",
+        "webm" => "video/webm",
         "avi" => "video/x-msvideo",
         "mkv" => "video/x-matroska",
         _ => "application/octet-stream",
@@ -129,6 +532,7 @@ pub async fn save_document(
     data: Vec<u8>,
     description: Option<String>,
     category: Option<String>,
+    folder: Option<String>,
 ) -> Result<Document, String> {
     let storage_dir = get_storage_dir()?;
     
@@ -157,6 +561,9 @@ pub async fn save_document(
             .as_secs(),
         description,
         category,
+        folder: folder.as_deref().and_then(normalize_folder_path),
+        visibility: DocumentVisibility::Public,
+        available_from: None,
     };
     
     // Store metadata
@@ -192,6 +599,335 @@ pub async fn delete_document(
     Ok(())
 }
 
+/// Move a document to another folder and update its category and description
+pub fn update_document_details(
+    state: &DocumentServerState,
+    id: &str,
+    folder: Option<String>,
+    category: Option<String>,
+    description: Option<String>,
+) -> Result<Document, String> {
+    let doc = {
+        let mut docs = state.documents.lock().map_err(|e| e.to_string())?;
+        let doc = docs
+            .get_mut(id)
+            .ok_or_else(|| "Document not found".to_string())?;
+        doc.folder = folder.as_deref().and_then(normalize_folder_path);
+        doc.category = category.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+        doc.description = description.filter(|d| !d.trim().is_empty());
+        doc.clone()
+    };
+
+    save_metadata(state)?;
+    Ok(doc)
+}
+
+/// Change who can see a document and when it becomes available
+pub fn set_document_visibility(
+    state: &DocumentServerState,
+    id: &str,
+    visibility: DocumentVisibility,
+    available_from: Option<u64>,
+) -> Result<Document, String> {
+    let doc = {
+        let mut docs = state.documents.lock().map_err(|e| e.to_string())?;
+        let doc = docs
+            .get_mut(id)
+            .ok_or_else(|| "Document not found".to_string())?;
+        doc.visibility = visibility;
+        doc.available_from = available_from;
+        doc.clone()
+    };
+
+    save_metadata(state)?;
+    Ok(doc)
+}
+
+/// Sign access claims into a URL-safe token of the form `<claims>.<signature>`
+pub fn issue_access_token(state: &DocumentServerState, claims: &AccessClaims) -> Result<String, String> {
+    let key = state.link_key()?;
+    let payload = serde_json::to_vec(claims)
+        .map_err(|e| format!("Failed to serialize claims: {}", e))?;
+    let payload = URL_SAFE_NO_PAD.encode(payload);
+
+    let signature = crypto::sign_challenge(&key.private_key, payload.as_bytes())?;
+    let signature = BASE64
+        .decode(signature)
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+
+    Ok(format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature)))
+}
+
+/// Check an access token's signature and expiry
+pub fn verify_access_token(
+    state: &DocumentServerState,
+    token: &str,
+    now: u64,
+) -> Result<AccessClaims, String> {
+    let (payload, signature) = token
+        .split_once('.')
+        .ok_or_else(|| "Malformed access token".to_string())?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| "Malformed access token".to_string())?;
+
+    let key = state.link_key()?;
+    let result = crypto::verify_signature(&key.public_key, payload.as_bytes(), &BASE64.encode(signature));
+    if !result.valid {
+        return Err("Invalid access token signature".to_string());
+    }
+
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| "Malformed access token".to_string())?;
+    let claims: AccessClaims = serde_json::from_slice(&payload)
+        .map_err(|e| format!("Invalid access token: {}", e))?;
+    if claims.expires_at <= now {
+        return Err("Access token has expired".to_string());
+    }
+
+    Ok(claims)
+}
+
+/// Store a submission's files under submissions/<student>/<id>/
+pub async fn save_submission(
+    state: &DocumentServerState,
+    fields: Vec<MultipartField>,
+    client_ip: String,
+) -> Result<Submission, String> {
+    let text_field = |name: &str| {
+        fields
+            .iter()
+            .find(|f| f.name == name && f.file_name.is_none())
+            .map(|f| String::from_utf8_lossy(&f.data).trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+
+    let student_name = text_field("student_name")
+        .ok_or_else(|| "Missing student_name".to_string())?;
+    let machine_name = text_field("machine_name").unwrap_or_else(|| client_ip.clone());
+    let note = text_field("note");
+
+    let uploads: Vec<&MultipartField> = fields
+        .iter()
+        .filter(|f| f.file_name.as_deref().map(|n| !n.is_empty()).unwrap_or(false))
+        .collect();
+    if uploads.is_empty() {
+        return Err("No files in submission".to_string());
+    }
+
+    let id = format!(
+        "sub-{}-{}",
+        chrono::Utc::now().timestamp_millis(),
+        SUBMISSION_COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    let submission_dir = get_submissions_dir()?
+        .join(sanitize_file_name(&student_name))
+        .join(&id);
+    tokio::fs::create_dir_all(&submission_dir)
+        .await
+        .map_err(|e| format!("Failed to create submission directory: {}", e))?;
+
+    let mut files: Vec<SubmittedFile> = Vec::new();
+    for upload in uploads {
+        let mut name = sanitize_file_name(upload.file_name.as_deref().unwrap_or(""));
+        // Two uploads with the same name keep both
+        if files.iter().any(|f| f.name == name) {
+            name = format!("{}-{}", files.len(), name);
+        }
+
+        tokio::fs::write(submission_dir.join(&name), &upload.data)
+            .await
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+        files.push(SubmittedFile {
+            name,
+            size: upload.data.len() as u64,
+        });
+    }
+
+    let submission = Submission {
+        id,
+        student_name,
+        machine_name,
+        client_ip,
+        submitted_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+        note,
+        files,
+    };
+
+    state.add_submission(submission.clone());
+    save_submissions_metadata(state)?;
+
+    log::info!(
+        "[DocumentServer] Submission {} from {} ({} files)",
+        submission.id,
+        submission.student_name,
+        submission.files.len()
+    );
+
+    Ok(submission)
+}
+
+/// Directory holding a submission's files
+pub fn get_submission_dir(submission: &Submission) -> Result<PathBuf, String> {
+    Ok(get_submissions_dir()?
+        .join(sanitize_file_name(&submission.student_name))
+        .join(&submission.id))
+}
+
+/// Copy a submission's files into `target_dir/<student>/`
+pub async fn export_submission(
+    state: &DocumentServerState,
+    id: &str,
+    target_dir: PathBuf,
+) -> Result<String, String> {
+    let submission = state
+        .get_submission(id)
+        .ok_or_else(|| "Submission not found".to_string())?;
+    let source_dir = get_submission_dir(&submission)?;
+    let dest_dir = target_dir.join(sanitize_file_name(&submission.student_name));
+
+    tokio::fs::create_dir_all(&dest_dir)
+        .await
+        .map_err(|e| format!("Failed to create directory: {}", e))?;
+    for file in &submission.files {
+        tokio::fs::copy(source_dir.join(&file.name), dest_dir.join(&file.name))
+            .await
+            .map_err(|e| format!("Failed to copy {}: {}", file.name, e))?;
+    }
+
+    Ok(dest_dir.to_string_lossy().to_string())
+}
+
+/// Delete a submission and its files
+pub async fn delete_submission(state: &DocumentServerState, id: &str) -> Result<(), String> {
+    if let Some(submission) = state.remove_submission(id) {
+        let dir = get_submission_dir(&submission)?;
+        if dir.exists() {
+            tokio::fs::remove_dir_all(&dir)
+                .await
+                .map_err(|e| format!("Failed to delete submission: {}", e))?;
+        }
+    }
+
+    save_submissions_metadata(state)
+}
+
+/// Save submission metadata to disk
+fn save_submissions_metadata(state: &DocumentServerState) -> Result<(), String> {
+    let metadata_path = get_submissions_dir()?.join("submissions.json");
+
+    let subs = state.list_submissions();
+    let json = serde_json::to_string_pretty(&subs)
+        .map_err(|e| format!("Failed to serialize submissions: {}", e))?;
+
+    std::fs::write(&metadata_path, json)
+        .map_err(|e| format!("Failed to write submissions: {}", e))
+}
+
+/// Load submission metadata from disk
+pub fn load_submissions_metadata(state: &DocumentServerState) -> Result<(), String> {
+    let metadata_path = get_submissions_dir()?.join("submissions.json");
+    if !metadata_path.exists() {
+        return Ok(());
+    }
+
+    let json = std::fs::read_to_string(&metadata_path)
+        .map_err(|e| format!("Failed to read submissions: {}", e))?;
+    let subs: Vec<Submission> = serde_json::from_str(&json)
+        .map_err(|e| format!("Failed to parse submissions: {}", e))?;
+
+    if let Ok(mut map) = state.submissions.lock() {
+        for sub in subs {
+            map.insert(sub.id.clone(), sub);
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract the boundary from a multipart/form-data Content-Type header
+pub fn multipart_boundary(content_type: &str) -> Option<String> {
+    if !content_type.to_lowercase().starts_with("multipart/form-data") {
+        return None;
+    }
+    content_type.split(';').find_map(|param| {
+        let (key, value) = param.trim().split_once('=')?;
+        if key.trim().eq_ignore_ascii_case("boundary") {
+            Some(value.trim().trim_matches('"').to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Parse a multipart/form-data body into its fields
+pub fn parse_multipart(body: &[u8], boundary: &str) -> Result<Vec<MultipartField>, String> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut fields = Vec::new();
+
+    let mut pos = find_bytes(body, &delimiter, 0).ok_or("Missing multipart boundary")?;
+    loop {
+        pos += delimiter.len();
+        // "--" after a delimiter marks the end of the body
+        if body[pos..].starts_with(b"--") {
+            break;
+        }
+        if !body[pos..].starts_with(b"\r\n") {
+            return Err("Malformed multipart boundary".to_string());
+        }
+        pos += 2;
+
+        let header_end = find_bytes(body, b"\r\n\r\n", pos).ok_or("Malformed multipart headers")?;
+        let headers = String::from_utf8_lossy(&body[pos..header_end]).to_string();
+        let data_start = header_end + 4;
+
+        let mut next_delimiter = b"\r\n".to_vec();
+        next_delimiter.extend_from_slice(&delimiter);
+        let data_end = find_bytes(body, &next_delimiter, data_start).ok_or("Unterminated multipart field")?;
+
+        let mut name = None;
+        let mut file_name = None;
+        for line in headers.lines() {
+            let Some((key, value)) = line.split_once(':') else { continue };
+            if !key.trim().eq_ignore_ascii_case("content-disposition") {
+                continue;
+            }
+            for param in value.split(';').skip(1) {
+                let Some((k, v)) = param.trim().split_once('=') else { continue };
+                let v = v.trim().trim_matches('"').to_string();
+                match k.trim() {
+                    "name" => name = Some(v),
+                    "filename" => file_name = Some(v),
+                    _ => {}
+                }
+            }
+        }
+
+        fields.push(MultipartField {
+            name: name.ok_or("Multipart field without name")?,
+            file_name,
+            data: body[data_start..data_end].to_vec(),
+        });
+        pos = data_end + 2;
+    }
+
+    Ok(fields)
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if from > haystack.len() {
+        return None;
+    }
+    haystack[from..]
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + from)
+}
+
 /// Save metadata to disk
 fn save_metadata(state: &DocumentServerState) -> Result<(), String> {
     let storage_dir = get_storage_dir()?;
@@ -232,12 +968,12 @@ pub fn load_metadata(state: &DocumentServerState) -> Result<(), String> {
 }
 
 /// Start HTTP server for document distribution
+///
+/// Runs until `stop_document_server` is called.
 pub async fn start_document_server(
     state: Arc<DocumentServerState>,
     port: u16,
 ) -> Result<(), String> {
-    use tokio::net::TcpListener;
-
     // Check if already running
     {
         let is_running = state.is_running.lock().map_err(|e| e.to_string())?;
@@ -245,167 +981,605 @@ pub async fn start_document_server(
             return Err("Document server already running".to_string());
         }
     }
-    
+
     // Load existing metadata
     let _ = load_metadata(&state);
-    
+    let _ = load_submissions_metadata(&state);
+
     // Store storage path
     {
         let storage_dir = get_storage_dir()?;
         let mut storage_path = state.storage_path.lock().map_err(|e| e.to_string())?;
         *storage_path = Some(storage_dir);
     }
-    
+
     // Bind to port
-    let addr = format!("0.0.0.0:{}", port);
-    let listener = TcpListener::bind(&addr)
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let listener = tokio::net::TcpListener::bind(addr)
         .await
         .map_err(|e| format!("Failed to bind to {}: {}", addr, e))?;
-    
+
     log::info!("[DocumentServer] HTTP server listening on port {}", port);
-    
-    // Mark as running
+
+    // Create shutdown channel and mark as running
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
     {
+        let mut tx = state.shutdown_tx.lock().map_err(|e| e.to_string())?;
+        *tx = Some(shutdown_tx);
         let mut is_running = state.is_running.lock().map_err(|e| e.to_string())?;
         *is_running = true;
         let mut server_port = state.server_port.lock().map_err(|e| e.to_string())?;
         *server_port = port;
     }
-    
-    // Accept connections
-    loop {
-        match listener.accept().await {
-            Ok((mut stream, addr)) => {
-                log::debug!("[DocumentServer] Connection from {}", addr);
-                let state_clone = Arc::clone(&state);
-                
-                tokio::spawn(async move {
-                    if let Err(e) = handle_http_request(state_clone, &mut stream).await {
-                        log::error!("[DocumentServer] Request error: {}", e);
-                    }
-                });
-            }
-            Err(e) => {
-                log::error!("[DocumentServer] Accept error: {}", e);
-            }
-        }
+
+    let app = build_router(Arc::clone(&state));
+    let result = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async {
+        let _ = shutdown_rx.await;
+        log::info!("[DocumentServer] Shutdown signal received");
+    })
+    .await;
+
+    if let Ok(mut is_running) = state.is_running.lock() {
+        *is_running = false;
     }
+    log::info!("[DocumentServer] Server stopped");
+
+    result.map_err(|e| format!("Document server error: {}", e))
 }
 
-/// Handle HTTP request
-async fn handle_http_request(
-    state: Arc<DocumentServerState>,
-    stream: &mut tokio::net::TcpStream,
-) -> Result<(), String> {
-    use tokio::io::{AsyncBufReadExt, BufReader};
-    
-    let (reader, mut writer) = stream.split();
-    let mut buf_reader = BufReader::new(reader);
-    
-    // Read request line
-    let mut request_line = String::new();
-    buf_reader.read_line(&mut request_line)
-        .await
-        .map_err(|e| format!("Failed to read request: {}", e))?;
-    
-    let parts: Vec<&str> = request_line.trim().split_whitespace().collect();
-    if parts.len() < 2 {
-        let response = build_error_response(400, "Bad Request");
-        writer.write_all(response.as_bytes()).await.map_err(|e| e.to_string())?;
-        return Ok(());
+/// Stop the HTTP server started by `start_document_server`
+pub fn stop_document_server(state: &DocumentServerState) -> Result<(), String> {
+    let shutdown_tx = state.shutdown_tx.lock().map_err(|e| e.to_string())?.take();
+    if let Some(tx) = shutdown_tx {
+        let _ = tx.send(());
     }
-    
-    let method = parts[0];
-    let path = parts[1];
-    
-    // Read headers (skip them for now)
-    loop {
-        let mut line = String::new();
-        buf_reader.read_line(&mut line)
-            .await
-            .map_err(|e| format!("Failed to read header: {}", e))?;
-        
-        if line.trim().is_empty() {
-            break;
-        }
+
+    let mut is_running = state.is_running.lock().map_err(|e| e.to_string())?;
+    *is_running = false;
+    Ok(())
+}
+
+/// Build the document server router
+fn build_router(state: Arc<DocumentServerState>) -> Router {
+    Router::new()
+        .route("/", get(serve_index))
+        .route("/index.html", get(serve_index))
+        .route("/api/documents", get(serve_document_list).options(cors_preflight))
+        .route("/api/folders", get(serve_folder_list).options(cors_preflight))
+        .route("/download/:id", get(serve_download).options(cors_preflight))
+        .route("/download-zip", get(serve_zip_download).options(cors_preflight))
+        .route(
+            "/api/submissions",
+            post(handle_submission_upload)
+                .options(cors_preflight)
+                .layer(DefaultBodyLimit::max(MAX_SUBMISSION_SIZE)),
+        )
+        .fallback(|| async { error_response(StatusCode::NOT_FOUND, "Not Found") })
+        .layer(middleware::map_response(add_cors_header))
+        .with_state(state)
+}
+
+/// Allow pages served from other origins (teacher dashboard) to call the API
+async fn add_cors_header(mut response: Response) -> Response {
+    response.headers_mut().insert(
+        header::ACCESS_CONTROL_ALLOW_ORIGIN,
+        HeaderValue::from_static("*"),
+    );
+    response
+}
+
+/// CORS preflight response
+async fn cors_preflight() -> Response {
+    (
+        StatusCode::NO_CONTENT,
+        [
+            (header::ACCESS_CONTROL_ALLOW_METHODS, "GET, POST, OPTIONS"),
+            (header::ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, Range"),
+            (header::ACCESS_CONTROL_MAX_AGE, "86400"),
+        ],
+    )
+        .into_response()
+}
+
+/// Query parameters for the index page
+#[derive(Debug, Default, Deserialize)]
+struct IndexQuery {
+    #[serde(default)]
+    submitted: Option<u8>,
+    #[serde(default)]
+    token: Option<String>,
+}
+
+/// Handler for the HTML document list
+async fn serve_index(
+    State(state): State<Arc<DocumentServerState>>,
+    Query(query): Query<IndexQuery>,
+    Query(filter): Query<DocumentQuery>,
+) -> Response {
+    let claims = match request_claims(&state, query.token.as_deref()) {
+        Ok(c) => c,
+        Err(e) => return invalid_link_response(&e),
+    };
+    let docs = state.list_visible_documents(claims.as_ref());
+    Html(build_document_list_html(
+        &docs,
+        &filter,
+        query.token.as_deref(),
+        query.submitted == Some(1),
+    ))
+    .into_response()
+}
+
+/// Query parameters for the JSON document list
+#[derive(Debug, Default, Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    token: Option<String>,
+}
+
+/// Handler for the JSON document list
+async fn serve_document_list(
+    State(state): State<Arc<DocumentServerState>>,
+    Query(query): Query<ListQuery>,
+    Query(filter): Query<DocumentQuery>,
+) -> Response {
+    match request_claims(&state, query.token.as_deref()) {
+        Ok(claims) => Json(filter.apply(state.list_visible_documents(claims.as_ref()))).into_response(),
+        Err(e) => invalid_link_response(&e),
     }
-    
-    log::info!("[DocumentServer] {} {}", method, path);
-    
-    // Route request
-    let response = match (method, path) {
-        ("GET", "/") | ("GET", "/index.html") => {
-            build_document_list_html(&state)
-        }
-        ("GET", "/api/documents") => {
-            build_document_list_json(&state)
-        }
-        ("GET", p) if p.starts_with("/download/") => {
-            let doc_id = &p[10..]; // Remove "/download/"
-            build_document_download(&state, doc_id).await
+}
+
+/// Handler for the folder tree and categories
+async fn serve_folder_list(
+    State(state): State<Arc<DocumentServerState>>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    match request_claims(&state, query.token.as_deref()) {
+        Ok(claims) => {
+            let docs = state.list_visible_documents(claims.as_ref());
+            Json(serde_json::json!({
+                "folders": list_folders(&docs),
+                "categories": list_categories(&docs),
+            }))
+            .into_response()
         }
-        ("OPTIONS", _) => {
-            build_cors_preflight()
+        Err(e) => invalid_link_response(&e),
+    }
+}
+
+/// Query parameters for downloads
+#[derive(Debug, Default, Deserialize)]
+struct DownloadQuery {
+    /// Show the file in the browser (PDF, video) instead of saving it
+    #[serde(default)]
+    inline: Option<u8>,
+    #[serde(default)]
+    token: Option<String>,
+}
+
+/// Handler for document downloads
+async fn serve_download(
+    State(state): State<Arc<DocumentServerState>>,
+    Path(id): Path<String>,
+    Query(query): Query<DownloadQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let claims = match request_claims(&state, query.token.as_deref()) {
+        Ok(c) => c,
+        Err(e) => return invalid_link_response(&e),
+    };
+    serve_document_file(&state, &id, claims.as_ref(), &headers, query.inline == Some(1)).await
+}
+
+/// Query parameters for ZIP downloads: explicit ids, or a category and/or folder
+#[derive(Debug, Default, Deserialize)]
+struct ZipQuery {
+    /// Comma-separated document ids
+    #[serde(default)]
+    ids: Option<String>,
+    #[serde(default)]
+    category: Option<String>,
+    /// Folder to download, subfolders included
+    #[serde(default)]
+    folder: Option<String>,
+    #[serde(default)]
+    token: Option<String>,
+}
+
+/// Handler streaming several documents as one ZIP archive
+async fn serve_zip_download(
+    State(state): State<Arc<DocumentServerState>>,
+    Query(query): Query<ZipQuery>,
+) -> Response {
+    let claims = match request_claims(&state, query.token.as_deref()) {
+        Ok(c) => c,
+        Err(e) => return invalid_link_response(&e),
+    };
+    let docs = match select_zip_documents(state.list_visible_documents(claims.as_ref()), &query) {
+        Ok(docs) if !docs.is_empty() => docs,
+        Ok(_) => return error_response(StatusCode::NOT_FOUND, "No documents found"),
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e),
+    };
+
+    // Sizes come from metadata so the archive length is exact; files are only opened
+    // while streaming. A missing document fails the request rather than being left out.
+    let base = query.folder.as_deref().and_then(normalize_folder_path);
+    let names = zip_entry_names(&docs, base.as_deref());
+    let mut files = Vec::new();
+    for (doc, name) in docs.iter().zip(names) {
+        let path = match document_file_path(&state, &doc.id) {
+            Ok(path) => path,
+            Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e),
+        };
+        let size = match tokio::fs::metadata(&path).await {
+            Ok(m) if m.is_file() => m.len(),
+            Ok(_) => return error_response(StatusCode::NOT_FOUND, "Document file not found"),
+            Err(e) => {
+                log::warn!("[DocumentServer] Cannot add {} to ZIP: {}", doc.id, e);
+                let status = if e.kind() == std::io::ErrorKind::NotFound {
+                    StatusCode::NOT_FOUND
+                } else {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                };
+                return error_response(status, "Document file not readable");
+            }
+        };
+        files.push((ZipEntry { name, size, modified: doc.uploaded_at }, path));
+    }
+
+    let entries: Vec<ZipEntry> = files.iter().map(|(entry, _)| entry.clone()).collect();
+    let total_size = match zip_stream::archive_size(&entries) {
+        Ok(size) => size,
+        Err(e) => return error_response(StatusCode::PAYLOAD_TOO_LARGE, &e),
+    };
+    log::info!(
+        "[DocumentServer] Streaming ZIP of {} documents ({})",
+        entries.len(),
+        format_file_size(total_size)
+    );
+
+    let (writer, reader) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        let mut zip = ZipStreamWriter::new(writer);
+        for (entry, path) in files {
+            let file = match File::open(&path).await {
+                Ok(f) => f,
+                Err(e) => {
+                    // Removed after the response started; the client gets a truncated archive
+                    log::warn!("[DocumentServer] ZIP download stopped: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = zip.add_entry(&entry, file).await {
+                // Also hit when the client disconnects; it gets a truncated archive
+                log::warn!("[DocumentServer] ZIP download stopped: {}", e);
+                return;
+            }
         }
-        _ => {
-            build_error_response(404, "Not Found")
+        if let Err(e) = zip.finish().await {
+            log::warn!("[DocumentServer] ZIP download stopped: {}", e);
         }
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(header::CONTENT_DISPOSITION, content_disposition(&zip_archive_name(&query), false))
+        .header(header::CONTENT_LENGTH, total_size)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from_stream(ReaderStream::new(reader)))
+        .unwrap()
+}
+
+/// Documents for a ZIP download, in archive order
+fn select_zip_documents(docs: Vec<Document>, query: &ZipQuery) -> Result<Vec<Document>, String> {
+    if let Some(ids) = query.ids.as_deref() {
+        let mut by_id: HashMap<String, Document> =
+            docs.into_iter().map(|d| (d.id.clone(), d)).collect();
+        // Removing as we go also drops repeated ids
+        return Ok(ids
+            .split(',')
+            .filter_map(|id| by_id.remove(id.trim()))
+            .collect());
+    }
+
+    if query.category.is_none() && query.folder.is_none() {
+        return Err("Specify ids, category or folder".to_string());
+    }
+    let filter = DocumentQuery {
+        folder: query.folder.clone(),
+        recursive: true,
+        category: query.category.clone(),
+        sort: DocumentSort::Name,
+        ..Default::default()
     };
-    
-    writer.write_all(response.as_bytes()).await.map_err(|e| e.to_string())?;
-    Ok(())
+    Ok(filter.apply(docs))
+}
+
+/// Paths inside the archive: folder relative to `base`, then the file name.
+/// Clashing paths get a " (n)" suffix like duplicate downloads do.
+fn zip_entry_names(docs: &[Document], base: Option<&str>) -> Vec<String> {
+    let mut used = BTreeSet::new();
+    docs.iter()
+        .map(|doc| {
+            let folder = match (doc.folder.as_deref(), base) {
+                (Some(folder), Some(base)) if folder == base => None,
+                (Some(folder), Some(base)) => Some(
+                    folder
+                        .strip_prefix(base)
+                        .and_then(|f| f.strip_prefix('/'))
+                        .unwrap_or(folder),
+                ),
+                (folder, _) => folder,
+            };
+            let file_name = sanitize_file_name(&doc.name);
+            let (stem, ext) = match file_name.rsplit_once('.') {
+                Some((stem, ext)) if !stem.is_empty() => (stem.to_string(), format!(".{}", ext)),
+                _ => (file_name.clone(), String::new()),
+            };
+
+            let mut counter = 1;
+            loop {
+                let candidate_name = if counter == 1 {
+                    file_name.clone()
+                } else {
+                    format!("{} ({}){}", stem, counter, ext)
+                };
+                let candidate = match folder {
+                    Some(folder) => format!("{}/{}", folder, candidate_name),
+                    None => candidate_name,
+                };
+                // Windows and macOS treat names differing only in case as the same file
+                if used.insert(candidate.to_lowercase()) {
+                    return candidate;
+                }
+                counter += 1;
+            }
+        })
+        .collect()
+}
+
+/// File name offered for a ZIP download
+fn zip_archive_name(query: &ZipQuery) -> String {
+    let folder = query.folder.as_deref().and_then(normalize_folder_path);
+    let label = folder
+        .as_deref()
+        .and_then(|f| f.rsplit('/').next())
+        .or(query.category.as_deref().map(str::trim).filter(|c| !c.is_empty()))
+        .unwrap_or("tai-lieu");
+    format!("{}.zip", sanitize_file_name(label))
 }
 
-/// Build CORS preflight response
-fn build_cors_preflight() -> String {
-    "HTTP/1.1 204 No Content\r\n\
-        Access-Control-Allow-Origin: *\r\n\
-        Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
-        Access-Control-Allow-Headers: Content-Type\r\n\
-        Access-Control-Max-Age: 86400\r\n\
-        \r\n".to_string()
+/// Resolve the `token` query parameter; a bad or expired link is rejected outright
+fn request_claims(
+    state: &DocumentServerState,
+    token: Option<&str>,
+) -> Result<Option<AccessClaims>, String> {
+    match token.filter(|t| !t.is_empty()) {
+        Some(token) => verify_access_token(state, token, now_secs()).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Response for a bad or expired access link
+fn invalid_link_response(error: &str) -> Response {
+    log::warn!("[DocumentServer] Rejected access token: {}", error);
+    error_response(StatusCode::FORBIDDEN, "Liên kết không hợp lệ hoặc đã hết hạn")
+}
+
+/// Handle a multipart submission upload from a browser or agent
+async fn handle_submission_upload(
+    State(state): State<Arc<DocumentServerState>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let boundary = match multipart_boundary(content_type) {
+        Some(b) => b,
+        None => return error_response(StatusCode::UNSUPPORTED_MEDIA_TYPE, "Unsupported Media Type"),
+    };
+    let fields = match parse_multipart(&body, &boundary) {
+        Ok(f) => f,
+        Err(e) => {
+            log::warn!("[DocumentServer] Bad submission from {}: {}", peer_addr, e);
+            return error_response(StatusCode::BAD_REQUEST, "Bad Request");
+        }
+    };
+
+    // Browser form posts ask to be sent back to a page instead of JSON
+    let redirect = fields
+        .iter()
+        .find(|f| f.name == "redirect" && f.file_name.is_none())
+        .map(|f| String::from_utf8_lossy(&f.data).to_string())
+        .filter(|r| r.starts_with('/') && !r.starts_with("//"));
+
+    match save_submission(&state, fields, peer_addr.ip().to_string()).await {
+        Ok(submission) => match redirect {
+            Some(location) => Redirect::to(&location).into_response(),
+            None => (StatusCode::CREATED, Json(submission)).into_response(),
+        },
+        Err(e) => {
+            log::warn!("[DocumentServer] Submission rejected from {}: {}", peer_addr, e);
+            error_response(StatusCode::BAD_REQUEST, &e)
+        }
+    }
 }
 
-/// Build error response
-fn build_error_response(status: u16, message: &str) -> String {
+/// Build HTML error page
+fn error_response(status: StatusCode, message: &str) -> Response {
+    let code = status.as_u16();
     let body = format!(r#"<!DOCTYPE html>
 <html><head><title>{} {}</title></head>
-<body><h1>{} {}</h1></body></html>"#, status, message, status, message);
-    
-    format!(
-        "HTTP/1.1 {} {}\r\n\
-        Content-Type: text/html; charset=utf-8\r\n\
-        Content-Length: {}\r\n\
-        Access-Control-Allow-Origin: *\r\n\
-        \r\n{}",
-        status, message, body.len(), body
-    )
-}
+<body><h1>{} {}</h1></body></html>"#, code, message, code, message);
 
+    (status, Html(body)).into_response()
+}
 /// Build document list as HTML page
-fn build_document_list_html(state: &DocumentServerState) -> String {
-    let docs = state.list_documents();
-    
+fn build_document_list_html(
+    docs: &[Document],
+    filter: &DocumentQuery,
+    token: Option<&str>,
+    submitted: bool,
+) -> String {
+    // Keep the access link on every URL the page generates
+    let token_query = token.map(|t| format!("token={}", urlencoding_encode(t)));
+    let download_suffix = token_query.as_ref().map(|q| format!("?{}", q)).unwrap_or_default();
+    let redirect = match &token_query {
+        Some(q) => format!("/?submitted=1&amp;{}", q),
+        None => "/?submitted=1".to_string(),
+    };
+    let folder_url = |path: Option<&str>| {
+        let params: Vec<String> = path
+            .map(|p| format!("folder={}", urlencoding_encode(p)))
+            .into_iter()
+            .chain(token_query.clone())
+            .collect();
+        if params.is_empty() {
+            "/".to_string()
+        } else {
+            format!("/?{}", params.join("&amp;"))
+        }
+    };
+
+    // Without a search, browse one folder level at a time starting at the root
+    let searching = filter.is_search();
+    let current_folder = filter.folder.as_deref().and_then(normalize_folder_path);
+    let mut effective = filter.clone();
+    if searching {
+        effective.recursive = true;
+    } else if effective.folder.is_none() {
+        effective.folder = Some(String::new());
+    }
+    let results = effective.apply(docs.to_vec());
+
+    let mut breadcrumb = format!(r#"<a href="{}">Tất cả</a>"#, folder_url(None));
+    if let Some(current) = &current_folder {
+        let mut path = String::new();
+        for segment in current.split('/') {
+            if !path.is_empty() {
+                path.push('/');
+            }
+            path.push_str(segment);
+            breadcrumb.push_str(&format!(
+                r#" / <a href="{}">{}</a>"#,
+                folder_url(Some(&path)),
+                html_escape(segment)
+            ));
+        }
+    }
+
     let mut doc_rows = String::new();
-    for doc in &docs {
+    if !searching {
+        let subfolders = list_folders(docs)
+            .into_iter()
+            .filter(|f| f.path.rsplit_once('/').map(|(parent, _)| parent) == current_folder.as_deref());
+        for folder in subfolders {
+            doc_rows.push_str(&format!(
+                r#"<tr>
+                <td><a href="{}" class="folder-link">{}</a></td>
+                <td>{} tài liệu</td>
+                <td>Thư mục</td>
+                <td></td>
+            </tr>"#,
+                folder_url(Some(&folder.path)),
+                html_escape(&folder.name),
+                folder.document_count
+            ));
+        }
+    }
+    for doc in &results {
         let size_str = format_file_size(doc.size);
         let date_str = format_timestamp(doc.uploaded_at);
         doc_rows.push_str(&format!(
             r#"<tr>
-                <td><a href="/download/{}" class="file-link">{}</a></td>
+                <td><a href="/download/{}{}" class="file-link">{}</a></td>
                 <td>{}</td>
                 <td>{}</td>
                 <td>{}</td>
             </tr>"#,
-            doc.id, doc.name, size_str, doc.mime_type, date_str
+            doc.id, download_suffix, html_escape(&doc.name), size_str, doc.mime_type, date_str
         ));
     }
     
-    if docs.is_empty() {
-        doc_rows = r#"<tr><td colspan="4" style="text-align:center;color:#666;">Chưa có tài liệu nào</td></tr>"#.to_string();
+    if doc_rows.is_empty() {
+        let message = if searching { "Không tìm thấy tài liệu phù hợp" } else { "Chưa có tài liệu nào" };
+        doc_rows = format!(r#"<tr><td colspan="4" style="text-align:center;color:#666;">{}</td></tr>"#, message);
     }
+
+    // Everything listed on this page as one archive
+    let zip_link = if results.is_empty() {
+        String::new()
+    } else {
+        let ids: Vec<&str> = results.iter().map(|d| d.id.as_str()).collect();
+        let mut href = format!("/download-zip?ids={}", urlencoding_encode(&ids.join(",")));
+        if let Some(q) = &token_query {
+            href.push_str("&amp;");
+            href.push_str(q);
+        }
+        format!(r#"<a href="{}" class="zip-link">⬇ Tải tất cả (.zip)</a>"#, href)
+    };
+
+    // Search / filter / sort toolbar; searching inside a folder covers its subfolders
+    let mut hidden_fields = String::new();
+    if let Some(current) = &current_folder {
+        hidden_fields.push_str(&format!(
+            r#"<input type="hidden" name="folder" value="{}">"#,
+            html_escape(current)
+        ));
+    }
+    if let Some(token) = token {
+        hidden_fields.push_str(&format!(r#"<input type="hidden" name="token" value="{}">"#, html_escape(token)));
+    }
+    let category_options: String = list_categories(docs)
+        .iter()
+        .map(|c| {
+            let selected = if filter.category_filter() == Some(c.as_str()) { " selected" } else { "" };
+            format!(r#"<option value="{0}"{1}>{0}</option>"#, html_escape(c), selected)
+        })
+        .collect();
+    let sort_options: String = [
+        (DocumentSort::Date, "date", "Mới nhất"),
+        (DocumentSort::Name, "name", "Tên"),
+        (DocumentSort::Size, "size", "Kích thước"),
+    ]
+    .iter()
+    .map(|(sort, value, label)| {
+        let selected = if filter.sort == *sort { " selected" } else { "" };
+        format!(r#"<option value="{}"{}>{}</option>"#, value, selected, label)
+    })
+    .collect();
+    let toolbar = format!(
+        r#"<form class="toolbar" method="get" action="/">
+                {}
+                <input type="text" name="q" value="{}" placeholder="Tìm theo tên hoặc mô tả">
+                <select name="category"><option value="">Tất cả danh mục</option>{}</select>
+                <select name="sort">{}</select>
+                <button type="submit">Lọc</button>
+            </form>
+            <div class="breadcrumb">📁 {}{}</div>"#,
+        hidden_fields,
+        html_escape(filter.q.as_deref().unwrap_or("")),
+        category_options,
+        sort_options,
+        breadcrumb,
+        zip_link
+    );
+    
+    let notice = if submitted {
+        r#"<div class="notice">✅ Đã nộp bài thành công</div>"#
+    } else {
+        ""
+    };
     
-    let html = format!(r#"<!DOCTYPE html>
+    format!(r#"<!DOCTYPE html>
 <html lang="vi">
 <head>
     <meta charset="UTF-8">
@@ -443,11 +1617,26 @@ fn build_document_list_html(state: &DocumentServerState) -> String {
         .file-link {{ color: #4f46e5; text-decoration: none; font-weight: 500; }}
         .file-link:hover {{ text-decoration: underline; }}
         .file-link::before {{ content: '📄 '; }}
+        .folder-link {{ color: #374151; text-decoration: none; font-weight: 600; }}
+        .folder-link::before {{ content: '📁 '; }}
+        .toolbar {{ display: flex; flex-wrap: wrap; gap: 12px; margin-bottom: 12px; }}
+        .toolbar input[type=text] {{ flex: 1; min-width: 200px; padding: 10px; border: 1px solid #d1d5db; border-radius: 8px; }}
+        .toolbar select {{ padding: 10px; border: 1px solid #d1d5db; border-radius: 8px; }}
+        .toolbar button {{ background: #4f46e5; color: white; border: none; padding: 10px 20px; border-radius: 8px; cursor: pointer; }}
+        .breadcrumb {{ margin-bottom: 12px; color: #6b7280; font-size: 14px; }}
+        .breadcrumb a {{ color: #4f46e5; text-decoration: none; }}
+        .zip-link {{ float: right; font-weight: 600; }}
         .stats {{ display: flex; gap: 20px; margin-bottom: 20px; }}
         .stat-card {{ background: #f3f4f6; padding: 16px 24px; border-radius: 12px; flex: 1; }}
         .stat-card h3 {{ font-size: 24px; color: #4f46e5; }}
         .stat-card p {{ font-size: 13px; color: #6b7280; }}
         .footer {{ text-align: center; padding: 20px; background: #f9fafb; color: #6b7280; font-size: 13px; }}
+        .notice {{ background: #dcfce7; color: #166534; padding: 12px 16px; border-radius: 12px; margin-bottom: 20px; }}
+        .submit-box {{ margin-top: 30px; padding: 20px; background: #f9fafb; border-radius: 12px; }}
+        .submit-box h2 {{ font-size: 18px; margin-bottom: 12px; color: #374151; }}
+        .submit-box form {{ display: flex; flex-wrap: wrap; gap: 12px; }}
+        .submit-box input[type=text] {{ flex: 1; min-width: 200px; padding: 10px; border: 1px solid #d1d5db; border-radius: 8px; }}
+        .submit-box button {{ background: #4f46e5; color: white; border: none; padding: 10px 20px; border-radius: 8px; cursor: pointer; }}
     </style>
 </head>
 <body>
@@ -457,6 +1646,7 @@ fn build_document_list_html(state: &DocumentServerState) -> String {
             <p>Tải tài liệu học tập từ giáo viên</p>
         </div>
         <div class="content">
+            {}
             <div class="stats">
                 <div class="stat-card">
                     <h3>{}</h3>
@@ -467,6 +1657,7 @@ fn build_document_list_html(state: &DocumentServerState) -> String {
                     <p>Tổng dung lượng</p>
                 </div>
             </div>
+            {}
             <table>
                 <thead>
                     <tr>
@@ -480,6 +1671,16 @@ fn build_document_list_html(state: &DocumentServerState) -> String {
                     {}
                 </tbody>
             </table>
+            <div class="submit-box">
+                <h2>📤 Nộp bài</h2>
+                <form action="/api/submissions" method="post" enctype="multipart/form-data">
+                    <input type="hidden" name="redirect" value="{}">
+                    <input type="text" name="student_name" placeholder="Họ và tên" required>
+                    <input type="text" name="note" placeholder="Ghi chú (không bắt buộc)">
+                    <input type="file" name="file" multiple required>
+                    <button type="submit">Nộp bài</button>
+                </form>
+            </div>
         </div>
         <div class="footer">
             SmartLab - Hệ thống quản lý phòng máy thông minh
@@ -487,81 +1688,155 @@ fn build_document_list_html(state: &DocumentServerState) -> String {
     </div>
 </body>
 </html>"#,
+        notice,
         docs.len(),
         format_file_size(docs.iter().map(|d| d.size).sum()),
-        doc_rows
-    );
-    
-    format!(
-        "HTTP/1.1 200 OK\r\n\
-        Content-Type: text/html; charset=utf-8\r\n\
-        Content-Length: {}\r\n\
-        Access-Control-Allow-Origin: *\r\n\
-        \r\n{}",
-        html.len(), html
+        toolbar,
+        doc_rows,
+        redirect
     )
 }
 
-/// Build document list as JSON
-fn build_document_list_json(state: &DocumentServerState) -> String {
-    let docs = state.list_documents();
-    let json = serde_json::to_string(&docs).unwrap_or_else(|_| "[]".to_string());
-    
+/// Path of a stored document's file
+fn document_file_path(state: &DocumentServerState, doc_id: &str) -> Result<PathBuf, String> {
+    let storage_dir = match state.storage_path.lock().ok().and_then(|p| p.clone()) {
+        Some(dir) => dir,
+        None => get_storage_dir()?,
+    };
+    Ok(storage_dir.join(doc_id))
+}
+
+/// Entity tag for a document. Stored files never change under the same id.
+fn document_etag(doc: &Document) -> String {
+    format!("\"{}-{:x}-{:x}\"", doc.id, doc.size, doc.uploaded_at)
+}
+
+/// Check an If-None-Match / If-Range header value against an entity tag
+fn etag_matches(header_value: &str, etag: &str) -> bool {
+    header_value
+        .split(',')
+        .map(|t| t.trim())
+        .any(|t| t == "*" || t.trim_start_matches("W/") == etag)
+}
+
+/// Build Content-Disposition with an ASCII fallback and the UTF-8 name
+fn content_disposition(name: &str, inline: bool) -> String {
+    let fallback: String = name
+        .chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
     format!(
-        "HTTP/1.1 200 OK\r\n\
-        Content-Type: application/json\r\n\
-        Content-Length: {}\r\n\
-        Access-Control-Allow-Origin: *\r\n\
-        \r\n{}",
-        json.len(), json
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        if inline { "inline" } else { "attachment" },
+        fallback,
+        urlencoding_encode(name)
     )
 }
 
-/// Build document download response
-async fn build_document_download(state: &DocumentServerState, doc_id: &str) -> String {
+/// Stream a document, honouring Range, If-Range and If-None-Match
+async fn serve_document_file(
+    state: &DocumentServerState,
+    doc_id: &str,
+    claims: Option<&AccessClaims>,
+    headers: &HeaderMap,
+    inline: bool,
+) -> Response {
     // Get document metadata
     let doc = match state.get_document(doc_id) {
         Some(d) => d,
-        None => return build_error_response(404, "Document not found"),
+        None => return error_response(StatusCode::NOT_FOUND, "Document not found"),
     };
-    
-    // Get file path
-    let storage_dir = match get_storage_dir() {
-        Ok(d) => d,
-        Err(_) => return build_error_response(500, "Storage error"),
-    };
-    let file_path = storage_dir.join(doc_id);
-    
-    if !file_path.exists() {
-        return build_error_response(404, "File not found");
+    if !doc.is_visible_to(claims, now_secs()) {
+        log::warn!("[DocumentServer] Download of {} outside its visibility scope", doc.id);
+        return error_response(StatusCode::FORBIDDEN, "Forbidden");
     }
-    
-    // Read file
-    let data = match tokio::fs::read(&file_path).await {
-        Ok(d) => d,
-        Err(_) => return build_error_response(500, "Failed to read file"),
+
+    let file_path = match document_file_path(state, &doc.id) {
+        Ok(p) => p,
+        Err(_) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Storage error"),
     };
-    
-    // URL encode filename for Content-Disposition
-    let encoded_name = urlencoding_encode(&doc.name);
-    
-    // Build response with file
-    let headers = format!(
-        "HTTP/1.1 200 OK\r\n\
-        Content-Type: {}\r\n\
-        Content-Length: {}\r\n\
-        Content-Disposition: attachment; filename=\"{}\"; filename*=UTF-8''{}\r\n\
-        Access-Control-Allow-Origin: *\r\n\
-        \r\n",
-        doc.mime_type, data.len(), doc.name, encoded_name
-    );
-    
-    // Combine headers and data
-    let mut response = headers.into_bytes();
-    response.extend(data);
-    
-    // Convert to string (this is a hack, but works for binary data)
-    unsafe { String::from_utf8_unchecked(response) }
+    let mut file = match File::open(&file_path).await {
+        Ok(f) => f,
+        Err(_) => return error_response(StatusCode::NOT_FOUND, "File not found"),
+    };
+    // Trust the file on disk over metadata for the byte count
+    let file_size = match file.metadata().await {
+        Ok(m) => m.len(),
+        Err(_) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read file"),
+    };
+
+    let etag = document_etag(&doc);
+    let header_str = |name: header::HeaderName| headers.get(name).and_then(|v| v.to_str().ok());
+
+    if header_str(header::IF_NONE_MATCH).map(|v| etag_matches(v, &etag)).unwrap_or(false) {
+        return Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, &etag)
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    // A stale If-Range means the client's partial copy is outdated: send everything
+    let range = header_str(header::RANGE)
+        .filter(|_| header_str(header::IF_RANGE).map(|v| etag_matches(v, &etag)).unwrap_or(true));
+
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, &doc.mime_type)
+        .header(header::CONTENT_DISPOSITION, content_disposition(&doc.name, inline))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, "no-cache");
+
+    if let Some(range) = range {
+        let (start, end) = match parse_range_header(range, file_size) {
+            Some(r) => r,
+            None => {
+                return (
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(header::CONTENT_RANGE, format!("bytes */{}", file_size))],
+                )
+                    .into_response();
+            }
+        };
+
+        if let Err(e) = file.seek(std::io::SeekFrom::Start(start)).await {
+            log::error!("[DocumentServer] Failed to seek in {}: {}", doc.id, e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read file");
+        }
+        let content_length = end - start + 1;
+
+        return builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_LENGTH, content_length)
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, file_size),
+            )
+            .body(Body::from_stream(ReaderStream::new(file.take(content_length))))
+            .unwrap();
+    }
+
+    builder
+        .status(StatusCode::OK)
+        .header(header::CONTENT_LENGTH, file_size)
+        .body(Body::from_stream(ReaderStream::new(file)))
+        .unwrap()
+}
+
+/// Escape text for HTML content and attribute values
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
 }
 
 /// Simple URL encoding for filenames
@@ -599,6 +1874,14 @@ fn format_file_size(size: u64) -> String {
     }
 }
 
+/// Current unix time in seconds
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 /// Format timestamp for display
 fn format_timestamp(ts: u64) -> String {
     use chrono::{TimeZone, Utc};
@@ -608,3 +1891,483 @@ fn format_timestamp(ts: u64) -> String {
         None => "N/A".to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multipart_body(boundary: &str, parts: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, file_name, data) in parts {
+            body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            match file_name {
+                Some(f) => body.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                         Content-Type: application/octet-stream\r\n\r\n",
+                        name, f
+                    )
+                    .as_bytes(),
+                ),
+                None => body.extend_from_slice(
+                    format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes(),
+                ),
+            }
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+        body
+    }
+
+    #[test]
+    fn test_multipart_boundary() {
+        assert_eq!(
+            multipart_boundary("multipart/form-data; boundary=----WebKitFormBoundaryX").as_deref(),
+            Some("----WebKitFormBoundaryX")
+        );
+        assert_eq!(
+            multipart_boundary("multipart/form-data; boundary=\"abc\"").as_deref(),
+            Some("abc")
+        );
+        assert!(multipart_boundary("application/json").is_none());
+    }
+
+    #[test]
+    fn test_parse_multipart_text_and_binary_fields() {
+        let binary: Vec<u8> = (0..=255u8).chain(b"\r\n--not-the-boundary".iter().copied()).collect();
+        let body = multipart_body(
+            "XyZ",
+            &[
+                ("student_name", None, "Nguyễn Văn A".as_bytes()),
+                ("file", Some("bai1.zip"), &binary),
+            ],
+        );
+
+        let fields = parse_multipart(&body, "XyZ").unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].name, "student_name");
+        assert_eq!(String::from_utf8_lossy(&fields[0].data), "Nguyễn Văn A");
+        assert_eq!(fields[1].file_name.as_deref(), Some("bai1.zip"));
+        assert_eq!(fields[1].data, binary);
+    }
+
+    #[test]
+    fn test_parse_multipart_rejects_garbage() {
+        assert!(parse_multipart(b"no boundary here", "XyZ").is_err());
+        assert!(parse_multipart(b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nunterminated", "XyZ").is_err());
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("bai1.py"), "bai1.py");
+        assert_eq!(sanitize_file_name("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_file_name("C:\\Users\\a\\bai1.py"), "bai1.py");
+        assert_eq!(sanitize_file_name(".."), "file");
+        assert_eq!(sanitize_file_name("a<b>:c?.txt"), "abc.txt");
+    }
+
+    fn state_with_document(name: &str, data: &[u8]) -> (tempfile::TempDir, DocumentServerState, Document) {
+        let dir = tempfile::tempdir().unwrap();
+        let doc = Document {
+            id: "doc-1".to_string(),
+            name: name.to_string(),
+            size: data.len() as u64,
+            mime_type: get_mime_type(name),
+            uploaded_at: 1_700_000_000,
+            description: None,
+            category: None,
+            folder: None,
+            visibility: DocumentVisibility::Public,
+            available_from: None,
+        };
+        std::fs::write(dir.path().join(&doc.id), data).unwrap();
+
+        let state = DocumentServerState::new();
+        *state.storage_path.lock().unwrap() = Some(dir.path().to_path_buf());
+        state.add_document(doc.clone());
+        (dir, state, doc)
+    }
+
+    fn binary_payload() -> Vec<u8> {
+        // Every byte value, invalid UTF-8 included, spanning several stream chunks
+        (0..70_000u32).map(|i| (i.wrapping_mul(31) % 256) as u8).collect()
+    }
+
+    async fn body_bytes(response: Response) -> Vec<u8> {
+        use http_body_util::BodyExt;
+        response.into_body().collect().await.unwrap().to_bytes().to_vec()
+    }
+
+    fn request_headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_download_preserves_binary_content() {
+        let data = binary_payload();
+        let (_dir, state, doc) = state_with_document("video.mp4", &data);
+
+        let response = serve_document_file(&state, &doc.id, None, &HeaderMap::new(), false).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "video/mp4");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], data.len().to_string().as_str());
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(response.headers()[header::ETAG], document_etag(&doc).as_str());
+        assert_eq!(body_bytes(response).await, data);
+    }
+
+    #[tokio::test]
+    async fn test_download_range_requests() {
+        let data = binary_payload();
+        let (_dir, state, doc) = state_with_document("video.mp4", &data);
+
+        let headers = request_headers(&[(header::RANGE, "bytes=1000-5095")]);
+        let response = serve_document_file(&state, &doc.id, None, &headers, false).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers()[header::CONTENT_RANGE],
+            format!("bytes 1000-5095/{}", data.len()).as_str()
+        );
+        assert_eq!(body_bytes(response).await, &data[1000..=5095]);
+
+        let headers = request_headers(&[(header::RANGE, "bytes=-256")]);
+        let response = serve_document_file(&state, &doc.id, None, &headers, false).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body_bytes(response).await, &data[data.len() - 256..]);
+
+        let headers = request_headers(&[(header::RANGE, "bytes=70000-")]);
+        let response = serve_document_file(&state, &doc.id, None, &headers, false).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */70000");
+    }
+
+    #[tokio::test]
+    async fn test_download_etag_revalidation() {
+        let data = binary_payload();
+        let (_dir, state, doc) = state_with_document("bai giang.pdf", &data);
+        let etag = document_etag(&doc);
+
+        let headers = request_headers(&[(header::IF_NONE_MATCH, &etag)]);
+        let response = serve_document_file(&state, &doc.id, None, &headers, false).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(body_bytes(response).await.is_empty());
+
+        // Resuming against an outdated copy gets the whole file
+        let headers = request_headers(&[
+            (header::RANGE, "bytes=100-"),
+            (header::IF_RANGE, "\"doc-1-old\""),
+        ]);
+        let response = serve_document_file(&state, &doc.id, None, &headers, false).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_bytes(response).await, data);
+
+        let headers = request_headers(&[(header::RANGE, "bytes=100-"), (header::IF_RANGE, &etag)]);
+        let response = serve_document_file(&state, &doc.id, None, &headers, false).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body_bytes(response).await, &data[100..]);
+    }
+
+    #[tokio::test]
+    async fn test_download_unknown_document() {
+        let (_dir, state, _doc) = state_with_document("a.txt", b"hello");
+        let response = serve_document_file(&state, "doc-missing", None, &HeaderMap::new(), false).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    fn test_state_with_key() -> DocumentServerState {
+        let state = DocumentServerState::new();
+        *state.link_key.lock().unwrap() = Some(crypto::generate_keypair());
+        state
+    }
+
+    fn claims(session_id: Option<&str>, group: Option<&str>, document_id: Option<&str>) -> AccessClaims {
+        AccessClaims {
+            session_id: session_id.map(String::from),
+            group: group.map(String::from),
+            document_id: document_id.map(String::from),
+            expires_at: 2_000,
+        }
+    }
+
+    #[test]
+    fn test_access_token_roundtrip_and_tampering() {
+        let state = test_state_with_key();
+        let issued = claims(None, Some("10A1"), None);
+        let token = issue_access_token(&state, &issued).unwrap();
+        assert!(token.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')));
+
+        assert_eq!(verify_access_token(&state, &token, 1_000).unwrap(), issued);
+        assert!(verify_access_token(&state, &token, 2_000).is_err(), "expired token accepted");
+
+        // Swapping in a different group must break the signature
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims(None, Some("10A2"), None)).unwrap());
+        assert!(verify_access_token(&state, &format!("{}.{}", forged, signature), 1_000).is_err());
+        assert!(verify_access_token(&state, "garbage", 1_000).is_err());
+
+        // Tokens from another server's key are rejected
+        let other = test_state_with_key();
+        assert!(verify_access_token(&other, &token, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_document_visibility_scopes() {
+        let mut doc = Document {
+            id: "doc-1".to_string(),
+            name: "dap-an.pdf".to_string(),
+            size: 1,
+            mime_type: "application/pdf".to_string(),
+            uploaded_at: 0,
+            description: None,
+            category: None,
+            folder: None,
+            visibility: DocumentVisibility::Public,
+            available_from: None,
+        };
+        assert!(doc.is_visible_to(None, 1_000));
+        assert!(doc.is_visible_to(Some(&claims(Some("s1"), None, None)), 1_000));
+
+        doc.visibility = DocumentVisibility::Group { group: "10A1".to_string() };
+        assert!(!doc.is_visible_to(None, 1_000));
+        assert!(doc.is_visible_to(Some(&claims(None, Some("10A1"), None)), 1_000));
+        assert!(!doc.is_visible_to(Some(&claims(None, Some("10A2"), None)), 1_000));
+        assert!(!doc.is_visible_to(Some(&claims(None, Some("10A1"), Some("doc-2"))), 1_000));
+
+        doc.visibility = DocumentVisibility::Session { session_id: "s1".to_string() };
+        assert!(doc.is_visible_to(Some(&claims(Some("s1"), None, None)), 1_000));
+        assert!(!doc.is_visible_to(Some(&claims(None, Some("10A1"), None)), 1_000));
+
+        // Answers stay hidden until the test is over, even for the right session
+        doc.available_from = Some(1_500);
+        assert!(!doc.is_visible_to(Some(&claims(Some("s1"), None, None)), 1_000));
+        assert!(doc.is_visible_to(Some(&claims(Some("s1"), None, None)), 1_500));
+    }
+
+    #[tokio::test]
+    async fn test_download_rejected_outside_scope() {
+        let (_dir, state, doc) = state_with_document("dap-an.pdf", b"answers");
+        state
+            .documents
+            .lock()
+            .unwrap()
+            .get_mut(&doc.id)
+            .unwrap()
+            .visibility = DocumentVisibility::Group { group: "10A1".to_string() };
+
+        let response = serve_document_file(&state, &doc.id, None, &HeaderMap::new(), false).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let other_class = claims(None, Some("10A2"), None);
+        let response = serve_document_file(&state, &doc.id, Some(&other_class), &HeaderMap::new(), false).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let own_class = claims(None, Some("10A1"), None);
+        let response = serve_document_file(&state, &doc.id, Some(&own_class), &HeaderMap::new(), false).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_bytes(response).await, b"answers");
+    }
+
+    #[test]
+    fn test_document_metadata_without_visibility_is_public() {
+        let json = r#"{"id":"doc-1","name":"a.txt","size":1,"mime_type":"text/plain","uploaded_at":0,"description":null,"category":null}"#;
+        let doc: Document = serde_json::from_str(json).unwrap();
+        assert_eq!(doc.visibility, DocumentVisibility::Public);
+        assert_eq!(doc.available_from, None);
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("Bài 1.pdf", false),
+            "attachment; filename=\"B_i 1.pdf\"; filename*=UTF-8''B%C3%A0i%201.pdf"
+        );
+        assert!(content_disposition("a\"b.txt", true).starts_with("inline; filename=\"a_b.txt\""));
+    }
+
+    fn library() -> Vec<Document> {
+        let doc = |id: &str, name: &str, folder: Option<&str>, category: Option<&str>, size: u64, uploaded_at: u64| Document {
+            id: id.to_string(),
+            name: name.to_string(),
+            size,
+            mime_type: get_mime_type(name),
+            uploaded_at,
+            description: None,
+            category: category.map(str::to_string),
+            folder: folder.map(str::to_string),
+            visibility: DocumentVisibility::Public,
+            available_from: None,
+        };
+        vec![
+            doc("1", "Nội quy.pdf", None, None, 300, 10),
+            doc("2", "Bài giảng hàm số.pptx", Some("Toán/Lớp 10"), Some("Bài giảng"), 100, 30),
+            doc("3", "Đề kiểm tra.docx", Some("Toán/Lớp 10"), Some("Đề thi"), 200, 20),
+            doc("4", "Bài tập đại số.pdf", Some("Toán"), Some("Bài tập"), 50, 40),
+            doc("5", "Bài giảng cơ học.pdf", Some("Vật lý"), Some("Bài giảng"), 400, 50),
+        ]
+    }
+
+    fn ids(docs: &[Document]) -> Vec<&str> {
+        docs.iter().map(|d| d.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_normalize_folder_path() {
+        assert_eq!(normalize_folder_path(" Toán / Lớp 10/ "), Some("Toán/Lớp 10".to_string()));
+        assert_eq!(normalize_folder_path("..\\Toán\\.\\Lớp 10"), Some("Toán/Lớp 10".to_string()));
+        assert_eq!(normalize_folder_path("/"), None);
+        assert_eq!(normalize_folder_path(""), None);
+    }
+
+    #[test]
+    fn test_document_query_folders() {
+        let query = |folder: &str, recursive: bool| DocumentQuery {
+            folder: Some(folder.to_string()),
+            recursive,
+            ..Default::default()
+        };
+        assert_eq!(ids(&query("", false).apply(library())), vec!["1"]);
+        assert_eq!(ids(&query("Toán", false).apply(library())), vec!["4"]);
+        assert_eq!(ids(&query("Toán/", true).apply(library())), vec!["4", "2", "3"]);
+        assert_eq!(ids(&DocumentQuery::default().apply(library())), vec!["5", "4", "2", "3", "1"]);
+    }
+
+    #[test]
+    fn test_document_query_search_and_category() {
+        // Search ignores case and Vietnamese diacritics
+        let search = DocumentQuery { q: Some("bai GIANG".to_string()), ..Default::default() };
+        assert_eq!(ids(&search.apply(library())), vec!["5", "2"]);
+        let search = DocumentQuery { q: Some("de kiem".to_string()), ..Default::default() };
+        assert_eq!(ids(&search.apply(library())), vec!["3"]);
+
+        let category = DocumentQuery { category: Some("Bài giảng".to_string()), ..Default::default() };
+        assert_eq!(ids(&category.apply(library())), vec!["5", "2"]);
+        assert!(category.is_search());
+        assert!(!DocumentQuery { category: Some(" ".to_string()), ..Default::default() }.is_search());
+    }
+
+    #[test]
+    fn test_document_query_sorting() {
+        let sorted = |sort: DocumentSort, order: Option<SortOrder>| {
+            DocumentQuery { sort, order, ..Default::default() }.apply(library())
+        };
+        assert_eq!(ids(&sorted(DocumentSort::Size, None)), vec!["5", "1", "3", "2", "4"]);
+        assert_eq!(ids(&sorted(DocumentSort::Size, Some(SortOrder::Asc))), vec!["4", "2", "3", "1", "5"]);
+        assert_eq!(ids(&sorted(DocumentSort::Name, None)), vec!["5", "2", "4", "3", "1"]);
+    }
+
+    #[test]
+    fn test_document_query_from_url() {
+        let uri: axum::http::Uri = "/?folder=To%C3%A1n&recursive=true&sort=size&order=asc&q=bai".parse().unwrap();
+        let Query(query) = Query::<DocumentQuery>::try_from_uri(&uri).unwrap();
+        assert_eq!(query.folder.as_deref(), Some("Toán"));
+        assert!(query.recursive);
+        assert_eq!(query.sort, DocumentSort::Size);
+        assert_eq!(query.order, Some(SortOrder::Asc));
+        assert_eq!(ids(&query.apply(library())), vec!["4", "2"]);
+    }
+
+    #[test]
+    fn test_list_folders_and_categories() {
+        let folders = list_folders(&library());
+        let summary: Vec<(&str, &str, usize)> = folders
+            .iter()
+            .map(|f| (f.path.as_str(), f.name.as_str(), f.document_count))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("Toán", "Toán", 3), ("Toán/Lớp 10", "Lớp 10", 2), ("Vật lý", "Vật lý", 1)]
+        );
+        assert_eq!(list_categories(&library()), vec!["Bài giảng", "Bài tập", "Đề thi"]);
+    }
+
+    #[test]
+    fn test_document_page_browses_folders_and_escapes_input() {
+        let docs = library();
+        let html = build_document_list_html(&docs, &DocumentQuery::default(), None, false);
+        assert!(html.contains("Nội quy.pdf"));
+        assert!(html.contains(r#"href="/?folder=To%C3%A1n""#));
+        assert!(!html.contains("Đề kiểm tra.docx"));
+
+        let filter = DocumentQuery { q: Some("<script>".to_string()), ..Default::default() };
+        let html = build_document_list_html(&docs, &filter, Some("tok"), false);
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains(r#"<input type="hidden" name="token" value="tok">"#));
+    }
+
+    #[test]
+    fn test_select_zip_documents() {
+        let by_ids = ZipQuery { ids: Some("3, 1,missing,3".to_string()), ..Default::default() };
+        assert_eq!(ids(&select_zip_documents(library(), &by_ids).unwrap()), vec!["3", "1"]);
+
+        let by_category = ZipQuery { category: Some("Bài giảng".to_string()), ..Default::default() };
+        assert_eq!(ids(&select_zip_documents(library(), &by_category).unwrap()), vec!["5", "2"]);
+
+        let by_folder = ZipQuery { folder: Some("Toán".to_string()), ..Default::default() };
+        assert_eq!(ids(&select_zip_documents(library(), &by_folder).unwrap()), vec!["2", "4", "3"]);
+
+        assert!(select_zip_documents(library(), &ZipQuery::default()).is_err());
+    }
+
+    #[test]
+    fn test_zip_entry_names() {
+        let mut docs = library();
+        docs[0].folder = Some("Toán".to_string());
+        docs[0].name = "bài tập đại số.PDF".to_string();
+        docs[1].name = "Bài tập đại số.pdf".to_string();
+        docs[1].folder = Some("Toán".to_string());
+
+        let names = zip_entry_names(&docs, Some("Toán"));
+        assert_eq!(names[0], "bài tập đại số.PDF");
+        assert_eq!(names[1], "Bài tập đại số (2).pdf");
+        assert_eq!(names[2], "Lớp 10/Đề kiểm tra.docx");
+        assert_eq!(names[4], "Vật lý/Bài giảng cơ học.pdf");
+
+        let query = ZipQuery { folder: Some("Toán/Lớp 10".to_string()), ..Default::default() };
+        assert_eq!(zip_archive_name(&query), "Lớp 10.zip");
+        assert_eq!(zip_archive_name(&ZipQuery::default()), "tai-lieu.zip");
+    }
+
+    #[tokio::test]
+    async fn test_zip_download_streams_archive() {
+        let (dir, state, first) = state_with_document("a.bin", &binary_payload());
+        let second = Document {
+            id: "doc-2".to_string(),
+            name: "b.txt".to_string(),
+            size: 5,
+            category: Some("Bài tập".to_string()),
+            ..first.clone()
+        };
+        std::fs::write(dir.path().join(&second.id), b"hello").unwrap();
+        state.add_document(second);
+
+        let query = ZipQuery { ids: Some("doc-1,doc-2".to_string()), ..Default::default() };
+        let response = serve_zip_download(State(Arc::new(state)), Query(query)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/zip");
+        let length: usize = response.headers()[header::CONTENT_LENGTH].to_str().unwrap().parse().unwrap();
+
+        let body = body_bytes(response).await;
+        assert_eq!(body.len(), length);
+        assert!(body.starts_with(b"PK\x03\x04"));
+        let payload = binary_payload();
+        assert!(body.windows(payload.len()).any(|w| w == payload.as_slice()));
+        assert!(body.windows(5).any(|w| w == b"hello"));
+    }
+
+    #[tokio::test]
+    async fn test_zip_download_fails_on_missing_file() {
+        let (_dir, state, first) = state_with_document("a.txt", b"hello");
+        // Listed in the library, but its file is gone from storage
+        state.add_document(Document { id: "doc-2".to_string(), name: "b.txt".to_string(), ..first });
+
+        let query = ZipQuery { ids: Some("doc-1,doc-2".to_string()), ..Default::default() };
+        let response = serve_zip_download(State(Arc::new(state)), Query(query)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
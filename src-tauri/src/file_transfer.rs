@@ -8,12 +8,14 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
 
 /// Chunk size for file transfer (64KB - optimal for most networks)
 pub const CHUNK_SIZE: usize = 64 * 1024;
@@ -21,6 +23,9 @@ pub const CHUNK_SIZE: usize = 64 * 1024;
 /// File transfer port offset from main WebSocket port
 pub const FILE_TRANSFER_PORT_OFFSET: u16 = 100;
 
+/// How long after the last remote input transfers stay backed off
+const REMOTE_CONTROL_BACKOFF: Duration = Duration::from_secs(3);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileInfo {
     pub name: String,
@@ -34,6 +39,8 @@ pub struct FileInfo {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TransferStatus {
     Pending,
+    /// Waiting for a free transfer slot
+    Queued,
     Connecting,
     Transferring,
     Completed,
@@ -52,6 +59,11 @@ pub struct FileTransferJob {
     pub direction: TransferDirection,
     pub student_id: String,
     pub progress: f32,
+    #[serde(default)]
+    pub priority: TransferPriority,
+    /// Position in the send queue while status is `Queued` (0 = next)
+    #[serde(default)]
+    pub queue_position: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -60,6 +72,37 @@ pub enum TransferDirection {
     Receive, // Student -> Teacher
 }
 
+/// Scheduling priority of a send job (higher runs first)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Bandwidth and concurrency limits for outgoing transfers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferLimits {
+    /// Global send rate across all jobs in bytes/sec (0 = unlimited)
+    pub max_bytes_per_sec: u64,
+    /// Jobs allowed to send at the same time; the rest wait in the queue
+    pub max_concurrent_jobs: usize,
+    /// Send rate while the teacher is in remote control (0 = pause)
+    pub remote_control_bytes_per_sec: u64,
+}
+
+impl Default for TransferLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes_per_sec: 8 * 1024 * 1024,
+            max_concurrent_jobs: 4,
+            remote_control_bytes_per_sec: 1024 * 1024,
+        }
+    }
+}
+
 /// Progress event emitted to frontend
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileTransferProgress {
@@ -102,6 +145,343 @@ pub enum FileTransferMessage {
     /// Cancel transfer
     #[serde(rename = "cancel")]
     Cancel { job_id: String },
+    /// Receiver kept its existing copy and declines this file
+    #[serde(rename = "skip")]
+    Skip { job_id: String, reason: String },
+    /// Folder sync: manifest of the sender's folder
+    #[serde(rename = "sync_init")]
+    SyncInit {
+        job_id: String,
+        folder_name: String,
+        entries: Vec<SyncManifestEntry>,
+        delete_extraneous: bool,
+    },
+    /// Folder sync: paths the receiver is missing or has out of date
+    #[serde(rename = "sync_request")]
+    SyncRequest { job_id: String, needed: Vec<String> },
+}
+
+/// One file in a folder sync manifest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SyncManifestEntry {
+    /// Path relative to the synced folder, always `/`-separated
+    pub path: String,
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch
+    pub modified: u64,
+    /// Lowercase hex SHA256 of the contents
+    pub hash: String,
+}
+
+/// Receive policy file name
+const RECEIVE_POLICY_FILE_NAME: &str = "receive_policy.json";
+
+/// What to do when a received file already exists at its destination
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// Keep both files, saving the new one as "name (1).ext"
+    Rename,
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file and decline the incoming one
+    Skip,
+}
+
+/// Where and how much a student machine accepts from incoming transfers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceivePolicy {
+    /// Directory all received files are confined to (None = Downloads)
+    pub receive_root: Option<String>,
+    pub on_collision: CollisionPolicy,
+    /// Maximum size of a single received file in bytes (0 = unlimited)
+    pub max_file_size: u64,
+    /// Maximum total bytes accepted in one transfer (0 = unlimited)
+    pub max_transfer_size: u64,
+}
+
+impl Default for ReceivePolicy {
+    fn default() -> Self {
+        Self {
+            receive_root: None,
+            on_collision: CollisionPolicy::Rename,
+            max_file_size: 4 * 1024 * 1024 * 1024,
+            max_transfer_size: 16 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+impl ReceivePolicy {
+    /// Resolve the configured receive root, falling back to Downloads
+    pub fn root_dir(&self) -> Result<PathBuf, String> {
+        match &self.receive_root {
+            Some(root) if !root.trim().is_empty() => Ok(PathBuf::from(root)),
+            _ => dirs::download_dir()
+                .ok_or_else(|| "Failed to get Downloads directory".to_string()),
+        }
+    }
+
+    /// Check a file against the per-file and per-transfer quotas
+    pub fn check_quota(&self, file_size: u64, transfer_total: u64) -> Result<(), String> {
+        if self.max_file_size > 0 && file_size > self.max_file_size {
+            return Err(format!(
+                "File size {} exceeds limit of {} bytes",
+                file_size, self.max_file_size
+            ));
+        }
+        if self.max_transfer_size > 0 && transfer_total > self.max_transfer_size {
+            return Err(format!(
+                "Transfer size {} exceeds limit of {} bytes",
+                transfer_total, self.max_transfer_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Get the receive policy file path (~/.smartlab/receive_policy.json)
+pub fn get_receive_policy_path() -> PathBuf {
+    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    home.join(".smartlab").join(RECEIVE_POLICY_FILE_NAME)
+}
+
+/// Load the receive policy, using defaults if the file is missing or invalid
+pub fn load_receive_policy() -> ReceivePolicy {
+    let path = get_receive_policy_path();
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("[FileTransfer] Invalid receive policy: {}, using defaults", e);
+            ReceivePolicy::default()
+        }),
+        Err(_) => ReceivePolicy::default(),
+    }
+}
+
+/// Save the receive policy
+pub fn save_receive_policy(policy: &ReceivePolicy) -> Result<(), String> {
+    let path = get_receive_policy_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let json = serde_json::to_string_pretty(policy)
+        .map_err(|e| format!("Failed to serialize receive policy: {}", e))?;
+    std::fs::write(&path, json)
+        .map_err(|e| format!("Failed to write receive policy: {}", e))
+}
+
+/// Turn a sender-supplied file name into a safe relative path.
+///
+/// Both `/` and `\` are treated as separators since senders may run on
+/// either platform. Absolute paths, drive prefixes and `..` are rejected
+/// rather than stripped so a malicious name never lands somewhere unexpected.
+pub fn sanitize_relative_path(name: &str) -> Result<PathBuf, String> {
+    if name.starts_with('/') || name.starts_with('\\') {
+        return Err(format!("Absolute path not allowed: {}", name));
+    }
+
+    let mut relative = PathBuf::new();
+    for segment in name.split(['/', '\\']) {
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(format!("Path traversal not allowed: {}", name)),
+            s if s.contains(':') || s.contains('\0') => {
+                return Err(format!("Invalid path segment: {}", s));
+            }
+            s => relative.push(s),
+        }
+    }
+
+    if relative.as_os_str().is_empty() {
+        return Err("Empty file name".to_string());
+    }
+
+    Ok(relative)
+}
+
+/// Create `relative` under `root` and return its canonical path.
+///
+/// Walks `relative` one folder at a time and refuses any existing symlink
+/// before creating the next level, so nothing is ever created through a
+/// symlinked folder outside the root.
+fn ensure_dir_within(root: &Path, relative: &Path) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(root)
+        .map_err(|e| format!("Failed to create receive directory: {}", e))?;
+    let canonical_root = root
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve receive directory: {}", e))?;
+
+    let mut dir = canonical_root.clone();
+    for component in relative.components() {
+        let std::path::Component::Normal(segment) = component else {
+            return Err(format!("Path escapes receive directory: {}", relative.display()));
+        };
+        dir.push(segment);
+        match std::fs::symlink_metadata(&dir) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                return Err(format!("Refusing to follow symlink: {}", relative.display()));
+            }
+            Ok(meta) if meta.is_dir() => {}
+            Ok(_) => return Err(format!("Not a directory: {}", dir.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                std::fs::create_dir(&dir)
+                    .map_err(|e| format!("Failed to create directories: {}", e))?;
+            }
+            Err(e) => return Err(format!("Failed to resolve directory: {}", e)),
+        }
+    }
+
+    Ok(dir)
+}
+
+/// Find "name (n).ext" next to `path` that does not exist yet
+fn next_available_path(path: &Path) -> PathBuf {
+    let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("file")
+        .to_string();
+    let ext = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_string();
+
+    let mut counter = 1;
+    loop {
+        let new_name = if ext.is_empty() {
+            format!("{} ({})", stem, counter)
+        } else {
+            format!("{} ({}).{}", stem, counter, ext)
+        };
+        let candidate = parent.join(new_name);
+        if std::fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// Resolve where a received file should be written inside `root`.
+///
+/// Creates the root and any parent directories (see `ensure_dir_within`).
+/// Returns `None` when the collision policy says to skip.
+pub fn resolve_receive_path(
+    root: &Path,
+    name: &str,
+    on_collision: CollisionPolicy,
+) -> Result<Option<PathBuf>, String> {
+    let relative = sanitize_relative_path(name)?;
+    let file_name = relative.file_name().ok_or("Invalid file path")?;
+    let parent = ensure_dir_within(root, relative.parent().unwrap_or(Path::new("")))?;
+    let target = parent.join(file_name);
+
+    match std::fs::symlink_metadata(&target) {
+        Err(_) => Ok(Some(target)),
+        Ok(meta) if meta.file_type().is_symlink() => {
+            Err(format!("Refusing to write through symlink: {}", name))
+        }
+        Ok(meta) => match on_collision {
+            CollisionPolicy::Rename => Ok(Some(next_available_path(&target))),
+            CollisionPolicy::Overwrite if meta.is_dir() => {
+                Err(format!("A directory already exists at: {}", name))
+            }
+            CollisionPolicy::Overwrite => Ok(Some(target)),
+            CollisionPolicy::Skip => Ok(None),
+        },
+    }
+}
+
+/// Global token bucket shared by every sending job
+struct RateLimiter {
+    available: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    fn new() -> Self {
+        Self {
+            available: 0.0,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take `bytes` from the bucket and return how long the caller must wait
+    fn consume(&mut self, bytes: f64, rate: f64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+        // Allow at most one second of burst
+        self.available = (self.available + elapsed * rate).min(rate) - bytes;
+
+        if self.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.available / rate)
+        }
+    }
+}
+
+struct QueuedJob {
+    job_id: String,
+    priority: TransferPriority,
+    seq: u64,
+}
+
+/// Jobs waiting for a send slot, highest priority first then FIFO
+#[derive(Default)]
+struct TransferQueue {
+    waiting: Vec<QueuedJob>,
+    active: usize,
+    next_seq: u64,
+}
+
+impl TransferQueue {
+    fn enqueue(&mut self, job_id: &str, priority: TransferPriority) {
+        self.waiting.push(QueuedJob {
+            job_id: job_id.to_string(),
+            priority,
+            seq: self.next_seq,
+        });
+        self.next_seq += 1;
+        self.waiting
+            .sort_by(|a, b| b.priority.cmp(&a.priority).then(a.seq.cmp(&b.seq)));
+    }
+
+    fn position(&self, job_id: &str) -> Option<usize> {
+        self.waiting.iter().position(|j| j.job_id == job_id)
+    }
+
+    fn remove(&mut self, job_id: &str) {
+        self.waiting.retain(|j| j.job_id != job_id);
+    }
+
+    /// Start `job_id` if it is at the head of the queue and a slot is free
+    fn try_start(&mut self, job_id: &str, max_active: usize) -> bool {
+        let is_next = self.waiting.first().map(|j| j.job_id == job_id).unwrap_or(false);
+        if is_next && self.active < max_active {
+            self.waiting.remove(0);
+            self.active += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A running send job's slot; frees it for the next queued job on drop
+pub struct TransferSlot {
+    state: Arc<FileTransferState>,
+}
+
+impl Drop for TransferSlot {
+    fn drop(&mut self) {
+        if let Ok(mut queue) = self.state.queue.lock() {
+            queue.active = queue.active.saturating_sub(1);
+        }
+        self.state.queue_changed.notify_waiters();
+    }
 }
 
 /// State for managing file transfers
@@ -109,6 +489,12 @@ pub struct FileTransferState {
     pub jobs: Mutex<HashMap<String, FileTransferJob>>,
     pub cancel_flags: Mutex<HashMap<String, Arc<AtomicBool>>>,
     pub listener_port: Mutex<Option<u16>>,
+    pub limits: Mutex<TransferLimits>,
+    queue: Mutex<TransferQueue>,
+    queue_changed: Notify,
+    rate_limiter: Mutex<RateLimiter>,
+    /// Last time the teacher sent remote mouse/keyboard input
+    last_remote_control: Mutex<Option<Instant>>,
 }
 
 impl Default for FileTransferState {
@@ -117,6 +503,11 @@ impl Default for FileTransferState {
             jobs: Mutex::new(HashMap::new()),
             cancel_flags: Mutex::new(HashMap::new()),
             listener_port: Mutex::new(None),
+            limits: Mutex::new(TransferLimits::default()),
+            queue: Mutex::new(TransferQueue::default()),
+            queue_changed: Notify::new(),
+            rate_limiter: Mutex::new(RateLimiter::new()),
+            last_remote_control: Mutex::new(None),
         }
     }
 }
@@ -151,7 +542,11 @@ impl FileTransferState {
     }
 
     pub fn get_job(&self, job_id: &str) -> Option<FileTransferJob> {
-        self.jobs.lock().ok()?.get(job_id).cloned()
+        let mut job = self.jobs.lock().ok()?.get(job_id).cloned()?;
+        if job.status == TransferStatus::Queued {
+            job.queue_position = self.queue.lock().ok().and_then(|q| q.position(job_id));
+        }
+        Some(job)
     }
 
     pub fn remove_job(&self, job_id: &str) {
@@ -167,6 +562,8 @@ impl FileTransferState {
         if let Ok(flags) = self.cancel_flags.lock() {
             if let Some(flag) = flags.get(job_id) {
                 flag.store(true, Ordering::Relaxed);
+                // Wake queued jobs so a cancelled one leaves the queue
+                self.queue_changed.notify_waiters();
                 return true;
             }
         }
@@ -181,6 +578,128 @@ impl FileTransferState {
         }
         false
     }
+
+    pub fn get_limits(&self) -> TransferLimits {
+        self.limits.lock().map(|l| l.clone()).unwrap_or_default()
+    }
+
+    pub fn set_limits(&self, limits: TransferLimits) {
+        if let Ok(mut l) = self.limits.lock() {
+            *l = limits;
+        }
+        // A larger concurrency limit may let queued jobs start
+        self.queue_changed.notify_waiters();
+    }
+
+    /// Record teacher remote input so transfers back off for a while
+    pub fn note_remote_control(&self) {
+        if let Ok(mut last) = self.last_remote_control.lock() {
+            *last = Some(Instant::now());
+        }
+    }
+
+    pub fn is_remote_control_active(&self) -> bool {
+        self.last_remote_control
+            .lock()
+            .ok()
+            .and_then(|last| *last)
+            .map(|t| t.elapsed() < REMOTE_CONTROL_BACKOFF)
+            .unwrap_or(false)
+    }
+
+    /// Wait for a send slot. Returns `None` if the job is cancelled while queued.
+    pub async fn acquire_slot(self: &Arc<Self>, job_id: &str) -> Option<TransferSlot> {
+        let priority = self
+            .jobs
+            .lock()
+            .ok()
+            .and_then(|jobs| jobs.get(job_id).map(|j| j.priority))
+            .unwrap_or_default();
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.enqueue(job_id, priority);
+        }
+
+        loop {
+            // Register for wakeups before checking so none are missed
+            let notified = self.queue_changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.is_cancelled(job_id) {
+                if let Ok(mut queue) = self.queue.lock() {
+                    queue.remove(job_id);
+                }
+                self.queue_changed.notify_waiters();
+                return None;
+            }
+
+            let max_active = self.get_limits().max_concurrent_jobs.max(1);
+            let started = self
+                .queue
+                .lock()
+                .map(|mut queue| queue.try_start(job_id, max_active))
+                .unwrap_or(false);
+            if started {
+                // The next job in line may fit too
+                self.queue_changed.notify_waiters();
+                return Some(TransferSlot {
+                    state: Arc::clone(self),
+                });
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Apply the global rate limit after sending `bytes`
+    pub async fn throttle(&self, bytes: usize) {
+        loop {
+            let limits = self.get_limits();
+            let rate = if self.is_remote_control_active() {
+                if limits.remote_control_bytes_per_sec == 0 {
+                    // Paused until the teacher stops remote controlling
+                    tokio::time::sleep(Duration::from_millis(250)).await;
+                    continue;
+                }
+                match limits.max_bytes_per_sec {
+                    0 => limits.remote_control_bytes_per_sec,
+                    max => max.min(limits.remote_control_bytes_per_sec),
+                }
+            } else {
+                limits.max_bytes_per_sec
+            };
+
+            if rate == 0 {
+                return;
+            }
+
+            let wait = match self.rate_limiter.lock() {
+                Ok(mut limiter) => limiter.consume(bytes as f64, rate as f64, Instant::now()),
+                Err(_) => return,
+            };
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+            return;
+        }
+    }
+}
+
+/// Queue a send job and wait for its slot, keeping the frontend informed
+async fn wait_for_slot(
+    state: &Arc<FileTransferState>,
+    app_handle: &AppHandle,
+    job_id: &str,
+) -> Option<TransferSlot> {
+    state.update_job(job_id, 0, TransferStatus::Queued);
+    emit_progress(app_handle, state, job_id);
+
+    let slot = state.acquire_slot(job_id).await;
+    if slot.is_none() {
+        state.update_job(job_id, 0, TransferStatus::Cancelled);
+        emit_progress(app_handle, state, job_id);
+    }
+    slot
 }
 
 /// Collect all files in a directory recursively
@@ -211,6 +730,118 @@ fn collect_files_in_directory(dir_path: &PathBuf, base_path: &PathBuf) -> Result
     Ok(files)
 }
 
+/// Modification time of a file in seconds since the Unix epoch
+fn modified_secs(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Set a file's modification time so later syncs can skip hashing it
+fn set_modified_secs(path: &Path, secs: u64) -> std::io::Result<()> {
+    let file = std::fs::File::options().write(true).open(path)?;
+    file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+}
+
+fn hash_file(path: &Path) -> Result<String, String> {
+    crate::auto_update::Verifier::calculate_sha256(path).map_err(|e| e.to_string())
+}
+
+/// Build a sync manifest for every file under `dir`
+pub fn build_sync_manifest(dir: &Path) -> Result<Vec<(PathBuf, SyncManifestEntry)>, String> {
+    let dir = dir.to_path_buf();
+    let mut manifest = Vec::new();
+
+    for (path, relative_path) in collect_files_in_directory(&dir, &dir)? {
+        let metadata = std::fs::metadata(&path)
+            .map_err(|e| format!("Failed to read file metadata: {}", e))?;
+        manifest.push((
+            path.clone(),
+            SyncManifestEntry {
+                path: relative_path.replace('\\', "/"),
+                size: metadata.len(),
+                modified: modified_secs(&metadata),
+                hash: hash_file(&path)?,
+            },
+        ));
+    }
+
+    Ok(manifest)
+}
+
+/// Compare a sync manifest against `dest_dir` on the receiver.
+///
+/// Returns the manifest paths that must be transferred. Files with matching
+/// size and mtime are trusted without hashing. When `delete_extraneous` is set,
+/// local files missing from the manifest are removed; the count is returned.
+pub fn plan_sync(
+    dest_dir: &Path,
+    entries: &[SyncManifestEntry],
+    delete_extraneous: bool,
+) -> Result<(Vec<String>, usize), String> {
+    let mut needed = Vec::new();
+    let mut wanted = std::collections::HashSet::new();
+
+    for entry in entries {
+        let relative = sanitize_relative_path(&entry.path)?;
+        wanted.insert(relative.clone());
+
+        let local = dest_dir.join(&relative);
+        let up_to_date = match std::fs::symlink_metadata(&local) {
+            Ok(meta) if meta.is_file() && meta.len() == entry.size => {
+                if modified_secs(&meta) == entry.modified {
+                    true
+                } else if hash_file(&local)? == entry.hash {
+                    // Same contents, adopt the sender's mtime for next time
+                    let _ = set_modified_secs(&local, entry.modified);
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        };
+
+        if !up_to_date {
+            needed.push(entry.path.clone());
+        }
+    }
+
+    let mut deleted = 0;
+    if delete_extraneous && dest_dir.is_dir() {
+        let dest_dir = dest_dir.to_path_buf();
+        let canonical_dest = dest_dir
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve directory: {}", e))?;
+
+        for (path, relative_path) in collect_files_in_directory(&dest_dir, &dest_dir)? {
+            if wanted.contains(&PathBuf::from(&relative_path)) {
+                continue;
+            }
+
+            // Never follow a symlinked folder out of the destination
+            let inside = path
+                .parent()
+                .and_then(|p| p.canonicalize().ok())
+                .map(|p| p.starts_with(&canonical_dest))
+                .unwrap_or(false);
+            if !inside {
+                continue;
+            }
+
+            match std::fs::remove_file(&path) {
+                Ok(()) => deleted += 1,
+                Err(e) => log::warn!("[FileTransfer] Failed to delete {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    Ok((needed, deleted))
+}
+
 /// Send a file or folder to student via dedicated TCP connection
 pub async fn send_file_chunked(
     state: Arc<FileTransferState>,
@@ -219,6 +850,7 @@ pub async fn send_file_chunked(
     student_port: u16,
     file_path: String,
     student_id: String,
+    priority: TransferPriority,
 ) -> Result<String, String> {
     let path = PathBuf::from(&file_path);
     let metadata = std::fs::metadata(&path)
@@ -226,7 +858,7 @@ pub async fn send_file_chunked(
     
     // Check if it's a directory
     if metadata.is_dir() {
-        return send_folder_chunked(state, app_handle, student_ip, student_port, file_path, student_id).await;
+        return send_folder_chunked(state, app_handle, student_ip, student_port, file_path, student_id, priority).await;
     }
     
     // Generate job ID
@@ -250,6 +882,8 @@ pub async fn send_file_chunked(
         direction: TransferDirection::Send,
         student_id: student_id.clone(),
         progress: 0.0,
+        priority,
+        queue_position: None,
     };
     state.add_job(job);
 
@@ -299,6 +933,11 @@ async fn send_file_task(
     file_name: String,
     file_size: u64,
 ) -> Result<(), String> {
+    // Wait for a free transfer slot (highest priority first)
+    let Some(_slot) = wait_for_slot(&state, &app_handle, &job_id).await else {
+        return Ok(());
+    };
+
     // Update status to connecting
     state.update_job(&job_id, 0, TransferStatus::Connecting);
     emit_progress(&app_handle, &state, &job_id);
@@ -330,6 +969,12 @@ async fn send_file_task(
         FileTransferMessage::Ack { ready: false, .. } => {
             return Err("Receiver not ready".to_string());
         }
+        FileTransferMessage::Skip { reason, .. } => {
+            log::info!("[FileTransfer] Receiver skipped {}: {}", file_name, reason);
+            state.update_job(&job_id, file_size, TransferStatus::Completed);
+            emit_progress(&app_handle, &state, &job_id);
+            return Ok(());
+        }
         FileTransferMessage::Error { message, .. } => {
             return Err(format!("Receiver error: {}", message));
         }
@@ -377,6 +1022,7 @@ async fn send_file_task(
             data: buffer[..bytes_read].to_vec(),
         };
         send_message(&mut stream, &chunk_msg).await?;
+        state.throttle(bytes_read).await;
 
         offset += bytes_read as u64;
 
@@ -409,6 +1055,7 @@ async fn send_folder_chunked(
     student_port: u16,
     folder_path: String,
     student_id: String,
+    priority: TransferPriority,
 ) -> Result<String, String> {
     let path = PathBuf::from(&folder_path);
     let folder_name = path
@@ -442,6 +1089,8 @@ async fn send_folder_chunked(
         direction: TransferDirection::Send,
         student_id: student_id.clone(),
         progress: 0.0,
+        priority,
+        queue_position: None,
     };
     state.add_job(job);
     emit_progress(&app_handle, &state, &job_id);
@@ -488,6 +1137,11 @@ async fn send_folder_task(
     total_size: u64,
     job_id: String,
 ) -> Result<(), String> {
+    // Wait for a free transfer slot (highest priority first)
+    let Some(_slot) = wait_for_slot(&state, &app_handle, &job_id).await else {
+        return Ok(());
+    };
+
     // Update status to connecting
     state.update_job(&job_id, 0, TransferStatus::Connecting);
     emit_progress(&app_handle, &state, &job_id);
@@ -502,17 +1156,43 @@ async fn send_folder_task(
 
     log::info!("[FileTransfer] Connected to {}", addr);
 
+    send_folder_files(&mut stream, &state, &app_handle, &folder_name, &files, &job_id).await?;
+    if state.is_cancelled(&job_id) {
+        return Ok(());
+    }
+
+    // Update final status
+    state.update_job(&job_id, total_size, TransferStatus::Completed);
+    emit_progress(&app_handle, &state, &job_id);
+
+    log::info!("[FileTransfer] Folder transfer completed: {} ({} files, {} bytes)", 
+        folder_name, files.len(), total_size);
+
+    Ok(())
+}
+
+/// Send each file of a folder over an open transfer stream.
+/// On cancellation the job is marked cancelled and `Ok` is returned.
+async fn send_folder_files(
+    stream: &mut TcpStream,
+    state: &Arc<FileTransferState>,
+    app_handle: &AppHandle,
+    folder_name: &str,
+    files: &[(PathBuf, String)],
+    job_id: &str,
+) -> Result<(), String> {
+    let job_id = job_id.to_string();
     let mut total_transferred: u64 = 0;
     let mut last_progress_emit = std::time::Instant::now();
 
     // Send each file
-    for (file_path, relative_path) in &files {
+    for (file_path, relative_path) in files {
         // Check for cancellation
         if state.is_cancelled(&job_id) {
             let cancel_msg = FileTransferMessage::Cancel { job_id: job_id.clone() };
-            let _ = send_message(&mut stream, &cancel_msg).await;
+            let _ = send_message(stream, &cancel_msg).await;
             state.update_job(&job_id, total_transferred, TransferStatus::Cancelled);
-            emit_progress(&app_handle, &state, &job_id);
+            emit_progress(app_handle, state, &job_id);
             return Ok(());
         }
 
@@ -529,15 +1209,20 @@ async fn send_folder_task(
             file_name: dest_path.clone(),
             file_size,
         };
-        send_message(&mut stream, &init_msg).await?;
+        send_message(stream, &init_msg).await?;
 
         // Wait for ack
-        let ack = receive_message(&mut stream).await?;
+        let ack = receive_message(stream).await?;
         match ack {
             FileTransferMessage::Ack { ready: true, .. } => {}
             FileTransferMessage::Ack { ready: false, .. } => {
                 return Err(format!("Receiver not ready for file: {}", dest_path));
             }
+            FileTransferMessage::Skip { reason, .. } => {
+                log::info!("[FileTransfer] Receiver skipped {}: {}", dest_path, reason);
+                total_transferred += file_size;
+                continue;
+            }
             FileTransferMessage::Error { message, .. } => {
                 return Err(format!("Receiver error for {}: {}", dest_path, message));
             }
@@ -548,7 +1233,7 @@ async fn send_folder_task(
 
         // Update status to transferring
         state.update_job(&job_id, total_transferred, TransferStatus::Transferring);
-        emit_progress(&app_handle, &state, &job_id);
+        emit_progress(app_handle, state, &job_id);
 
         // Open file and send chunks
         let mut file = tokio::fs::File::open(file_path)
@@ -562,9 +1247,9 @@ async fn send_folder_task(
             // Check for cancellation
             if state.is_cancelled(&job_id) {
                 let cancel_msg = FileTransferMessage::Cancel { job_id: job_id.clone() };
-                let _ = send_message(&mut stream, &cancel_msg).await;
+                let _ = send_message(stream, &cancel_msg).await;
                 state.update_job(&job_id, total_transferred, TransferStatus::Cancelled);
-                emit_progress(&app_handle, &state, &job_id);
+                emit_progress(app_handle, state, &job_id);
                 return Ok(());
             }
 
@@ -583,7 +1268,8 @@ async fn send_folder_task(
                 offset: file_offset,
                 data: buffer[..bytes_read].to_vec(),
             };
-            send_message(&mut stream, &chunk_msg).await?;
+            send_message(stream, &chunk_msg).await?;
+            state.throttle(bytes_read).await;
 
             file_offset += bytes_read as u64;
             total_transferred += bytes_read as u64;
@@ -591,23 +1277,168 @@ async fn send_folder_task(
             // Update progress (throttle to every 100ms)
             if last_progress_emit.elapsed().as_millis() >= 100 {
                 state.update_job(&job_id, total_transferred, TransferStatus::Transferring);
-                emit_progress(&app_handle, &state, &job_id);
+                emit_progress(app_handle, state, &job_id);
                 last_progress_emit = std::time::Instant::now();
             }
         }
 
         // Send complete message for this file
         let complete_msg = FileTransferMessage::Complete { job_id: job_id.clone() };
-        send_message(&mut stream, &complete_msg).await?;
+        send_message(stream, &complete_msg).await?;
 
         log::info!("[FileTransfer] File in folder completed: {}", dest_path);
     }
 
-    // Update final status
+    Ok(())
+}
+
+/// Sync a folder to student, sending only files that are missing or changed
+pub async fn sync_folder_chunked(
+    state: Arc<FileTransferState>,
+    app_handle: AppHandle,
+    student_ip: String,
+    student_port: u16,
+    folder_path: String,
+    student_id: String,
+    delete_extraneous: bool,
+    priority: TransferPriority,
+) -> Result<String, String> {
+    let path = PathBuf::from(&folder_path);
+    if !path.is_dir() {
+        return Err(format!("Path is not a directory: {}", folder_path));
+    }
+    let folder_name = path
+        .file_name()
+        .ok_or("Invalid folder path")?
+        .to_string_lossy()
+        .to_string();
+
+    // Generate job ID for the sync
+    let job_id = format!("sync-{}-{}", student_id, chrono::Utc::now().timestamp_millis());
+
+    // Size is unknown until the receiver replies with what it needs
+    let job = FileTransferJob {
+        id: job_id.clone(),
+        file_name: format!("🔄 {}", folder_name),
+        file_size: 0,
+        transferred: 0,
+        status: TransferStatus::Pending,
+        direction: TransferDirection::Send,
+        student_id: student_id.clone(),
+        progress: 0.0,
+        priority,
+        queue_position: None,
+    };
+    state.add_job(job);
+    emit_progress(&app_handle, &state, &job_id);
+
+    // Calculate file transfer port
+    let transfer_port = student_port + FILE_TRANSFER_PORT_OFFSET;
+
+    // Clone for async task
+    let state_clone = Arc::clone(&state);
+    let app_clone = app_handle.clone();
+    let job_id_clone = job_id.clone();
+
+    // Spawn async task for non-blocking sync
+    tokio::spawn(async move {
+        let result = sync_folder_task(
+            state_clone.clone(),
+            app_clone.clone(),
+            student_ip,
+            transfer_port,
+            path,
+            folder_name,
+            delete_extraneous,
+            job_id_clone.clone(),
+        ).await;
+
+        if let Err(e) = result {
+            log::error!("[FileTransfer] Folder sync failed: {}", e);
+            state_clone.update_job(&job_id_clone, 0, TransferStatus::Failed { error: e });
+            emit_progress(&app_clone, &state_clone, &job_id_clone);
+        }
+    });
+
+    Ok(job_id)
+}
+
+/// Internal task to sync folder
+async fn sync_folder_task(
+    state: Arc<FileTransferState>,
+    app_handle: AppHandle,
+    student_ip: String,
+    transfer_port: u16,
+    folder_path: PathBuf,
+    folder_name: String,
+    delete_extraneous: bool,
+    job_id: String,
+) -> Result<(), String> {
+    // Wait for a free transfer slot (highest priority first)
+    let Some(_slot) = wait_for_slot(&state, &app_handle, &job_id).await else {
+        return Ok(());
+    };
+
+    // Hashing can take a while for large folders, keep it off the runtime
+    let manifest = tokio::task::spawn_blocking(move || build_sync_manifest(&folder_path))
+        .await
+        .map_err(|e| format!("Manifest task failed: {}", e))??;
+
+    // Update status to connecting
+    state.update_job(&job_id, 0, TransferStatus::Connecting);
+    emit_progress(&app_handle, &state, &job_id);
+
+    // Connect to student's file transfer port
+    let addr = format!("{}:{}", student_ip, transfer_port);
+    log::info!("[FileTransfer] Connecting to {} for folder sync", addr);
+
+    let mut stream = TcpStream::connect(&addr)
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {}", addr, e))?;
+
+    // Exchange manifest for the list of files the receiver needs
+    let init_msg = FileTransferMessage::SyncInit {
+        job_id: job_id.clone(),
+        folder_name: folder_name.clone(),
+        entries: manifest.iter().map(|(_, entry)| entry.clone()).collect(),
+        delete_extraneous,
+    };
+    send_message(&mut stream, &init_msg).await?;
+
+    let needed = match receive_message(&mut stream).await? {
+        FileTransferMessage::SyncRequest { needed, .. } => needed,
+        FileTransferMessage::Error { message, .. } => {
+            return Err(format!("Receiver error: {}", message));
+        }
+        _ => return Err("Unexpected response".to_string()),
+    };
+
+    let files: Vec<(PathBuf, String)> = manifest
+        .into_iter()
+        .filter(|(_, entry)| needed.contains(&entry.path))
+        .map(|(path, entry)| (path, entry.path))
+        .collect();
+    let total_size: u64 = files
+        .iter()
+        .map(|(p, _)| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0))
+        .sum();
+
+    if let Ok(mut jobs) = state.jobs.lock() {
+        if let Some(job) = jobs.get_mut(&job_id) {
+            job.file_name = format!("🔄 {} ({} changed)", folder_name, files.len());
+            job.file_size = total_size;
+        }
+    }
+
+    send_folder_files(&mut stream, &state, &app_handle, &folder_name, &files, &job_id).await?;
+    if state.is_cancelled(&job_id) {
+        return Ok(());
+    }
+
     state.update_job(&job_id, total_size, TransferStatus::Completed);
     emit_progress(&app_handle, &state, &job_id);
 
-    log::info!("[FileTransfer] Folder transfer completed: {} ({} files, {} bytes)", 
+    log::info!("[FileTransfer] Folder sync completed: {} ({} changed files, {} bytes)",
         folder_name, files.len(), total_size);
 
     Ok(())
@@ -675,9 +1506,9 @@ async fn handle_incoming_transfer(
     app_handle: AppHandle,
     mut stream: TcpStream,
 ) -> Result<(), String> {
-    // Get Downloads directory
-    let downloads_dir = dirs::download_dir()
-        .ok_or_else(|| "Failed to get Downloads directory".to_string())?;
+    // Every file is confined to the configured receive root
+    let policy = load_receive_policy();
+    let receive_root = policy.root_dir()?;
 
     let mut total_received: u64 = 0;
     let mut total_size: u64 = 0;
@@ -685,6 +1516,9 @@ async fn handle_incoming_transfer(
     let mut job_id: Option<String> = None;
     let mut is_folder_transfer = false;
     let mut last_progress_emit = std::time::Instant::now();
+    // Folder sync always replaces out-of-date files and restores sender mtimes
+    let mut on_collision = policy.on_collision;
+    let mut sync_mtimes: HashMap<String, u64> = HashMap::new();
 
     loop {
         // Receive message (Init for new file, or end of connection)
@@ -729,10 +1563,25 @@ async fn handle_incoming_transfer(
                         direction: TransferDirection::Receive,
                         student_id: "local".to_string(),
                         progress: 0.0,
+                        priority: TransferPriority::default(),
+                        queue_position: None,
                     };
                     state.add_job(job);
                 }
 
+                let current_job = job_id.clone().unwrap_or(jid);
+
+                // Enforce quotas before accepting any data
+                let projected_total = if is_folder_transfer {
+                    total_size + file_size
+                } else {
+                    file_size
+                };
+                if let Err(e) = policy.check_quota(file_size, projected_total) {
+                    reject_incoming(&mut stream, &app_handle, &state, &current_job, total_received, &e).await;
+                    return Err(e);
+                }
+
                 // For folder transfer, accumulate total size
                 if is_folder_transfer {
                     total_size += file_size;
@@ -750,41 +1599,31 @@ async fn handle_incoming_transfer(
 
                 emit_progress(&app_handle, &state, job_id.as_ref().unwrap());
 
-                // Create file path (handle subdirectories for folder transfer)
-                let file_path = if is_folder_transfer {
-                    let relative_path = downloads_dir.join(&file_name);
-                    // Create parent directories
-                    if let Some(parent) = relative_path.parent() {
-                        tokio::fs::create_dir_all(parent)
-                            .await
-                            .map_err(|e| format!("Failed to create directories: {}", e))?;
-                    }
-                    relative_path
-                } else {
-                    // Single file - handle duplicates
-                    let mut file_path = downloads_dir.join(&file_name);
-                    let mut counter = 1;
-                    while file_path.exists() {
-                        let stem = PathBuf::from(&file_name)
-                            .file_stem()
-                            .and_then(|s| s.to_str())
-                            .unwrap_or("file")
-                            .to_string();
-                        let ext = PathBuf::from(&file_name)
-                            .extension()
-                            .and_then(|s| s.to_str())
-                            .unwrap_or("")
-                            .to_string();
-
-                        let new_name = if ext.is_empty() {
-                            format!("{} ({})", stem, counter)
-                        } else {
-                            format!("{} ({}).{}", stem, counter, ext)
+                // Resolve destination inside the receive root (rejects traversal)
+                let file_path = match resolve_receive_path(&receive_root, &file_name, on_collision) {
+                    Ok(Some(path)) => path,
+                    Ok(None) => {
+                        log::info!("[FileTransfer] Skipping existing file: {}", file_name);
+                        let skip_msg = FileTransferMessage::Skip {
+                            job_id: current_job.clone(),
+                            reason: "File already exists".to_string(),
                         };
-                        file_path = downloads_dir.join(new_name);
-                        counter += 1;
+                        send_message(&mut stream, &skip_msg).await?;
+                        total_received += file_size;
+                        file_count += 1;
+
+                        if !is_folder_transfer {
+                            state.update_job(&current_job, total_size, TransferStatus::Completed);
+                            emit_progress(&app_handle, &state, &current_job);
+                            return Ok(());
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        log::warn!("[FileTransfer] Rejected file name {:?}: {}", file_name, e);
+                        reject_incoming(&mut stream, &app_handle, &state, &current_job, total_received, &e).await;
+                        return Err(e);
                     }
-                    file_path
                 };
 
                 // Send ack
@@ -806,11 +1645,22 @@ async fn handle_incoming_transfer(
                     .map_err(|e| format!("Failed to create file: {}", e))?;
 
                 // Receive chunks for this file
+                let mut file_received: u64 = 0;
                 loop {
                     let chunk_msg = receive_message(&mut stream).await?;
 
                     match chunk_msg {
                         FileTransferMessage::Chunk { data, .. } => {
+                            // Never accept more than the size that passed the quota check
+                            file_received += data.len() as u64;
+                            if file_received > file_size {
+                                drop(file);
+                                let _ = tokio::fs::remove_file(&file_path).await;
+                                let e = format!("{} exceeds its declared size of {} bytes", file_name, file_size);
+                                reject_incoming(&mut stream, &app_handle, &state, &current_job, total_received, &e).await;
+                                return Err(e);
+                            }
+
                             file.write_all(&data)
                                 .await
                                 .map_err(|e| format!("Failed to write chunk: {}", e))?;
@@ -829,6 +1679,12 @@ async fn handle_incoming_transfer(
                         FileTransferMessage::Complete { .. } => {
                             file_count += 1;
                             log::info!("[FileTransfer] File {} complete: {}", file_count, file_path.display());
+
+                            if let Some(&modified) = sync_mtimes.get(&file_name) {
+                                let _ = file.flush().await;
+                                drop(file);
+                                let _ = set_modified_secs(&file_path, modified);
+                            }
                             
                             // For single file, we're done
                             if !is_folder_transfer {
@@ -865,6 +1721,54 @@ async fn handle_incoming_transfer(
                     }
                 }
             }
+            FileTransferMessage::SyncInit { job_id: jid, folder_name, entries, delete_extraneous } => {
+                log::info!("[FileTransfer] Sync request for {} ({} files)", folder_name, entries.len());
+
+                let job = FileTransferJob {
+                    id: jid.clone(),
+                    file_name: format!("📁 {}", folder_name),
+                    file_size: 0,
+                    transferred: 0,
+                    status: TransferStatus::Pending,
+                    direction: TransferDirection::Receive,
+                    student_id: "local".to_string(),
+                    progress: 0.0,
+                    priority: TransferPriority::default(),
+                    queue_position: None,
+                };
+                state.add_job(job);
+                job_id = Some(jid.clone());
+
+                let plan = sanitize_relative_path(&folder_name)
+                    .and_then(|relative| ensure_dir_within(&receive_root, &relative))
+                    .and_then(|dest_dir| plan_sync(&dest_dir, &entries, delete_extraneous));
+                let (needed, deleted) = match plan {
+                    Ok(plan) => plan,
+                    Err(e) => {
+                        reject_incoming(&mut stream, &app_handle, &state, &jid, 0, &e).await;
+                        return Err(e);
+                    }
+                };
+                log::info!("[FileTransfer] Sync needs {} files, deleted {} extraneous", needed.len(), deleted);
+
+                for entry in &entries {
+                    sync_mtimes.insert(format!("{}/{}", folder_name, entry.path), entry.modified);
+                }
+                on_collision = CollisionPolicy::Overwrite;
+                is_folder_transfer = true;
+
+                let request_msg = FileTransferMessage::SyncRequest {
+                    job_id: jid.clone(),
+                    needed: needed.clone(),
+                };
+                send_message(&mut stream, &request_msg).await?;
+
+                if needed.is_empty() {
+                    state.update_job(&jid, 0, TransferStatus::Completed);
+                    emit_progress(&app_handle, &state, &jid);
+                    return Ok(());
+                }
+            }
             FileTransferMessage::Cancel { .. } => {
                 log::info!("[FileTransfer] Transfer cancelled");
                 if let Some(ref jid) = job_id {
@@ -899,6 +1803,24 @@ async fn handle_incoming_transfer(
     Ok(())
 }
 
+/// Tell the sender a file was refused and mark the receive job as failed
+async fn reject_incoming(
+    stream: &mut TcpStream,
+    app_handle: &AppHandle,
+    state: &FileTransferState,
+    job_id: &str,
+    received: u64,
+    error: &str,
+) {
+    let error_msg = FileTransferMessage::Error {
+        job_id: job_id.to_string(),
+        message: error.to_string(),
+    };
+    let _ = send_message(stream, &error_msg).await;
+    state.update_job(job_id, received, TransferStatus::Failed { error: error.to_string() });
+    emit_progress(app_handle, state, job_id);
+}
+
 /// Send a message over TCP
 async fn send_message(stream: &mut TcpStream, msg: &FileTransferMessage) -> Result<(), String> {
     let json = serde_json::to_vec(msg)
@@ -1107,3 +2029,270 @@ pub fn get_file_info(path: &str) -> Result<FileInfo, String> {
         modified,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sanitize_accepts_nested_paths() {
+        let path = sanitize_relative_path("lesson1/src/main.rs").unwrap();
+        assert_eq!(path, PathBuf::from("lesson1").join("src").join("main.rs"));
+
+        let path = sanitize_relative_path("lesson1\\src\\.\\main.rs").unwrap();
+        assert_eq!(path, PathBuf::from("lesson1").join("src").join("main.rs"));
+    }
+
+    #[test]
+    fn test_sanitize_rejects_traversal() {
+        for name in [
+            "../evil.exe",
+            "lesson/../../evil.exe",
+            "..\\evil.exe",
+            "lesson\\..\\..\\evil.exe",
+            "/etc/passwd",
+            "\\Windows\\System32\\evil.dll",
+            "C:\\Windows\\evil.dll",
+            "C:evil.dll",
+            "notes.txt:stream",
+            "",
+            "./",
+        ] {
+            assert!(sanitize_relative_path(name).is_err(), "accepted {:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_resolve_stays_inside_root() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("received");
+
+        let path = resolve_receive_path(&root, "folder/a.txt", CollisionPolicy::Rename)
+            .unwrap()
+            .unwrap();
+        assert!(path.starts_with(root.canonicalize().unwrap()));
+        assert!(path.parent().unwrap().is_dir());
+
+        assert!(resolve_receive_path(&root, "../outside.txt", CollisionPolicy::Overwrite).is_err());
+        assert!(!dir.path().join("outside.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_symlink_escape() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("received");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("target.txt"), root.join("file.txt")).unwrap();
+
+        assert!(resolve_receive_path(&root, "link/evil.txt", CollisionPolicy::Rename).is_err());
+        assert!(resolve_receive_path(&root, "link/nested/evil.txt", CollisionPolicy::Rename).is_err());
+        assert!(resolve_receive_path(&root, "file.txt", CollisionPolicy::Overwrite).is_err());
+        // Rejected before anything was created on the far side of the link
+        assert_eq!(std::fs::read_dir(&outside).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_collision_policies() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("notes.txt"), b"old").unwrap();
+
+        let renamed = resolve_receive_path(root, "notes.txt", CollisionPolicy::Rename)
+            .unwrap()
+            .unwrap();
+        assert_eq!(renamed.file_name().unwrap(), "notes (1).txt");
+
+        let overwritten = resolve_receive_path(root, "notes.txt", CollisionPolicy::Overwrite)
+            .unwrap()
+            .unwrap();
+        assert_eq!(overwritten.file_name().unwrap(), "notes.txt");
+
+        assert!(resolve_receive_path(root, "notes.txt", CollisionPolicy::Skip)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_plan_sync_transfers_only_changed_files() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("source");
+        let dest = dir.path().join("dest");
+        std::fs::create_dir_all(source.join("src")).unwrap();
+        std::fs::write(source.join("README.md"), b"starter").unwrap();
+        std::fs::write(source.join("src").join("main.rs"), b"fn main() {}").unwrap();
+
+        let manifest: Vec<SyncManifestEntry> = build_sync_manifest(&source)
+            .unwrap()
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .collect();
+        assert!(manifest.iter().any(|e| e.path == "src/main.rs"));
+
+        // Empty destination needs everything
+        let (needed, _) = plan_sync(&dest, &manifest, false).unwrap();
+        assert_eq!(needed.len(), 2);
+
+        // Same contents with a different mtime are hashed and accepted
+        std::fs::create_dir_all(dest.join("src")).unwrap();
+        std::fs::write(dest.join("README.md"), b"starter").unwrap();
+        std::fs::write(dest.join("src").join("main.rs"), b"fn main() { edited }").unwrap();
+        std::fs::write(dest.join("extra.txt"), b"student notes").unwrap();
+        let (needed, deleted) = plan_sync(&dest, &manifest, false).unwrap();
+        assert_eq!(needed, vec!["src/main.rs".to_string()]);
+        assert_eq!(deleted, 0);
+        assert!(dest.join("extra.txt").exists());
+
+        let (_, deleted) = plan_sync(&dest, &manifest, true).unwrap();
+        assert_eq!(deleted, 1);
+        assert!(!dest.join("extra.txt").exists());
+        assert!(dest.join("README.md").exists());
+    }
+
+    #[test]
+    fn test_plan_sync_rejects_traversal() {
+        let dir = tempdir().unwrap();
+        let entries = vec![SyncManifestEntry {
+            path: "../evil.txt".to_string(),
+            size: 1,
+            modified: 0,
+            hash: String::new(),
+        }];
+        assert!(plan_sync(dir.path(), &entries, false).is_err());
+    }
+
+    #[test]
+    fn test_rate_limiter_waits_for_debt() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter {
+            available: 0.0,
+            last_refill: start,
+        };
+
+        // 2000 bytes at 1000 B/s with an empty bucket needs two seconds
+        let wait = limiter.consume(2000.0, 1000.0, start);
+        assert_eq!(wait, Duration::from_secs(2));
+
+        // Burst is capped at one second worth of tokens
+        let later = start + Duration::from_secs(10);
+        assert_eq!(limiter.consume(1000.0, 1000.0, later), Duration::ZERO);
+        assert!(limiter.consume(1.0, 1000.0, later) > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_queue_orders_by_priority_then_fifo() {
+        let mut queue = TransferQueue::default();
+        queue.enqueue("low", TransferPriority::Low);
+        queue.enqueue("normal-1", TransferPriority::Normal);
+        queue.enqueue("high", TransferPriority::High);
+        queue.enqueue("normal-2", TransferPriority::Normal);
+
+        assert_eq!(queue.position("high"), Some(0));
+        assert_eq!(queue.position("normal-1"), Some(1));
+        assert_eq!(queue.position("normal-2"), Some(2));
+        assert_eq!(queue.position("low"), Some(3));
+
+        assert!(!queue.try_start("normal-1", 1));
+        assert!(queue.try_start("high", 1));
+        assert!(!queue.try_start("normal-1", 1));
+        queue.active -= 1;
+        assert!(queue.try_start("normal-1", 1));
+    }
+
+    fn queued_job(id: &str, priority: TransferPriority) -> FileTransferJob {
+        FileTransferJob {
+            id: id.to_string(),
+            file_name: id.to_string(),
+            file_size: 0,
+            transferred: 0,
+            status: TransferStatus::Pending,
+            direction: TransferDirection::Send,
+            student_id: "student".to_string(),
+            progress: 0.0,
+            priority,
+            queue_position: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slots_limit_concurrency_and_honor_priority() {
+        let state = Arc::new(FileTransferState::new());
+        state.set_limits(TransferLimits {
+            max_concurrent_jobs: 1,
+            ..TransferLimits::default()
+        });
+        for (id, priority) in [
+            ("first", TransferPriority::Normal),
+            ("low", TransferPriority::Low),
+            ("high", TransferPriority::High),
+        ] {
+            state.add_job(queued_job(id, priority));
+        }
+
+        let first = state.acquire_slot("first").await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for id in ["low", "high"] {
+            let state = Arc::clone(&state);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let slot = state.acquire_slot(id).await;
+                tx.send(id).unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                drop(slot);
+            });
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(rx.try_recv().is_err());
+
+        drop(first);
+        assert_eq!(rx.recv().await, Some("high"));
+        assert_eq!(rx.recv().await, Some("low"));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_removes_queued_job() {
+        let state = Arc::new(FileTransferState::new());
+        state.set_limits(TransferLimits {
+            max_concurrent_jobs: 1,
+            ..TransferLimits::default()
+        });
+        state.add_job(queued_job("running", TransferPriority::Normal));
+        state.add_job(queued_job("waiting", TransferPriority::Normal));
+
+        let _running = state.acquire_slot("running").await.unwrap();
+        let waiter = {
+            let state = Arc::clone(&state);
+            tokio::spawn(async move { state.acquire_slot("waiting").await.is_none() })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert!(state.cancel_job("waiting"));
+        assert!(waiter.await.unwrap());
+    }
+
+    #[test]
+    fn test_quota_limits() {
+        let policy = ReceivePolicy {
+            receive_root: None,
+            on_collision: CollisionPolicy::Rename,
+            max_file_size: 100,
+            max_transfer_size: 250,
+        };
+
+        assert!(policy.check_quota(100, 200).is_ok());
+        assert!(policy.check_quota(101, 101).is_err());
+        assert!(policy.check_quota(50, 251).is_err());
+
+        let unlimited = ReceivePolicy {
+            max_file_size: 0,
+            max_transfer_size: 0,
+            ..policy
+        };
+        assert!(unlimited.check_quota(u64::MAX, u64::MAX).is_ok());
+    }
+}
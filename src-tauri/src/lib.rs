@@ -25,6 +25,7 @@ mod student_tray;
 mod teacher_connector;
 mod udp_audio;
 mod udp_frame_transport;
+mod zip_stream;
 
 use crypto::{KeyPairInfo, VerifyResult};
 use document_distribution::{Document, DocumentServerState};
@@ -39,6 +40,17 @@ use lan_discovery::{discover_devices, respond_to_discovery, DiscoveredDevice};
 pub struct ServerState {
     process: Mutex<Option<Child>>,
     info: Mutex<Option<ServerInfo>>,
+    /// Where and how to reach the SFU admin API, new for each server started
+    admin: Mutex<Option<SfuAdmin>>,
+}
+
+/// Port the SFU serves stats and the admin API on, pinned so that a port from
+/// ~/.smartlab/sfu.toml cannot send admin requests elsewhere
+const SFU_METRICS_PORT: u16 = 3026;
+
+struct SfuAdmin {
+    port: u16,
+    token: String,
 }
 
 /// Global AppHandle for logging (set during app initialization)
@@ -121,6 +133,15 @@ fn start_server(#[allow(unused)] app: AppHandle, state: State<ServerState>) -> R
         }
     }
 
+    // The server only accepts teacher joins signed with this key
+    let keypair = crypto::ensure_keypair()?;
+
+    // Lets this app list and close rooms through the SFU's admin API
+    let admin_token = {
+        use base64::Engine as _;
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>())
+    };
+
     #[cfg(debug_assertions)]
     let child = {
         let mut root_dir = std::env::current_dir().map_err(|e| e.to_string())?;
@@ -140,6 +161,9 @@ fn start_server(#[allow(unused)] app: AppHandle, state: State<ServerState>) -> R
         command
             .args(["run", "--release"])
             .current_dir(&rust_server_dir)
+            .env("SMARTLAB_TEACHER_KEYS", &keypair.public_key)
+            .env("SMARTLAB_SFU_ADMIN_TOKEN", &admin_token)
+            .env("SMARTLAB_SFU_METRICS_PORT", SFU_METRICS_PORT.to_string())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
@@ -179,7 +203,12 @@ fn start_server(#[allow(unused)] app: AppHandle, state: State<ServerState>) -> R
         println!("[Server] Starting Rust mediasoup server: {:?}", binary_path);
 
         let mut command = Command::new(&binary_path);
-        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        command
+            .env("SMARTLAB_TEACHER_KEYS", &keypair.public_key)
+            .env("SMARTLAB_SFU_ADMIN_TOKEN", &admin_token)
+            .env("SMARTLAB_SFU_METRICS_PORT", SFU_METRICS_PORT.to_string())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
 
         #[cfg(unix)]
         {
@@ -200,6 +229,10 @@ fn start_server(#[allow(unused)] app: AppHandle, state: State<ServerState>) -> R
     };
 
     *process_guard = Some(child);
+    *state.admin.lock().map_err(|e| e.to_string())? = Some(SfuAdmin {
+        port: SFU_METRICS_PORT,
+        token: admin_token,
+    });
 
     // Wait for server to start (Rust server starts faster than Node.js)
     std::thread::sleep(std::time::Duration::from_millis(1000));
@@ -228,6 +261,7 @@ fn stop_server(state: State<ServerState>) -> Result<(), String> {
 
     let mut info_guard = state.info.lock().map_err(|e| e.to_string())?;
     *info_guard = None;
+    *state.admin.lock().map_err(|e| e.to_string())? = None;
 
     Ok(())
 }
@@ -240,6 +274,90 @@ fn get_server_info(state: State<ServerState>) -> Result<ServerInfo, String> {
         .ok_or_else(|| "Server not running".to_string())
 }
 
+/// Admin API URL for `segments` under /admin on the SFU this app started, and its token
+fn sfu_admin_url(state: &ServerState, segments: &[&str]) -> Result<(reqwest::Url, String), String> {
+    let (port, token) = state
+        .admin
+        .lock()
+        .map_err(|e| e.to_string())?
+        .as_ref()
+        .map(|admin| (admin.port, admin.token.clone()))
+        .ok_or_else(|| "Server not running".to_string())?;
+
+    let mut url = reqwest::Url::parse(&format!("http://127.0.0.1:{}/admin", port))
+        .map_err(|e| e.to_string())?;
+    url.path_segments_mut()
+        .map_err(|_| "Invalid admin URL".to_string())?
+        .extend(segments);
+    Ok((url, token))
+}
+
+/// Send an admin request; the server's error message on failure
+async fn sfu_admin_send(
+    request: reqwest::RequestBuilder,
+    token: &str,
+) -> Result<reqwest::Response, String> {
+    let response = request
+        .bearer_auth(token)
+        .timeout(std::time::Duration::from_secs(5))
+        .send()
+        .await
+        .map_err(|e| format!("SFU admin API not reachable: {}", e))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    Err(body["error"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("SFU admin API error: {}", status)))
+}
+
+/// Live rooms on the SFU: peers, teacher and producers of each
+#[tauri::command]
+async fn list_sfu_rooms(state: State<'_, ServerState>) -> Result<serde_json::Value, String> {
+    let (url, token) = sfu_admin_url(&state, &["rooms"])?;
+    let response = sfu_admin_send(reqwest::Client::new().get(url), &token).await?;
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid admin API response: {}", e))
+}
+
+/// Close a stale room; everyone in it is disconnected with `reason`
+#[tauri::command]
+async fn close_sfu_room(
+    room_id: String,
+    reason: Option<String>,
+    state: State<'_, ServerState>,
+) -> Result<(), String> {
+    let (mut url, token) = sfu_admin_url(&state, &["rooms", &room_id])?;
+    if let Some(reason) = reason {
+        url.query_pairs_mut().append_pair("reason", &reason);
+    }
+    sfu_admin_send(reqwest::Client::new().delete(url), &token).await?;
+    log::info!("[Server] Closed SFU room {}", room_id);
+    Ok(())
+}
+
+#[tauri::command]
+async fn kick_sfu_peer(
+    room_id: String,
+    peer_id: String,
+    reason: Option<String>,
+    state: State<'_, ServerState>,
+) -> Result<(), String> {
+    let (mut url, token) = sfu_admin_url(&state, &["rooms", &room_id, "peers", &peer_id])?;
+    if let Some(reason) = reason {
+        url.query_pairs_mut().append_pair("reason", &reason);
+    }
+    sfu_admin_send(reqwest::Client::new().delete(url), &token).await?;
+    log::info!("[Server] Removed {} from SFU room {}", peer_id, room_id);
+    Ok(())
+}
+
 #[derive(Default)]
 pub struct DatabaseState {
     conn: Mutex<Option<rusqlite::Connection>>,
@@ -679,6 +797,31 @@ fn crypto_verify_signature(
     ))
 }
 
+/// Create a join token for the classroom server, signed with the teacher key.
+/// `role` is "teacher" or "student"; `peer_id` binds the token to one client.
+#[tauri::command]
+fn crypto_create_join_token(
+    room_id: String,
+    role: String,
+    peer_id: Option<String>,
+    ttl_minutes: Option<u64>,
+) -> Result<String, String> {
+    if role != "teacher" && role != "student" {
+        return Err(format!("Unknown role: {}", role));
+    }
+
+    let keypair = crypto::ensure_keypair()?;
+    // At most a day, so a typo in the TTL cannot mint a token that never expires
+    let ttl_secs = ttl_minutes.unwrap_or(120).clamp(1, 24 * 60).saturating_mul(60);
+    let claims = crypto::JoinTokenClaims {
+        room_id,
+        role,
+        peer_id,
+        expires_at: (chrono::Utc::now().timestamp().max(0) as u64).saturating_add(ttl_secs),
+    };
+    crypto::sign_join_token(&keypair.private_key, &claims)
+}
+
 /// Generate a random challenge
 #[tauri::command]
 fn crypto_generate_challenge() -> String {
@@ -1090,7 +1233,10 @@ fn send_remote_mouse_event(
     student_id: String,
     event: teacher_connector::MouseInputEvent,
     state: State<Arc<ConnectorState>>,
+    transfer_state: State<Arc<FileTransferState>>,
 ) -> Result<(), String> {
+    // File transfers back off while the teacher is remote controlling
+    transfer_state.note_remote_control();
     teacher_connector::send_mouse_input(&state, &student_id, event)
 }
 
@@ -1100,7 +1246,9 @@ fn send_remote_keyboard_event(
     student_id: String,
     event: teacher_connector::KeyboardInputEvent,
     state: State<Arc<ConnectorState>>,
+    transfer_state: State<Arc<FileTransferState>>,
 ) -> Result<(), String> {
+    transfer_state.note_remote_control();
     teacher_connector::send_keyboard_input(&state, &student_id, event)
 }
 
@@ -1240,6 +1388,7 @@ async fn send_file_to_student(
     app: AppHandle,
     student_id: String,
     file_path: String,
+    priority: Option<file_transfer::TransferPriority>,
     connector_state: State<'_, Arc<ConnectorState>>,
     transfer_state: State<'_, Arc<FileTransferState>>,
 ) -> Result<String, String> {
@@ -1255,6 +1404,34 @@ async fn send_file_to_student(
         conn.port,
         file_path,
         student_id,
+        priority.unwrap_or_default(),
+    ).await
+}
+
+/// Sync a folder to a student, sending only missing or changed files
+#[tauri::command]
+async fn sync_folder_to_student(
+    app: AppHandle,
+    student_id: String,
+    folder_path: String,
+    delete_extraneous: Option<bool>,
+    priority: Option<file_transfer::TransferPriority>,
+    connector_state: State<'_, Arc<ConnectorState>>,
+    transfer_state: State<'_, Arc<FileTransferState>>,
+) -> Result<String, String> {
+    // Get student connection info
+    let conn = connector_state.get_connection(&student_id)
+        .ok_or_else(|| "Student not connected".to_string())?;
+
+    file_transfer::sync_folder_chunked(
+        Arc::clone(&transfer_state),
+        app,
+        conn.ip,
+        conn.port,
+        folder_path,
+        student_id,
+        delete_extraneous.unwrap_or(false),
+        priority.unwrap_or_default(),
     ).await
 }
 
@@ -1267,7 +1444,7 @@ fn cancel_file_transfer(
     Ok(state.cancel_job(&job_id))
 }
 
-/// Get file transfer job status
+/// Get file transfer job status (including queue position while queued)
 #[tauri::command]
 fn get_file_transfer_status(
     job_id: String,
@@ -1276,10 +1453,53 @@ fn get_file_transfer_status(
     Ok(state.get_job(&job_id))
 }
 
+/// Get bandwidth and concurrency limits for outgoing transfers
+#[tauri::command]
+fn get_file_transfer_limits(state: State<Arc<FileTransferState>>) -> file_transfer::TransferLimits {
+    state.get_limits()
+}
+
+/// Set bandwidth and concurrency limits for outgoing transfers
+#[tauri::command]
+fn set_file_transfer_limits(
+    limits: file_transfer::TransferLimits,
+    state: State<Arc<FileTransferState>>,
+) -> Result<(), String> {
+    if limits.max_concurrent_jobs == 0 {
+        return Err("max_concurrent_jobs must be at least 1".to_string());
+    }
+    state.set_limits(limits);
+    Ok(())
+}
+
 // ============================================================
 // File Transfer Commands
 // ============================================================
 
+/// Get the policy applied to files received from the teacher
+#[tauri::command]
+fn get_receive_policy() -> file_transfer::ReceivePolicy {
+    file_transfer::load_receive_policy()
+}
+
+/// Save the policy applied to files received from the teacher
+#[tauri::command]
+fn save_receive_policy(policy: file_transfer::ReceivePolicy) -> Result<(), String> {
+    file_transfer::save_receive_policy(&policy)
+}
+
+/// Get what the student agent does with documents the teacher publishes
+#[tauri::command]
+fn get_document_download_config() -> student_agent::DocumentDownloadConfig {
+    student_agent::load_document_download_config()
+}
+
+/// Save what the student agent does with documents the teacher publishes
+#[tauri::command]
+fn save_document_download_config(config: student_agent::DocumentDownloadConfig) -> Result<(), String> {
+    student_agent::save_document_download_config(&config)
+}
+
 /// List files in a directory
 #[tauri::command]
 fn list_directory(path: String) -> Result<Vec<file_transfer::FileInfo>, String> {
@@ -1382,9 +1602,7 @@ fn start_document_server(
 /// Stop document distribution server
 #[tauri::command]
 fn stop_document_server(state: State<Arc<DocumentServerState>>) -> Result<(), String> {
-    let mut is_running = state.is_running.lock().map_err(|e| e.to_string())?;
-    *is_running = false;
-    Ok(())
+    document_distribution::stop_document_server(&state)
 }
 
 /// Get document server status
@@ -1400,6 +1618,30 @@ fn get_document_server_status(state: State<Arc<DocumentServerState>>) -> Result<
     Ok((is_running, port, url))
 }
 
+/// Tell connected students about a newly uploaded public document
+fn announce_document(doc: &Document, doc_state: &DocumentServerState, connector_state: &ConnectorState) {
+    if doc.visibility != document_distribution::DocumentVisibility::Public {
+        return;
+    }
+    let is_running = doc_state.is_running.lock().map(|r| *r).unwrap_or(false);
+    if !is_running {
+        log::info!("[DocumentServer] Server not running, not announcing {}", doc.name);
+        return;
+    }
+
+    let port = doc_state.server_port.lock().map(|p| *p).unwrap_or(document_distribution::DOCUMENT_SERVER_PORT);
+    let download_url = format!("http://{}:{}/download/{}", get_local_ip(), port, doc.id);
+    if let Err(e) = teacher_connector::broadcast_document_published(
+        connector_state,
+        doc.id.clone(),
+        doc.name.clone(),
+        doc.size,
+        download_url,
+    ) {
+        log::warn!("[DocumentServer] Failed to announce {}: {}", doc.name, e);
+    }
+}
+
 /// Upload a document
 #[tauri::command]
 async fn upload_document(
@@ -1407,15 +1649,20 @@ async fn upload_document(
     data: Vec<u8>,
     description: Option<String>,
     category: Option<String>,
+    folder: Option<String>,
     state: State<'_, Arc<DocumentServerState>>,
+    connector_state: State<'_, Arc<ConnectorState>>,
 ) -> Result<Document, String> {
-    document_distribution::save_document(
+    let doc = document_distribution::save_document(
         Arc::clone(&state),
         name,
         data,
         description,
         category,
-    ).await
+        folder,
+    ).await?;
+    announce_document(&doc, &state, &connector_state);
+    Ok(doc)
 }
 
 /// Upload document from file path
@@ -1424,7 +1671,9 @@ async fn upload_document_from_path(
     file_path: String,
     description: Option<String>,
     category: Option<String>,
+    folder: Option<String>,
     state: State<'_, Arc<DocumentServerState>>,
+    connector_state: State<'_, Arc<ConnectorState>>,
 ) -> Result<Document, String> {
     let path = std::path::PathBuf::from(&file_path);
     
@@ -1439,13 +1688,16 @@ async fn upload_document_from_path(
         .await
         .map_err(|e| format!("Failed to read file: {}", e))?;
     
-    document_distribution::save_document(
+    let doc = document_distribution::save_document(
         Arc::clone(&state),
         name,
         data,
         description,
         category,
-    ).await
+        folder,
+    ).await?;
+    announce_document(&doc, &state, &connector_state);
+    Ok(doc)
 }
 
 /// Delete a document
@@ -1469,6 +1721,93 @@ fn get_document(id: String, state: State<Arc<DocumentServerState>>) -> Option<Do
     state.get_document(&id)
 }
 
+/// Move a document to another folder and edit its category and description
+#[tauri::command]
+fn update_document_details(
+    id: String,
+    folder: Option<String>,
+    category: Option<String>,
+    description: Option<String>,
+    state: State<Arc<DocumentServerState>>,
+) -> Result<Document, String> {
+    document_distribution::update_document_details(&state, &id, folder, category, description)
+}
+
+/// Restrict a document to a session or group, optionally hidden until a given time
+#[tauri::command]
+fn set_document_visibility(
+    id: String,
+    visibility: document_distribution::DocumentVisibility,
+    available_from: Option<u64>,
+    state: State<Arc<DocumentServerState>>,
+) -> Result<Document, String> {
+    document_distribution::set_document_visibility(&state, &id, visibility, available_from)
+}
+
+/// Create a signed, time-limited link to the document page for a session or group
+#[tauri::command]
+fn create_document_access_link(
+    session_id: Option<String>,
+    group: Option<String>,
+    document_id: Option<String>,
+    ttl_minutes: Option<u64>,
+    state: State<Arc<DocumentServerState>>,
+) -> Result<String, String> {
+    let ttl_secs = ttl_minutes
+        .map(|m| m.saturating_mul(60))
+        .unwrap_or(document_distribution::DEFAULT_LINK_TTL_SECS);
+    let expires_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs()
+        .saturating_add(ttl_secs);
+
+    let claims = document_distribution::AccessClaims {
+        session_id,
+        group,
+        document_id: document_id.clone(),
+        expires_at,
+    };
+    let token = document_distribution::issue_access_token(&state, &claims)?;
+    let port = *state.server_port.lock().map_err(|e| e.to_string())?;
+    let base = format!("http://{}:{}", get_local_ip(), port);
+
+    Ok(match document_id {
+        Some(id) => format!("{}/download/{}?token={}", base, id, token),
+        None => format!("{}/?token={}", base, token),
+    })
+}
+
+/// List assignment submissions uploaded by students (newest first)
+#[tauri::command]
+fn list_submissions(state: State<Arc<DocumentServerState>>) -> Vec<document_distribution::Submission> {
+    state.list_submissions()
+}
+
+/// Copy a submission's files into a folder chosen by the teacher
+#[tauri::command]
+async fn export_submission(
+    id: String,
+    target_folder: Option<String>,
+    state: State<'_, Arc<DocumentServerState>>,
+) -> Result<String, String> {
+    let target_dir = match target_folder {
+        Some(folder) => std::path::PathBuf::from(folder),
+        None => dirs::download_dir()
+            .ok_or_else(|| "Failed to get Downloads directory".to_string())?,
+    };
+    document_distribution::export_submission(&state, &id, target_dir).await
+}
+
+/// Delete a submission and its files
+#[tauri::command]
+async fn delete_submission(
+    id: String,
+    state: State<'_, Arc<DocumentServerState>>,
+) -> Result<(), String> {
+    document_distribution::delete_submission(&state, &id).await
+}
+
 /// Download document from URL to Downloads folder or custom folder
 #[tauri::command]
 async fn download_document_to_downloads(
@@ -1476,6 +1815,57 @@ async fn download_document_to_downloads(
     filename: String,
     custom_folder: Option<String>,
 ) -> Result<String, String> {
+    download_to_folder(&url, &filename, custom_folder).await
+}
+
+/// Download several documents as one ZIP: a category, a folder, or a list of ids
+#[tauri::command]
+async fn download_documents_zip(
+    server_url: String,
+    category: Option<String>,
+    folder: Option<String>,
+    ids: Option<Vec<String>>,
+    custom_folder: Option<String>,
+) -> Result<String, String> {
+    let mut params: Vec<(&str, String)> = Vec::new();
+    if let Some(ids) = &ids {
+        params.push(("ids", ids.join(",")));
+    }
+    if let Some(category) = &category {
+        params.push(("category", category.clone()));
+    }
+    if let Some(folder) = &folder {
+        params.push(("folder", folder.clone()));
+    }
+    if params.is_empty() {
+        return Err("Chưa chọn tài liệu để tải".to_string());
+    }
+
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/download-zip", server_url.trim_end_matches('/')),
+        &params,
+    )
+    .map_err(|e| format!("Invalid server URL: {}", e))?;
+
+    let label = folder
+        .as_deref()
+        .and_then(|f| f.trim_matches('/').rsplit('/').next())
+        .or(category.as_deref())
+        .filter(|l| !l.trim().is_empty())
+        .unwrap_or("tai-lieu");
+    let filename = format!("{}.zip", document_distribution::sanitize_file_name(label));
+
+    download_to_folder(url.as_str(), &filename, custom_folder).await
+}
+
+/// Stream `url` into the Downloads folder (or `custom_folder`) without overwriting
+async fn download_to_folder(
+    url: &str,
+    filename: &str,
+    custom_folder: Option<String>,
+) -> Result<String, String> {
+    use tokio::io::AsyncWriteExt;
+
     // Get target directory
     let target_dir = if let Some(folder) = custom_folder {
         std::path::PathBuf::from(folder)
@@ -1492,17 +1882,17 @@ async fn download_document_to_downloads(
     }
     
     // Create file path
-    let mut file_path = target_dir.join(&filename);
+    let mut file_path = target_dir.join(filename);
     
     // Handle duplicate filenames
     let mut counter = 1;
     while file_path.exists() {
-        let stem = std::path::Path::new(&filename)
+        let stem = std::path::Path::new(filename)
             .file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("file")
             .to_string();
-        let ext = std::path::Path::new(&filename)
+        let ext = std::path::Path::new(filename)
             .extension()
             .and_then(|s| s.to_str())
             .unwrap_or("")
@@ -1518,7 +1908,7 @@ async fn download_document_to_downloads(
     }
     
     // Download file
-    let response = reqwest::get(&url)
+    let mut response = reqwest::get(url)
         .await
         .map_err(|e| format!("Failed to download: {}", e))?;
     
@@ -1526,14 +1916,29 @@ async fn download_document_to_downloads(
         return Err(format!("HTTP error: {}", response.status()));
     }
     
-    let bytes = response.bytes()
-        .await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
-    
-    // Write to file
-    tokio::fs::write(&file_path, bytes)
+    // Write chunks as they arrive; archives can be far larger than memory allows
+    let mut file = tokio::fs::File::create(&file_path)
         .await
         .map_err(|e| format!("Failed to write file: {}", e))?;
+    let result: Result<(), String> = async {
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| format!("Failed to read response: {}", e))?
+        {
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| format!("Failed to write file: {}", e))?;
+        }
+        file.flush().await.map_err(|e| format!("Failed to write file: {}", e))
+    }
+    .await;
+
+    if let Err(e) = result {
+        drop(file);
+        let _ = tokio::fs::remove_file(&file_path).await;
+        return Err(e);
+    }
     
     Ok(file_path.to_string_lossy().to_string())
 }
@@ -2357,6 +2762,9 @@ pub fn run() {
             start_server,
             stop_server,
             get_server_info,
+            list_sfu_rooms,
+            close_sfu_room,
+            kick_sfu_peer,
             start_udp_audio_capture,
             stop_udp_audio_capture,
             read_audio_samples,
@@ -2382,6 +2790,7 @@ pub fn run() {
             crypto_sign_challenge,
             crypto_verify_signature,
             crypto_generate_challenge,
+            crypto_create_join_token,
             // Auth mode commands
             auth_set_mode,
             auth_get_mode,
@@ -2419,8 +2828,11 @@ pub fn run() {
             send_logout_command,
             start_teacher_discovery,
             send_file_to_student,
+            sync_folder_to_student,
             cancel_file_transfer,
             get_file_transfer_status,
+            get_file_transfer_limits,
+            set_file_transfer_limits,
             // File Transfer commands
             list_directory,
             get_home_directory,
@@ -2430,7 +2842,12 @@ pub fn run() {
             write_file_from_base64,
             get_file_info,
             get_student_directory,
+            get_receive_policy,
+            save_receive_policy,
+            get_document_download_config,
+            save_document_download_config,
             download_document_to_downloads,
+            download_documents_zip,
             // Document Distribution commands
             start_document_server,
             stop_document_server,
@@ -2440,6 +2857,12 @@ pub fn run() {
             delete_document,
             list_documents,
             get_document,
+            update_document_details,
+            set_document_visibility,
+            create_document_access_link,
+            list_submissions,
+            export_submission,
+            delete_submission,
             // Auto-Update commands
             check_for_updates,
             download_update,
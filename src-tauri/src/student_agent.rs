@@ -62,6 +62,58 @@ impl Default for AgentConfig {
     }
 }
 
+/// What the agent does when the teacher publishes a document
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentDownloadConfig {
+    /// Download published documents without asking
+    #[serde(default)]
+    pub auto_download: bool,
+    /// Target folder; Downloads when unset
+    #[serde(default)]
+    pub download_dir: Option<String>,
+}
+
+impl DocumentDownloadConfig {
+    /// Folder auto-downloaded documents are saved into
+    pub fn target_dir(&self) -> Result<std::path::PathBuf, String> {
+        match &self.download_dir {
+            Some(dir) if !dir.trim().is_empty() => Ok(std::path::PathBuf::from(dir)),
+            _ => dirs::download_dir().ok_or_else(|| "Failed to get Downloads directory".to_string()),
+        }
+    }
+}
+
+/// Get the document download config path (~/.smartlab/document_download.json)
+pub fn get_document_download_config_path() -> std::path::PathBuf {
+    let home = dirs::home_dir().unwrap_or_else(|| std::path::PathBuf::from("."));
+    home.join(".smartlab").join("document_download.json")
+}
+
+/// Load the document download config, using defaults if missing or invalid
+pub fn load_document_download_config() -> DocumentDownloadConfig {
+    match std::fs::read_to_string(get_document_download_config_path()) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("[StudentAgent] Invalid document download config: {}, using defaults", e);
+            DocumentDownloadConfig::default()
+        }),
+        Err(_) => DocumentDownloadConfig::default(),
+    }
+}
+
+/// Save the document download config
+pub fn save_document_download_config(config: &DocumentDownloadConfig) -> Result<(), String> {
+    let path = get_document_download_config_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let json = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize document download config: {}", e))?;
+    std::fs::write(&path, json)
+        .map_err(|e| format!("Failed to write document download config: {}", e))
+}
+
 /// Mouse button type
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "lowercase")]
@@ -180,6 +232,15 @@ pub enum TeacherMessage {
     UdpOffer {
         udp_port: u16,
     },
+
+    /// Teacher published a new document on the document server
+    #[serde(rename = "document_published")]
+    DocumentPublished {
+        document_id: String,
+        name: String,
+        size: u64,
+        download_url: String,
+    },
 }
 
 /// Messages from student to teacher
@@ -267,6 +328,14 @@ pub enum StudentMessage {
     /// Student reports UDP failed, will use WebSocket
     #[serde(rename = "udp_fallback")]
     UdpFallback,
+
+    /// Result of auto-downloading a published document
+    #[serde(rename = "document_downloaded")]
+    DocumentDownloaded {
+        document_id: String,
+        success: bool,
+        message: String,
+    },
 }/// Connection state for a single teacher connection
 struct TeacherConnection {
     #[allow(dead_code)]
@@ -295,6 +364,8 @@ pub struct AgentState {
     pub udp_socket: Mutex<Option<Arc<std::net::UdpSocket>>>,
     /// UDP target address (teacher_ip:port)
     pub udp_target: Mutex<Option<SocketAddr>>,
+    /// Tray notifications (title, body) waiting to be shown
+    notifications: Mutex<Vec<(String, String)>>,
 }
 
 impl Default for AgentState {
@@ -308,6 +379,7 @@ impl Default for AgentState {
             auto_connect_stop: Arc::new(AtomicBool::new(false)),
             udp_socket: Mutex::new(None),
             udp_target: Mutex::new(None),
+            notifications: Mutex::new(Vec::new()),
         }
     }
 }
@@ -348,6 +420,21 @@ impl AgentState {
             AgentStatus::UpdateRequired { .. } | AgentStatus::Updating { .. }
         )
     }
+
+    /// Queue a tray notification; shown by `student_tray::monitor_agent_status`
+    pub fn push_notification(&self, title: &str, body: &str) {
+        if let Ok(mut queue) = self.notifications.lock() {
+            queue.push((title.to_string(), body.to_string()));
+        }
+    }
+
+    /// Take all queued tray notifications
+    pub fn take_notifications(&self) -> Vec<(String, String)> {
+        self.notifications
+            .lock()
+            .map(|mut queue| std::mem::take(&mut *queue))
+            .unwrap_or_default()
+    }
 }
 
 /// Handle a single WebSocket connection from teacher
@@ -654,7 +741,7 @@ where
                 addr
             );
 
-            // Save file to Downloads folder
+            // Save file inside the receive root
             match save_received_file(&file_name, &file_data).await {
                 Ok(save_path) => {
                     log::info!("[StudentAgent] File saved to: {}", save_path);
@@ -894,6 +981,50 @@ where
             );
         }
 
+        TeacherMessage::DocumentPublished {
+            document_id,
+            name,
+            size,
+            download_url,
+        } => {
+            log::info!(
+                "[StudentAgent] Document published: {} ({} bytes) at {}",
+                name,
+                size,
+                download_url
+            );
+            state.push_notification("Tài liệu mới", &name);
+
+            let config = load_document_download_config();
+            if config.auto_download {
+                // Download in the background and report through the frame channel
+                let state = Arc::clone(state);
+                let frame_tx = frame_tx.clone();
+                tokio::spawn(async move {
+                    let response = match download_published_document(&config, &name, size, &download_url).await {
+                        Ok(path) => {
+                            log::info!("[StudentAgent] Document saved to: {}", path);
+                            state.push_notification("Đã tải tài liệu", &format!("{} → {}", name, path));
+                            StudentMessage::DocumentDownloaded {
+                                document_id,
+                                success: true,
+                                message: path,
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("[StudentAgent] Failed to download document {}: {}", name, e);
+                            StudentMessage::DocumentDownloaded {
+                                document_id,
+                                success: false,
+                                message: e,
+                            }
+                        }
+                    };
+                    let _ = frame_tx.send(status_frame(&response)).await;
+                });
+            }
+        }
+
         TeacherMessage::UdpOffer { udp_port } => {
             log::info!("[StudentAgent] Received UDP offer: port {} from {}", udp_port, addr);
 
@@ -1230,43 +1361,21 @@ where
         .map_err(|e| format!("Failed to send: {}", e))
 }
 
-/// Save received file to Downloads folder
+/// Save received file inside the configured receive root (Downloads by default)
 async fn save_received_file(file_name: &str, file_data: &str) -> Result<String, String> {
-    use std::path::PathBuf;
-
-    // Get Downloads directory
-    let downloads_dir = dirs::download_dir()
-        .ok_or_else(|| "Failed to get Downloads directory".to_string())?;
-
-    // Create full path
-    let mut file_path = downloads_dir.join(file_name);
-
-    // If file exists, add number suffix
-    let mut counter = 1;
-    while file_path.exists() {
-        let path_buf = PathBuf::from(file_name);
-        let stem = path_buf
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("file");
-        let ext = path_buf
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("");
-
-        let new_name = if ext.is_empty() {
-            format!("{} ({})", stem, counter)
-        } else {
-            format!("{} ({}).{}", stem, counter, ext)
-        };
+    use crate::file_transfer::{load_receive_policy, resolve_receive_path};
 
-        file_path = downloads_dir.join(new_name);
-        counter += 1;
-    }
+    let policy = load_receive_policy();
+    let receive_root = policy.root_dir()?;
 
-    // Decode base64 and write file
+    // Decode base64 and check quota before touching the filesystem
     let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, file_data)
         .map_err(|e| format!("Failed to decode base64: {}", e))?;
+    policy.check_quota(bytes.len() as u64, bytes.len() as u64)?;
+
+    // Resolve destination (rejects traversal, applies collision policy)
+    let file_path = resolve_receive_path(&receive_root, file_name, policy.on_collision)?
+        .ok_or_else(|| format!("Skipped, file already exists: {}", file_name))?;
 
     tokio::fs::write(&file_path, bytes)
         .await
@@ -1275,6 +1384,68 @@ async fn save_received_file(file_name: &str, file_data: &str) -> Result<String,
     Ok(file_path.to_string_lossy().to_string())
 }
 
+/// Download a published document into the configured folder
+async fn download_published_document(
+    config: &DocumentDownloadConfig,
+    name: &str,
+    size: u64,
+    download_url: &str,
+) -> Result<String, String> {
+    use crate::file_transfer::{load_receive_policy, resolve_receive_path, CollisionPolicy};
+    use tokio::io::AsyncWriteExt;
+
+    let policy = load_receive_policy();
+    policy.check_quota(size, size)?;
+
+    let target_dir = config.target_dir()?;
+    tokio::fs::create_dir_all(&target_dir)
+        .await
+        .map_err(|e| format!("Failed to create directory: {}", e))?;
+    let file_path = resolve_receive_path(&target_dir, name, CollisionPolicy::Rename)?
+        .ok_or_else(|| format!("Skipped, file already exists: {}", name))?;
+
+    let mut response = reqwest::get(download_url)
+        .await
+        .map_err(|e| format!("Failed to download: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP error: {}", response.status()));
+    }
+
+    let mut file = tokio::fs::File::create(&file_path)
+        .await
+        .map_err(|e| format!("Failed to create file: {}", e))?;
+    let mut written: u64 = 0;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?
+    {
+        written += chunk.len() as u64;
+        if written > size {
+            drop(file);
+            let _ = tokio::fs::remove_file(&file_path).await;
+            return Err(format!("Server sent more than the announced {} bytes", size));
+        }
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+/// Wrap a message for the frame channel, which forwards 0xFF-prefixed frames as text
+fn status_frame(msg: &StudentMessage) -> Vec<u8> {
+    let json = serde_json::to_string(msg).unwrap_or_default();
+    let mut frame = Vec::with_capacity(1 + json.len());
+    frame.push(0xFF);
+    frame.extend_from_slice(json.as_bytes());
+    frame
+}
+
 /// Handle mouse input event from teacher
 fn handle_mouse_input(event: &MouseInputEvent) -> Result<(), String> {
     // Get all monitors
@@ -1979,6 +2150,52 @@ mod tests {
         assert!(!version.is_empty());
     }
 
+    #[test]
+    fn test_document_published_roundtrip() {
+        let json = r#"{"type":"document_published","document_id":"doc-1","name":"bai1.pdf","size":42,"download_url":"http://10.0.0.1:8765/download/doc-1"}"#;
+        match serde_json::from_str::<TeacherMessage>(json).unwrap() {
+            TeacherMessage::DocumentPublished { document_id, size, .. } => {
+                assert_eq!(document_id, "doc-1");
+                assert_eq!(size, 42);
+            }
+            _ => panic!("Expected DocumentPublished message"),
+        }
+
+        let frame = status_frame(&StudentMessage::DocumentDownloaded {
+            document_id: "doc-1".to_string(),
+            success: true,
+            message: "/tmp/bai1.pdf".to_string(),
+        });
+        assert_eq!(frame[0], 0xFF);
+        let text = String::from_utf8(frame[1..].to_vec()).unwrap();
+        assert!(text.contains("\"type\":\"document_downloaded\""));
+    }
+
+    #[test]
+    fn test_agent_notifications_are_drained() {
+        let state = AgentState::new();
+        state.push_notification("Tài liệu mới", "bai1.pdf");
+        state.push_notification("Tài liệu mới", "bai2.pdf");
+
+        let queued = state.take_notifications();
+        assert_eq!(queued.len(), 2);
+        assert_eq!(queued[1].1, "bai2.pdf");
+        assert!(state.take_notifications().is_empty());
+    }
+
+    #[test]
+    fn test_document_download_config_defaults() {
+        let config: DocumentDownloadConfig = serde_json::from_str("{}").unwrap();
+        assert!(!config.auto_download);
+        assert!(config.download_dir.is_none());
+
+        let config = DocumentDownloadConfig {
+            auto_download: true,
+            download_dir: Some("/tmp/tai-lieu".to_string()),
+        };
+        assert_eq!(config.target_dir().unwrap(), std::path::PathBuf::from("/tmp/tai-lieu"));
+    }
+
     #[test]
     fn test_version_handshake_response_serialization() {
         let msg = TeacherMessage::VersionHandshakeResponse {
@@ -195,6 +195,11 @@ pub async fn monitor_agent_status<R: Runtime>(
             last_status = status_text.to_string();
         }
 
+        // Notifications queued by the agent (e.g. newly published documents)
+        for (title, body) in agent_state.take_notifications() {
+            let _ = show_tray_notification(&app, &title, &body);
+        }
+
         sleep(Duration::from_secs(1)).await;
     }
 }
@@ -153,6 +153,14 @@ pub enum StudentMessage {
     /// Student reports UDP failed, will use WebSocket
     #[serde(rename = "udp_fallback")]
     UdpFallback,
+
+    /// Result of auto-downloading a published document
+    #[serde(rename = "document_downloaded")]
+    DocumentDownloaded {
+        document_id: String,
+        success: bool,
+        message: String,
+    },
 }
 
 /// Mouse button type
@@ -273,6 +281,15 @@ pub enum TeacherMessage {
     UdpOffer {
         udp_port: u16,
     },
+
+    /// Announce a newly published document on the document server
+    #[serde(rename = "document_published")]
+    DocumentPublished {
+        document_id: String,
+        name: String,
+        size: u64,
+        download_url: String,
+    },
 }
 
 /// Command to send to a connection handler
@@ -1300,6 +1317,24 @@ async fn handle_student_message(
                 protos.insert(id.to_string(), "websocket".to_string());
             }
         }
+        StudentMessage::DocumentDownloaded { document_id, success, message } => {
+            log::info!(
+                "[TeacherConnector] Document {} downloaded by {}: {} - {}",
+                document_id,
+                id,
+                success,
+                message
+            );
+
+            if let Some(ref app) = app_handle {
+                let _ = app.emit("student-document-downloaded", serde_json::json!({
+                    "studentId": id,
+                    "documentId": document_id,
+                    "success": success,
+                    "message": message,
+                }));
+            }
+        }
     }
 
     Ok(())
@@ -1664,6 +1699,53 @@ pub fn broadcast_update_required(
     })
 }
 
+/// Broadcast document_published to all connected students
+pub fn broadcast_document_published(
+    state: &ConnectorState,
+    document_id: String,
+    name: String,
+    size: u64,
+    download_url: String,
+) -> Result<BroadcastResult, String> {
+    let senders = state.command_senders.lock().map_err(|e| e.to_string())?;
+    let connections = state.connections.lock().map_err(|e| e.to_string())?;
+
+    let msg = TeacherMessage::DocumentPublished {
+        document_id,
+        name: name.clone(),
+        size,
+        download_url,
+    };
+
+    let mut sent_count = 0;
+    let mut failed_ids = Vec::new();
+    for id in connections.keys() {
+        let sent = senders
+            .get(id)
+            .map(|sender| sender.try_send(ConnectionCommand::SendTeacherMessage(msg.clone())).is_ok())
+            .unwrap_or(false);
+        if sent {
+            sent_count += 1;
+        } else {
+            log::warn!("[TeacherConnector] Failed to send document_published to {}", id);
+            failed_ids.push(id.clone());
+        }
+    }
+
+    log::info!(
+        "[TeacherConnector] Announced document {} to {}/{} students",
+        name,
+        sent_count,
+        connections.len()
+    );
+
+    Ok(BroadcastResult {
+        total_students: connections.len(),
+        sent_count,
+        failed_ids,
+    })
+}
+
 /// Send update_required to a specific student
 /// Requirements: 14.1, 14.2
 ///
@@ -1879,6 +1961,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_broadcast_document_published() {
+        let state = ConnectorState::new();
+
+        for id in ["student1", "student2"] {
+            let conn = StudentConnection {
+                id: id.to_string(),
+                ip: "192.168.1.1".to_string(),
+                port: 3017,
+                name: None,
+                status: ConnectionStatus::Connected,
+                current_version: None,
+                machine_name: None,
+                update_status: None,
+            };
+            state.connections.lock().unwrap().insert(id.to_string(), conn);
+        }
+
+        // Only student1 has a live connection handler
+        let (tx, mut rx) = mpsc::channel(4);
+        state.command_senders.lock().unwrap().insert("student1".to_string(), tx);
+
+        let result = broadcast_document_published(
+            &state,
+            "doc-1".to_string(),
+            "bai1.pdf".to_string(),
+            42,
+            "http://192.168.1.100:8765/download/doc-1".to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(result.total_students, 2);
+        assert_eq!(result.sent_count, 1);
+        assert_eq!(result.failed_ids, vec!["student2".to_string()]);
+        match rx.try_recv().unwrap() {
+            ConnectionCommand::SendTeacherMessage(TeacherMessage::DocumentPublished { document_id, .. }) => {
+                assert_eq!(document_id, "doc-1");
+            }
+            other => panic!("Unexpected command: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_acknowledgment_tracking() {
         let state = ConnectorState::new();
@@ -0,0 +1,324 @@
+//! Streaming ZIP writer
+//!
+//! Writes an uncompressed (stored) ZIP archive entry by entry to any
+//! `AsyncWrite`, so a selection of documents can be sent over HTTP without
+//! building the archive on disk first. Teaching materials (PDF, Office files,
+//! video) are already compressed, so storing them costs little and keeps the
+//! archive size known before the first byte is sent.
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIGNATURE: u32 = 0x0605_4b50;
+
+const LOCAL_HEADER_LEN: u64 = 30;
+const DATA_DESCRIPTOR_LEN: u64 = 16;
+const CENTRAL_HEADER_LEN: u64 = 46;
+const END_OF_CENTRAL_DIR_LEN: u64 = 22;
+
+/// Version 2.0: data descriptors
+const VERSION: u16 = 20;
+/// Bit 3: CRC and sizes follow the data; bit 11: names are UTF-8
+const FLAGS: u16 = (1 << 3) | (1 << 11);
+const METHOD_STORED: u16 = 0;
+
+/// A file to be added to the archive
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    /// Path inside the archive, `/` separated
+    pub name: String,
+    /// Exact number of bytes the reader will produce
+    pub size: u64,
+    /// Modification time (Unix seconds)
+    pub modified: u64,
+}
+
+/// Total archive size for `entries`, or an error if it needs ZIP64
+pub fn archive_size(entries: &[ZipEntry]) -> Result<u64, String> {
+    if entries.len() >= u16::MAX as usize {
+        return Err(format!(
+            "Too many files for one archive ({})",
+            entries.len()
+        ));
+    }
+
+    let mut size = END_OF_CENTRAL_DIR_LEN;
+    for entry in entries {
+        let name_len = entry.name.len() as u64;
+        size += LOCAL_HEADER_LEN + name_len + entry.size + DATA_DESCRIPTOR_LEN;
+        size += CENTRAL_HEADER_LEN + name_len;
+    }
+
+    if size > u32::MAX as u64 {
+        return Err("Archive would exceed 4 GB".to_string());
+    }
+    Ok(size)
+}
+
+/// Central directory record kept until `finish`
+struct CentralRecord {
+    name: String,
+    crc: u32,
+    size: u32,
+    dos_time: u16,
+    dos_date: u16,
+    offset: u32,
+}
+
+/// Writes stored ZIP entries to `writer` as they are read
+pub struct ZipStreamWriter<W> {
+    writer: W,
+    offset: u64,
+    records: Vec<CentralRecord>,
+}
+
+impl<W: AsyncWrite + Unpin> ZipStreamWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            offset: 0,
+            records: Vec::new(),
+        }
+    }
+
+    /// Copy exactly `entry.size` bytes from `reader` into a new entry
+    pub async fn add_entry<R: AsyncRead + Unpin>(
+        &mut self,
+        entry: &ZipEntry,
+        mut reader: R,
+    ) -> Result<(), String> {
+        let name_len = u16::try_from(entry.name.len())
+            .map_err(|_| format!("File name too long: {}", entry.name))?;
+        let size = u32::try_from(entry.size)
+            .map_err(|_| format!("File too large for ZIP: {}", entry.name))?;
+        let offset = u32::try_from(self.offset).map_err(|_| "Archive exceeds 4 GB".to_string())?;
+        let (dos_time, dos_date) = dos_date_time(entry.modified);
+
+        // CRC and sizes are zero here and follow the data in the descriptor
+        let mut header = Vec::with_capacity(LOCAL_HEADER_LEN as usize + entry.name.len());
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut header, VERSION);
+        put_u16(&mut header, FLAGS);
+        put_u16(&mut header, METHOD_STORED);
+        put_u16(&mut header, dos_time);
+        put_u16(&mut header, dos_date);
+        put_u32(&mut header, 0);
+        put_u32(&mut header, 0);
+        put_u32(&mut header, 0);
+        put_u16(&mut header, name_len);
+        put_u16(&mut header, 0);
+        header.extend_from_slice(entry.name.as_bytes());
+        self.write(&header).await?;
+
+        let mut hasher = crc32fast::Hasher::new();
+        let mut remaining = entry.size;
+        let mut buffer = vec![0u8; 64 * 1024];
+        while remaining > 0 {
+            let want = remaining.min(buffer.len() as u64) as usize;
+            let n = reader
+                .read(&mut buffer[..want])
+                .await
+                .map_err(|e| format!("Failed to read {}: {}", entry.name, e))?;
+            if n == 0 {
+                return Err(format!("{} is shorter than expected", entry.name));
+            }
+            hasher.update(&buffer[..n]);
+            self.write(&buffer[..n]).await?;
+            remaining -= n as u64;
+        }
+        let crc = hasher.finalize();
+
+        let mut descriptor = Vec::with_capacity(DATA_DESCRIPTOR_LEN as usize);
+        put_u32(&mut descriptor, DATA_DESCRIPTOR_SIGNATURE);
+        put_u32(&mut descriptor, crc);
+        put_u32(&mut descriptor, size);
+        put_u32(&mut descriptor, size);
+        self.write(&descriptor).await?;
+
+        self.records.push(CentralRecord {
+            name: entry.name.clone(),
+            crc,
+            size,
+            dos_time,
+            dos_date,
+            offset,
+        });
+        Ok(())
+    }
+
+    /// Write the central directory and return the underlying writer
+    pub async fn finish(mut self) -> Result<W, String> {
+        let directory_offset =
+            u32::try_from(self.offset).map_err(|_| "Archive exceeds 4 GB".to_string())?;
+
+        let mut directory = Vec::new();
+        for record in &self.records {
+            put_u32(&mut directory, CENTRAL_HEADER_SIGNATURE);
+            put_u16(&mut directory, VERSION);
+            put_u16(&mut directory, VERSION);
+            put_u16(&mut directory, FLAGS);
+            put_u16(&mut directory, METHOD_STORED);
+            put_u16(&mut directory, record.dos_time);
+            put_u16(&mut directory, record.dos_date);
+            put_u32(&mut directory, record.crc);
+            put_u32(&mut directory, record.size);
+            put_u32(&mut directory, record.size);
+            put_u16(&mut directory, record.name.len() as u16);
+            put_u16(&mut directory, 0); // extra field length
+            put_u16(&mut directory, 0); // comment length
+            put_u16(&mut directory, 0); // disk number
+            put_u16(&mut directory, 0); // internal attributes
+            put_u32(&mut directory, 0); // external attributes
+            put_u32(&mut directory, record.offset);
+            directory.extend_from_slice(record.name.as_bytes());
+        }
+
+        let count = self.records.len() as u16;
+        let directory_size = directory.len() as u32;
+        put_u32(&mut directory, END_OF_CENTRAL_DIR_SIGNATURE);
+        put_u16(&mut directory, 0);
+        put_u16(&mut directory, 0);
+        put_u16(&mut directory, count);
+        put_u16(&mut directory, count);
+        put_u32(&mut directory, directory_size);
+        put_u32(&mut directory, directory_offset);
+        put_u16(&mut directory, 0);
+        self.write(&directory).await?;
+
+        self.writer
+            .flush()
+            .await
+            .map_err(|e| format!("Failed to write archive: {}", e))?;
+        Ok(self.writer)
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.writer
+            .write_all(data)
+            .await
+            .map_err(|e| format!("Failed to write archive: {}", e))?;
+        self.offset += data.len() as u64;
+        Ok(())
+    }
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// MS-DOS time and date in local time; ZIP cannot store dates before 1980
+fn dos_date_time(unix_secs: u64) -> (u16, u16) {
+    use chrono::{Datelike, Local, TimeZone, Timelike};
+
+    let local = match Local.timestamp_opt(unix_secs as i64, 0).single() {
+        Some(t) if t.year() >= 1980 => t,
+        _ => return (0, (1 << 5) | 1),
+    };
+    let time = (local.hour() << 11) | (local.minute() << 5) | (local.second() / 2);
+    let date = (((local.year() - 1980) as u32).min(127) << 9) | (local.month() << 5) | local.day();
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u16(data: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([data[at], data[at + 1]])
+    }
+
+    fn read_u32(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+    }
+
+    /// Extract (name, contents) pairs by walking the central directory
+    fn read_archive(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let eocd = data.len() - END_OF_CENTRAL_DIR_LEN as usize;
+        assert_eq!(read_u32(data, eocd), END_OF_CENTRAL_DIR_SIGNATURE);
+        let count = read_u16(data, eocd + 10) as usize;
+        let directory_size = read_u32(data, eocd + 12) as usize;
+        let mut at = read_u32(data, eocd + 16) as usize;
+        assert_eq!(at + directory_size, eocd);
+
+        let mut files = Vec::new();
+        for _ in 0..count {
+            assert_eq!(read_u32(data, at), CENTRAL_HEADER_SIGNATURE);
+            let crc = read_u32(data, at + 16);
+            let size = read_u32(data, at + 20) as usize;
+            let name_len = read_u16(data, at + 28) as usize;
+            let offset = read_u32(data, at + 42) as usize;
+            let name = String::from_utf8(data[at + 46..at + 46 + name_len].to_vec()).unwrap();
+
+            assert_eq!(read_u32(data, offset), LOCAL_HEADER_SIGNATURE);
+            let start = offset + LOCAL_HEADER_LEN as usize + read_u16(data, offset + 26) as usize;
+            let contents = data[start..start + size].to_vec();
+            assert_eq!(crc32fast::hash(&contents), crc);
+            assert_eq!(read_u32(data, start + size), DATA_DESCRIPTOR_SIGNATURE);
+            assert_eq!(read_u32(data, start + size + 4), crc);
+
+            files.push((name, contents));
+            at += CENTRAL_HEADER_LEN as usize + name_len;
+        }
+        files
+    }
+
+    #[tokio::test]
+    async fn test_archive_roundtrip() {
+        let binary: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let files = vec![
+            ("Toán/Bài giảng.pdf".to_string(), binary),
+            ("empty.txt".to_string(), Vec::new()),
+            ("notes.txt".to_string(), b"hello".to_vec()),
+        ];
+        let entries: Vec<ZipEntry> = files
+            .iter()
+            .map(|(name, data)| ZipEntry {
+                name: name.clone(),
+                size: data.len() as u64,
+                modified: 1_700_000_000,
+            })
+            .collect();
+
+        let mut zip = ZipStreamWriter::new(Vec::new());
+        for (entry, (_, data)) in entries.iter().zip(&files) {
+            zip.add_entry(entry, data.as_slice()).await.unwrap();
+        }
+        let archive = zip.finish().await.unwrap();
+
+        assert_eq!(archive.len() as u64, archive_size(&entries).unwrap());
+        assert_eq!(read_archive(&archive), files);
+    }
+
+    #[tokio::test]
+    async fn test_short_reader_is_an_error() {
+        let entry = ZipEntry {
+            name: "a.bin".to_string(),
+            size: 10,
+            modified: 0,
+        };
+        let mut zip = ZipStreamWriter::new(Vec::new());
+        assert!(zip.add_entry(&entry, &b"12345"[..]).await.is_err());
+    }
+
+    #[test]
+    fn test_archive_size_limits() {
+        let big = ZipEntry {
+            name: "video.mp4".to_string(),
+            size: 5 * 1024 * 1024 * 1024,
+            modified: 0,
+        };
+        assert!(archive_size(&[big]).is_err());
+        assert_eq!(archive_size(&[]).unwrap(), END_OF_CENTRAL_DIR_LEN);
+    }
+
+    #[test]
+    fn test_dos_date_time_before_1980() {
+        assert_eq!(dos_date_time(0), (0, (1 << 5) | 1));
+    }
+}
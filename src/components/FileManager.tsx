@@ -26,6 +26,7 @@ interface StudentConnection {
 
 type TransferStatus = 
   | 'Pending'
+  | 'Queued'
   | 'Connecting'
   | 'Transferring'
   | 'Completed'
@@ -261,6 +262,7 @@ export function FileManager({ student, onClose }: FileManagerProps) {
     if (typeof status === 'string') {
       switch (status) {
         case 'Pending': return 'Đang chờ...';
+        case 'Queued': return 'Trong hàng đợi...';
         case 'Connecting': return 'Kết nối...';
         case 'Transferring': return 'Đang truyền...';
         case 'Completed': return '✅ Xong';
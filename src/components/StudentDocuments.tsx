@@ -123,6 +123,20 @@ const StudentDocuments: React.FC<StudentDocumentsProps> = ({ serverUrl }) => {
     }
   };
 
+  // Download every listed document as one ZIP archive
+  const handleDownloadAll = async () => {
+    try {
+      const filePath = await invoke<string>('download_documents_zip', {
+        serverUrl,
+        ids: filteredDocs.map(doc => doc.id),
+      });
+      alert(`Đã tải xuống: ${filePath}`);
+    } catch (err) {
+      console.error('Download failed:', err);
+      alert('Tải xuống thất bại: ' + err);
+    }
+  };
+
   if (!serverUrl) {
     return (
       <div className="flex flex-col items-center justify-center p-12 text-slate-400">
@@ -146,14 +160,24 @@ const StudentDocuments: React.FC<StudentDocumentsProps> = ({ serverUrl }) => {
             <p className="text-xs text-slate-500">{documents.length} tài liệu</p>
           </div>
         </div>
-        <button
-          onClick={loadDocuments}
-          disabled={isLoading}
-          className="p-2 hover:bg-slate-100 rounded-xl transition"
-          title="Làm mới"
-        >
-          <RefreshCw className={`w-5 h-5 text-slate-400 ${isLoading ? 'animate-spin' : ''}`} />
-        </button>
+        <div className="flex items-center gap-1">
+          <button
+            onClick={handleDownloadAll}
+            disabled={filteredDocs.length === 0}
+            className="p-2 hover:bg-slate-100 rounded-xl transition disabled:opacity-40"
+            title="Tải tất cả (.zip)"
+          >
+            <Download className="w-5 h-5 text-slate-400" />
+          </button>
+          <button
+            onClick={loadDocuments}
+            disabled={isLoading}
+            className="p-2 hover:bg-slate-100 rounded-xl transition"
+            title="Làm mới"
+          >
+            <RefreshCw className={`w-5 h-5 text-slate-400 ${isLoading ? 'animate-spin' : ''}`} />
+          </button>
+        </div>
       </div>
 
       {/* Search */}
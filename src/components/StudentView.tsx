@@ -91,7 +91,8 @@ export function StudentView({ serverUrl, roomId, name, onDisconnect }: StudentVi
     }
   }, [audioMode, isServerRunning, stopUdpAudioServer, disablePushToTalk]);
 
-  const isViewingStream = connectionState === 'connected' && remoteStream !== null;
+  // Media keeps playing while signaling reconnects
+  const isViewingStream = (connectionState === 'connected' || connectionState === 'reconnecting') && remoteStream !== null;
   const videoSectionRef = useRef<HTMLDivElement>(null);
 
   // Auto fullscreen when stream starts
@@ -186,6 +187,7 @@ export function StudentView({ serverUrl, roomId, name, onDisconnect }: StudentVi
               <span className={`connection-status ${connectionState}`}>
                 {connectionState === 'connected' ? '🟢 Đã kết nối' : 
                  connectionState === 'connecting' ? '🟡 Đang kết nối...' : 
+                 connectionState === 'reconnecting' ? '🟡 Đang kết nối lại...' : 
                  '🔴 Chưa kết nối'}
               </span>
             </div>
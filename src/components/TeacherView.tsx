@@ -62,6 +62,7 @@ export function TeacherView({ serverUrl, roomId, name, onDisconnect }: TeacherVi
           <span className={`connection-status ${connectionState}`}>
             {connectionState === 'connected' ? '🟢 Đã kết nối' : 
              connectionState === 'connecting' ? '🟡 Đang kết nối...' : 
+             connectionState === 'reconnecting' ? '🟡 Đang kết nối lại...' : 
              '🔴 Chưa kết nối'}
           </span>
           <span className="student-count">👥 {studentCount} học sinh</span>
@@ -1,5 +1,6 @@
 import { useState, useCallback, useRef, useEffect } from 'react';
-import { MediasoupClient, ConnectionState, MediaKind } from '../lib/mediasoup-client';
+import { invoke } from '@tauri-apps/api/core';
+import { MediasoupClient, ConnectionState, MediaKind, JoinAuth, ChatMessage } from '../lib/mediasoup-client';
 
 interface Peer {
   id: string;
@@ -80,7 +81,8 @@ export function useMediasoup() {
   const [hasScreenAudio, setHasScreenAudio] = useState(false);
   
   // Chat messages callback
-  const chatMessageCallbackRef = useRef<((message: { senderId: string; senderName: string; content: string; timestamp: string; isTeacher: boolean }) => void) | null>(null);
+  const chatMessageCallbackRef = useRef<((message: ChatMessage) => void) | null>(null);
+  const chatHistoryCallbackRef = useRef<((messages: ChatMessage[]) => void) | null>(null);
 
   const clientRef = useRef<MediasoupClient | null>(null);
 
@@ -113,10 +115,19 @@ export function useMediasoup() {
     serverUrl: string,
     roomId: string,
     name: string,
-    isTeacher: boolean
+    isTeacher: boolean,
+    auth: JoinAuth = {}
   ) => {
     const peerId = crypto.randomUUID();
 
+    // The server only grants the teacher role with a token signed by the teacher key
+    if (isTeacher && !auth.token) {
+      auth = {
+        ...auth,
+        token: await invoke<string>('crypto_create_join_token', { roomId, role: 'teacher', peerId }),
+      };
+    }
+
     const client = new MediasoupClient({
       onConnectionStateChange: setConnectionState,
       onError: setError,
@@ -237,20 +248,34 @@ export function useMediasoup() {
           chatMessageCallbackRef.current(message);
         }
       },
+      onChatHistory: (messages) => {
+        chatHistoryCallbackRef.current?.(messages);
+      },
+      onRoomReset: async (reason) => {
+        // The old room's producers and consumers are gone; the teacher has to share again
+        setError(reason);
+        stopScreenShare();
+        micProducerIdRef.current = null;
+        setIsMicActive(false);
+        setMicStream(null);
+        setRemoteStream(null);
+        studentAudioTracksRef.current.clear();
+        setStudentAudioStream(null);
+        await consumeExisting();
+      },
     });
 
     clientRef.current = client;
 
-    try {
-      await client.connect(serverUrl, roomId, peerId, name, isTeacher);
-
+    // Media setup after joining; runs again when the server resets the room
+    const consumeExisting = async () => {
       // Students: create recv transport and consume existing producers
       if (!isTeacher) {
         try {
           console.log('[Student] Attempting to consume all existing producers...');
           const stream = await client.consumeAll();
           console.log('[Student] ConsumeAll result:', stream, 'tracks:', stream?.getTracks().length);
-          
+        
           // Ensure remoteStream is set even if consumeAll returns empty stream
           if (stream && stream.getTracks().length > 0) {
             setRemoteStream(stream);
@@ -258,7 +283,7 @@ export function useMediasoup() {
           } else {
             console.log('[Student] No producers yet, waiting for teacher to share...');
           }
-          
+        
           // Initialize microphone for push-to-talk
           setTimeout(() => {
             initializeStudentMicrophone();
@@ -280,6 +305,11 @@ export function useMediasoup() {
           console.log('[Teacher] No student audio producers yet');
         }
       }
+    };
+
+    try {
+      await client.connect(serverUrl, roomId, peerId, name, isTeacher, auth);
+      await consumeExisting();
     } catch (err) {
       console.error('Connection error:', err);
       setError(err instanceof Error ? err.message : 'Connection failed');
@@ -632,19 +662,25 @@ export function useMediasoup() {
   }, [isScreenAudioEnabled]);
 
   // Send chat message
-  const sendChatMessage = useCallback((content: string) => {
+  // `to` = peer id for a private message (teacher <-> student)
+  const sendChatMessage = useCallback((content: string, to?: string) => {
     if (!clientRef.current) {
       console.warn('[Chat] Cannot send message: not connected');
       return;
     }
-    clientRef.current.sendChatMessage(content);
+    clientRef.current.sendChatMessage(content, to);
   }, []);
 
   // Set chat message callback
-  const onChatMessage = useCallback((callback: (message: { senderId: string; senderName: string; content: string; timestamp: string; isTeacher: boolean }) => void) => {
+  const onChatMessage = useCallback((callback: (message: ChatMessage) => void) => {
     chatMessageCallbackRef.current = callback;
   }, []);
 
+  // Set chat history callback (on join and after a resumed connection)
+  const onChatHistory = useCallback((callback: (messages: ChatMessage[]) => void) => {
+    chatHistoryCallbackRef.current = callback;
+  }, []);
+
   return {
     connectionState,
     error,
@@ -669,5 +705,6 @@ export function useMediasoup() {
     toggleScreenAudio,
     sendChatMessage,
     onChatMessage,
+    onChatHistory,
   };
 }
@@ -3,12 +3,35 @@ import { Device, types, detectDevice } from 'mediasoup-client';
 type Transport = types.Transport;
 type Producer = types.Producer;
 type Consumer = types.Consumer;
+type DataProducer = types.DataProducer;
+type DataConsumer = types.DataConsumer;
 type RtpCapabilities = types.RtpCapabilities;
 type DtlsParameters = types.DtlsParameters;
 
-export type ConnectionState = 'disconnected' | 'connecting' | 'connected' | 'error';
+/** 'reconnecting': signaling dropped, media keeps flowing while the session is resumed */
+export type ConnectionState = 'disconnected' | 'connecting' | 'connected' | 'reconnecting' | 'error';
 export type MediaKind = 'audio' | 'video';
 
+/** Credentials checked by the server before joining a room */
+export interface JoinAuth {
+  /** Join token signed with the teacher key (required for the teacher role) */
+  token?: string;
+  /** Room password for students without a token */
+  password?: string;
+}
+
+/** Chat message relayed by the SFU; 'Direct' ones go between the teacher and one student */
+export interface ChatMessage {
+  id: number;
+  senderId: string;
+  senderName: string;
+  content: string;
+  timestamp: string;
+  isTeacher: boolean;
+  messageType: 'All' | 'Direct';
+  receiverId: string | null;
+}
+
 export interface MediasoupClientEvents {
   onConnectionStateChange: (state: ConnectionState) => void;
   onNewProducer: (producerId: string, kind: MediaKind, peerId?: string) => void;
@@ -17,7 +40,37 @@ export interface MediasoupClientEvents {
   onPeerLeft: (peerId: string, wasTeacher: boolean) => void;
   onError: (error: string) => void;
   onStreamReady: (stream: MediaStream) => void;
-  onChatMessage: (message: { senderId: string; senderName: string; content: string; timestamp: string; isTeacher: boolean }) => void;
+  onChatMessage: (message: ChatMessage) => void;
+  /** Messages sent before this peer joined (or while it was reconnecting), oldest first */
+  onChatHistory: (messages: ChatMessage[]) => void;
+  onHandRaised: (peerId: string, name: string, raised: boolean) => void;
+  onPermissionsChanged: (peerId: string, permissions: { audio: boolean; video: boolean }) => void;
+  onKicked: (reason: string | null) => void;
+  onPresentRequested: (peerId: string, name: string, requested: boolean) => void;
+  onPresenterChanged: (peerId: string | null, name: string | null) => void;
+  onConsumerLayersChanged: (consumerId: string, spatialLayer: number, temporalLayer: number | null, automatic: boolean) => void;
+  onRecordingChanged: (recording: boolean, info: { path: string; format?: string; durationSecs?: number }) => void;
+  /** Teacher only: the student heard most right now */
+  onActiveSpeaker: (peerId: string, producerId: string) => void;
+  /** Teacher only: students making sound, loudest first (dBvo, -127..0); empty when the room is silent */
+  onAudioLevels: (levels: { peerId: string; producerId: string; volume: number }[]) => void;
+  /** A student's producer was paused by the server (reason 'silence') or resumed */
+  onProducerPaused: (producerId: string, peerId: string, paused: boolean, reason?: string) => void;
+  /** The server lost this room's media worker, or our session expired while offline; we already rejoined, so produce/consume again */
+  onRoomReset: (reason: string) => void;
+  /** The teacher opened a data channel; call consumeData to receive it */
+  onNewDataProducer: (dataProducerId: string, label: string, peerId: string) => void;
+  onDataProducerClosed: (dataProducerId: string) => void;
+  /** Message on a consumed data channel, e.g. a pointer position or whiteboard stroke */
+  onDataMessage: (label: string, data: string | ArrayBuffer, dataProducerId: string) => void;
+}
+
+/** Delivery of a data channel; unordered with no retransmits suits a laser pointer */
+export interface DataChannelOptions {
+  ordered?: boolean;
+  maxRetransmits?: number;
+  maxPacketLifeTime?: number;
+  protocol?: string;
 }
 
 export class MediasoupClient {
@@ -27,6 +80,8 @@ export class MediasoupClient {
   private recvTransport: Transport | null = null;
   private producers: Map<string, Producer> = new Map();
   private consumers: Map<string, Consumer> = new Map();
+  private dataProducers: Map<string, DataProducer> = new Map();
+  private dataConsumers: Map<string, DataConsumer> = new Map();
   private events: Partial<MediasoupClientEvents> = {};
   private pendingRequests: Map<string, { resolve: (value: any) => void; reject: (reason: any) => void }> = new Map();
 
@@ -34,15 +89,31 @@ export class MediasoupClient {
   public peerId: string = '';
   public isTeacher: boolean = false;
   public rtpCapabilities: RtpCapabilities | null = null;
+  /** Highest simulcast layer to receive (0 = lowest); null lets the server send the best it can */
+  public preferredSpatialLayer: number | null = null;
+  /** How long to keep trying to resume after the socket drops; the server's grace period */
+  public reconnectWindowMs = 30000;
+
+  private serverUrl = '';
+  private peerName = '';
+  private joinAuth: JoinAuth = {};
+  private resumeToken: string | null = null;
+  private resuming = false;
+  private closing = false;
 
   constructor(events: Partial<MediasoupClientEvents>) {
     this.events = events;
   }
 
-  async connect(serverUrl: string, roomId: string, peerId: string, name: string, isTeacher: boolean): Promise<void> {
+  async connect(serverUrl: string, roomId: string, peerId: string, name: string, isTeacher: boolean, auth: JoinAuth = {}): Promise<void> {
     this.roomId = roomId;
     this.peerId = peerId;
     this.isTeacher = isTeacher;
+    this.serverUrl = serverUrl;
+    this.peerName = name;
+    this.joinAuth = auth;
+    this.resumeToken = null;
+    this.closing = false;
 
     // Check device support first
     // Check device support first
@@ -64,10 +135,12 @@ export class MediasoupClient {
       this.ws.onopen = async () => {
         try {
           // Join room - response contains rtpCapabilities
-          const joinResponse = await this.sendRequest('join', { roomId, peerId, name, isTeacher });
+          const joinResponse = await this.sendRequest('join', { roomId, peerId, name, isTeacher, ...auth });
 
           // Store rtpCapabilities from response
           this.rtpCapabilities = joinResponse.rtpCapabilities;
+          this.resumeToken = joinResponse.resumeToken ?? null;
+          this.events.onChatHistory?.(joinResponse.chatHistory ?? []);
 
           if (!this.rtpCapabilities) {
             throw new Error('Server không trả về rtpCapabilities');
@@ -97,13 +170,146 @@ export class MediasoupClient {
         reject(new Error('WebSocket error'));
       };
 
+      const ws = this.ws;
       this.ws.onclose = () => {
-        this.events.onConnectionStateChange?.('disconnected');
-        this.cleanup();
+        if (this.ws === ws) this.handleClose();
       };
     });
   }
 
+  private handleClose(): void {
+    if (this.resuming) return;
+    if (!this.closing && this.resumeToken && this.device) {
+      void this.resume();
+      return;
+    }
+    this.events.onConnectionStateChange?.('disconnected');
+    this.cleanup();
+  }
+
+  /** Reopen the socket and take our peer back; transports keep their media meanwhile */
+  private async resume(): Promise<void> {
+    this.resuming = true;
+    this.events.onConnectionStateChange?.('reconnecting');
+    const deadline = Date.now() + this.reconnectWindowMs;
+
+    try {
+      while (!this.closing && Date.now() < deadline) {
+        await new Promise((resolve) => setTimeout(resolve, 2000));
+        try {
+          await this.openSocket();
+          // Carry the original auth too: past the grace period the server joins us as a new peer
+          const joined = await this.sendRequest('join', {
+            roomId: this.roomId,
+            peerId: this.peerId,
+            name: this.peerName,
+            isTeacher: this.isTeacher,
+            ...this.joinAuth,
+            resumeToken: this.resumeToken,
+          });
+          if (!joined.resumed) {
+            // Our old transports are gone on the server; start over like after a room reset
+            this.dropMedia();
+            this.resumeToken = joined.resumeToken ?? null;
+            this.rtpCapabilities = joined.rtpCapabilities;
+            this.events.onChatHistory?.(joined.chatHistory ?? []);
+            this.events.onConnectionStateChange?.('connected');
+            this.events.onRoomReset?.('Phiên kết nối đã hết hạn, đã tham gia lại phòng');
+            return;
+          }
+
+          this.resumeToken = joined.resumeToken ?? this.resumeToken;
+          console.log('[MediasoupClient] Session resumed');
+          this.events.onChatHistory?.(joined.chatHistory ?? []);
+          this.events.onConnectionStateChange?.('connected');
+          await this.syncProducers();
+          return;
+        } catch (error) {
+          console.warn('[MediasoupClient] Resume attempt failed:', error);
+        }
+      }
+    } finally {
+      this.resuming = false;
+    }
+
+    this.resumeToken = null;
+    this.ws?.close();
+    this.ws = null;
+    this.events.onConnectionStateChange?.('disconnected');
+    this.cleanup();
+  }
+
+  private openSocket(): Promise<void> {
+    return new Promise((resolve, reject) => {
+      const ws = new WebSocket(this.serverUrl);
+      this.ws = ws;
+      ws.onopen = () => resolve();
+      ws.onerror = () => reject(new Error('WebSocket error'));
+      ws.onmessage = (event) => this.handleMessage(JSON.parse(event.data));
+      ws.onclose = () => {
+        if (this.ws === ws) this.handleClose();
+      };
+    });
+  }
+
+  /** Catch up on producers opened or closed while the socket was down */
+  private async syncProducers(): Promise<void> {
+    const producers: { producerId: string; kind: MediaKind; peerId?: string }[] = await this.sendRequest('getProducers', {});
+    const live = new Set(producers.map((p) => p.producerId));
+    const consumed = new Set(Array.from(this.consumers.values(), (c) => c.producerId));
+
+    for (const [consumerId, consumer] of this.consumers) {
+      if (!live.has(consumer.producerId)) {
+        consumer.close();
+        this.consumers.delete(consumerId);
+        this.events.onProducerClosed?.(consumer.producerId);
+      }
+    }
+    for (const producer of producers) {
+      if (!consumed.has(producer.producerId) && !this.producers.has(producer.producerId)) {
+        this.events.onNewProducer?.(producer.producerId, producer.kind, producer.peerId);
+      }
+    }
+  }
+
+  /** Close producers, consumers and transports whose server side no longer exists */
+  private dropMedia(): void {
+    this.stopProducing();
+    this.closeAllConsumers();
+    this.closeDataChannels();
+    this.sendTransport?.close();
+    this.recvTransport?.close();
+    this.sendTransport = null;
+    this.recvTransport = null;
+  }
+
+  /** Drop media from a room the server had to reset and join it again on the same socket */
+  private async rejoin(reason: string): Promise<void> {
+    console.warn(`[MediasoupClient] Room reset by server: ${reason}`);
+    this.dropMedia();
+    this.events.onConnectionStateChange?.('reconnecting');
+
+    try {
+      const joined = await this.sendRequest('join', {
+        roomId: this.roomId,
+        peerId: this.peerId,
+        name: this.peerName,
+        isTeacher: this.isTeacher,
+        ...this.joinAuth,
+      });
+      this.resumeToken = joined.resumeToken ?? null;
+      this.rtpCapabilities = joined.rtpCapabilities;
+      this.events.onChatHistory?.(joined.chatHistory ?? []);
+      this.events.onConnectionStateChange?.('connected');
+      this.events.onRoomReset?.(reason);
+    } catch (error) {
+      console.error('[MediasoupClient] Rejoin after room reset failed:', error);
+      this.events.onError?.(reason);
+      this.disconnect();
+      this.events.onConnectionStateChange?.('disconnected');
+    }
+  }
+
   private handleMessage(message: { type: string; data?: any }): void {
     const { type, data } = message;
     console.log(`[MediasoupClient] Received message: ${type}`, data);
@@ -134,6 +340,68 @@ export class MediasoupClient {
       case 'chatMessage':
         this.events.onChatMessage?.(data);
         break;
+      case 'producerClosed':
+        for (const [consumerId, consumer] of this.consumers.entries()) {
+          if (consumer.producerId === data.producerId) {
+            consumer.close();
+            this.consumers.delete(consumerId);
+          }
+        }
+        this.events.onProducerClosed?.(data.producerId);
+        break;
+      case 'handRaised':
+        this.events.onHandRaised?.(data.peerId, data.name, data.raised);
+        break;
+      case 'permissionsChanged':
+        this.events.onPermissionsChanged?.(data.peerId, { audio: data.audio, video: data.video });
+        break;
+      case 'kicked':
+        this.events.onKicked?.(data.reason ?? null);
+        break;
+      case 'presentRequested':
+        this.events.onPresentRequested?.(data.peerId, data.name, data.requested);
+        break;
+      case 'presenterChanged':
+        this.events.onPresenterChanged?.(data.peerId ?? null, data.name ?? null);
+        break;
+      case 'recordingStarted':
+        this.events.onRecordingChanged?.(true, { path: data.path, format: data.format });
+        break;
+      case 'recordingStopped':
+        this.events.onRecordingChanged?.(false, { path: data.path, durationSecs: data.durationSecs });
+        break;
+      case 'activeSpeaker':
+        this.events.onActiveSpeaker?.(data.peerId, data.producerId);
+        break;
+      case 'audioLevels':
+        this.events.onAudioLevels?.(data.levels);
+        break;
+      case 'producerPaused':
+        this.producers.get(data.producerId)?.pause();
+        this.events.onProducerPaused?.(data.producerId, data.peerId, true, data.reason);
+        break;
+      case 'producerResumed':
+        this.producers.get(data.producerId)?.resume();
+        this.events.onProducerPaused?.(data.producerId, data.peerId, false);
+        break;
+      case 'roomReset':
+        void this.rejoin(data.reason);
+        break;
+      case 'newDataProducer':
+        this.events.onNewDataProducer?.(data.dataProducerId, data.label, data.peerId);
+        break;
+      case 'dataProducerClosed':
+        for (const [dataConsumerId, dataConsumer] of this.dataConsumers.entries()) {
+          if (dataConsumer.dataProducerId === data.dataProducerId) {
+            dataConsumer.close();
+            this.dataConsumers.delete(dataConsumerId);
+          }
+        }
+        this.events.onDataProducerClosed?.(data.dataProducerId);
+        break;
+      case 'consumerLayersChanged':
+        this.events.onConsumerLayersChanged?.(data.consumerId, data.spatialLayer, data.temporalLayer ?? null, data.automatic);
+        break;
       case 'error':
         console.error(`[MediasoupClient] Server error: ${data.message}`);
         this.events.onError?.(data.message);
@@ -176,6 +444,9 @@ export class MediasoupClient {
       consume: 'consumed',
       resumeConsumer: 'consumerResumed',
       getProducers: 'producers',
+      produceData: 'dataProduced',
+      consumeData: 'dataConsumed',
+      getDataProducers: 'dataProducers',
     };
     return mapping[requestType] || requestType;
   }
@@ -192,6 +463,7 @@ export class MediasoupClient {
       iceParameters: params.iceParameters,
       iceCandidates: params.iceCandidates,
       dtlsParameters: params.dtlsParameters,
+      sctpParameters: params.sctpParameters ?? undefined,
     });
 
     this.sendTransport.on('connect', async ({ dtlsParameters }: { dtlsParameters: DtlsParameters }, callback: () => void, errback: (error: Error) => void) => {
@@ -218,6 +490,17 @@ export class MediasoupClient {
       }
     });
 
+    this.sendTransport.on('producedata', async ({ sctpStreamParameters, label, protocol }: { sctpStreamParameters: any; label?: string; protocol?: string }, callback: (params: { id: string }) => void, errback: (error: Error) => void) => {
+      try {
+        const { dataProducerId } = await this.sendRequest('produceData', { sctpStreamParameters, label, protocol });
+        console.log(`[MediasoupClient] Produced data channel ${label}: ${dataProducerId}`);
+        callback({ id: dataProducerId });
+      } catch (error) {
+        console.error(`[MediasoupClient] Produce data ${label} error:`, error);
+        errback(error as Error);
+      }
+    });
+
     console.log('[MediasoupClient] Send transport created successfully');
   }
 
@@ -231,6 +514,7 @@ export class MediasoupClient {
       iceParameters: params.iceParameters,
       iceCandidates: params.iceCandidates,
       dtlsParameters: params.dtlsParameters,
+      sctpParameters: params.sctpParameters ?? undefined,
     });
 
     this.recvTransport.on('connect', async ({ dtlsParameters }: { dtlsParameters: DtlsParameters }, callback: () => void, errback: (error: Error) => void) => {
@@ -263,11 +547,11 @@ export class MediasoupClient {
       
       const producer = await this.sendTransport!.produce({
         track: videoTrack,
+        // Simulcast 3 layers: máy yếu nhận layer thấp thay vì giải mã cả luồng 5Mbps
         encodings: [
-          {
-            maxBitrate: 5000000, // Max 5Mbps cho LAN
-            maxFramerate: 30
-          },
+          { scaleResolutionDownBy: 4, maxBitrate: 500000, maxFramerate: 15, scalabilityMode: 'L1T3' },
+          { scaleResolutionDownBy: 2, maxBitrate: 1500000, maxFramerate: 30, scalabilityMode: 'L1T3' },
+          { scaleResolutionDownBy: 1, maxBitrate: 5000000, maxFramerate: 30, scalabilityMode: 'L1T3' }, // Max 5Mbps cho LAN
         ],
         codecOptions: {
           videoGoogleStartBitrate: 3000, // 3Mbps start
@@ -411,6 +695,7 @@ export class MediasoupClient {
       const params = await this.sendRequest('consume', {
         producerId,
         rtpCapabilities: this.device.rtpCapabilities,
+        preferredSpatialLayer: this.preferredSpatialLayer ?? undefined,
       });
 
       const consumer = await this.recvTransport.consume({
@@ -432,6 +717,70 @@ export class MediasoupClient {
     }
   }
 
+  /** Teacher only: open a data channel the students receive through consumeData */
+  async produceData(label: string, options: DataChannelOptions = {}): Promise<DataProducer> {
+    if (!this.sendTransport) {
+      await this.createSendTransport();
+    }
+
+    const dataProducer = await this.sendTransport!.produceData({
+      label,
+      protocol: options.protocol,
+      ordered: options.ordered ?? true,
+      maxRetransmits: options.maxRetransmits,
+      maxPacketLifeTime: options.maxPacketLifeTime,
+    });
+    this.dataProducers.set(dataProducer.id, dataProducer);
+    dataProducer.on('transportclose', () => this.dataProducers.delete(dataProducer.id));
+    return dataProducer;
+  }
+
+  closeDataProducer(dataProducerId: string): void {
+    const dataProducer = this.dataProducers.get(dataProducerId);
+    if (!dataProducer) return;
+    dataProducer.close();
+    this.dataProducers.delete(dataProducerId);
+    this.sendEvent('closeDataProducer', { dataProducerId });
+  }
+
+  async consumeData(dataProducerId: string): Promise<DataConsumer | null> {
+    if (!this.recvTransport) {
+      await this.createRecvTransport();
+    }
+
+    try {
+      const params = await this.sendRequest('consumeData', { dataProducerId });
+      const dataConsumer = await this.recvTransport!.consumeData({
+        id: params.dataConsumerId,
+        dataProducerId: params.dataProducerId,
+        sctpStreamParameters: params.sctpStreamParameters,
+        label: params.label,
+        protocol: params.protocol,
+      });
+
+      this.dataConsumers.set(dataConsumer.id, dataConsumer);
+      dataConsumer.on('message', (data: string | ArrayBuffer) => {
+        this.events.onDataMessage?.(dataConsumer.label, data, dataConsumer.dataProducerId);
+      });
+      dataConsumer.on('transportclose', () => this.dataConsumers.delete(dataConsumer.id));
+      return dataConsumer;
+    } catch (error) {
+      console.error('Failed to consume data:', error);
+      return null;
+    }
+  }
+
+  /** Consume the data channels opened before we joined */
+  async consumeAllData(): Promise<void> {
+    const dataProducers: { dataProducerId: string; label: string; peerId: string }[] = await this.sendRequest('getDataProducers', {});
+    const consumed = new Set(Array.from(this.dataConsumers.values(), (c) => c.dataProducerId));
+    for (const { dataProducerId } of dataProducers) {
+      if (!consumed.has(dataProducerId)) {
+        await this.consumeData(dataProducerId);
+      }
+    }
+  }
+
   stopProducing(): void {
     for (const producer of this.producers.values()) {
       producer.close();
@@ -502,9 +851,21 @@ export class MediasoupClient {
     this.consumers.clear();
   }
 
+  private closeDataChannels(): void {
+    for (const dataProducer of this.dataProducers.values()) {
+      dataProducer.close();
+    }
+    for (const dataConsumer of this.dataConsumers.values()) {
+      dataConsumer.close();
+    }
+    this.dataProducers.clear();
+    this.dataConsumers.clear();
+  }
+
   private cleanup(): void {
     this.stopProducing();
     this.closeAllConsumers();
+    this.closeDataChannels();
     this.sendTransport?.close();
     this.recvTransport?.close();
     this.sendTransport = null;
@@ -513,12 +874,72 @@ export class MediasoupClient {
   }
 
   disconnect(): void {
+    this.closing = true;
+    this.resumeToken = null;
     this.cleanup();
     this.ws?.close();
     this.ws = null;
   }
 
-  sendChatMessage(content: string): void {
+  // Moderation: students raise hands, the teacher mutes, gives the floor or removes peers
+  raiseHand(raised = true): void {
+    this.sendEvent('raiseHand', { raised });
+  }
+
+  mutePeer(peerId: string, close = false): void {
+    this.sendEvent('mutePeer', { peerId, close });
+  }
+
+  grantFloor(peerId: string): void {
+    this.sendEvent('grantFloor', { peerId });
+  }
+
+  kickPeer(peerId: string, reason?: string): void {
+    this.sendEvent('kickPeer', { peerId, reason });
+  }
+
+  // Highest layers to receive for a simulcast consumer; the server still drops lower on a poor link
+  setPreferredLayers(consumerId: string, spatialLayer: number, temporalLayer?: number): void {
+    this.sendEvent('setPreferredLayers', { consumerId, spatialLayer, temporalLayer });
+  }
+
+  // Resume one of our own producers, e.g. a microphone the server paused after a long silence
+  resumeProducer(producerId: string): void {
+    this.sendEvent('resumeProducer', { producerId });
+  }
+
+  // Server-side recording of the teacher's screen and audio (teacher only)
+  startRecording(): void {
+    this.sendEvent('startRecording', {});
+  }
+
+  stopRecording(): void {
+    this.sendEvent('stopRecording', {});
+  }
+
+  // Student screen presentation: students ask, the teacher approves or revokes
+  requestPresent(requested = true): void {
+    this.sendEvent('requestPresent', { requested });
+  }
+
+  approvePresent(peerId: string): void {
+    this.sendEvent('approvePresent', { peerId });
+  }
+
+  revokePresent(peerId: string): void {
+    this.sendEvent('revokePresent', { peerId });
+  }
+
+  private sendEvent(type: string, data: unknown): void {
+    if (!this.ws || this.ws.readyState !== WebSocket.OPEN) {
+      console.warn(`[MediasoupClient] Cannot send ${type}: WebSocket not connected`);
+      return;
+    }
+    this.ws.send(JSON.stringify({ type, data }));
+  }
+
+  /** Send to the whole room, or privately to `to` (a peer id) */
+  sendChatMessage(content: string, to?: string): void {
     if (!this.ws || this.ws.readyState !== WebSocket.OPEN) {
       console.warn('[MediasoupClient] Cannot send chat message: WebSocket not connected');
       return;
@@ -529,6 +950,7 @@ export class MediasoupClient {
       data: {
         content,
         timestamp: new Date().toISOString(),
+        to,
       }
     }));
   }
@@ -18,8 +18,16 @@ interface Document {
   uploaded_at: number;
   description?: string;
   category?: string;
+  folder?: string | null;
+  visibility?: DocumentVisibility;
+  available_from?: number | null;
 }
 
+type DocumentVisibility =
+  | { scope: 'public' }
+  | { scope: 'session'; session_id: string }
+  | { scope: 'group'; group: string };
+
 const DocumentManager: React.FC<{ user: User; teacherIp?: string }> = ({ user, teacherIp }) => {
   const [activeFolder, setActiveFolder] = useState('Tất cả tài liệu');
   const [documents, setDocuments] = useState<Document[]>([]);
@@ -8,6 +8,7 @@ import {
 } from 'lucide-react';
 import { UserAccount as UserType, Message, UserRole } from '../types';
 import { useMediasoup } from '../hooks/useMediasoup';
+import type { ChatMessage } from '../lib/mediasoup-client';
 import { VideoPlayer } from '../components/VideoPlayer';
 import { SharingIndicator } from '../components/SharingIndicator';
 
@@ -64,24 +65,29 @@ const OnlineClassroom: React.FC<OnlineClassroomProps> = ({ user, onStartScreenSh
     disablePushToTalk,
     sendChatMessage,
     onChatMessage,
+    onChatHistory,
   } = useMediasoup();
 
   const roomId = 'classroom-main';
 
   // Setup chat message listener
   useEffect(() => {
+    const toMessage = (message: ChatMessage): Message => ({
+      id: `sfu-${message.id}`,
+      senderId: message.senderId,
+      senderName: message.messageType === 'Direct' ? `${message.senderName} (riêng)` : message.senderName,
+      content: message.content,
+      timestamp: new Date(message.timestamp).toLocaleTimeString([], { hour: '2-digit', minute: '2-digit' }),
+      role: message.isTeacher ? UserRole.TEACHER : UserRole.STUDENT
+    });
     onChatMessage((message) => {
-      const newMsg: Message = {
-        id: Date.now().toString(),
-        senderId: message.senderId,
-        senderName: message.senderName,
-        content: message.content,
-        timestamp: new Date(message.timestamp).toLocaleTimeString([], { hour: '2-digit', minute: '2-digit' }),
-        role: message.isTeacher ? UserRole.TEACHER : UserRole.STUDENT
-      };
-      setMessages(prev => [...prev, newMsg]);
+      setMessages(prev => [...prev, toMessage(message)]);
+    });
+    // The server's history already includes our own earlier messages
+    onChatHistory((history) => {
+      setMessages(history.map(toMessage));
     });
-  }, [onChatMessage]);
+  }, [onChatMessage, onChatHistory]);
 
   // Auto-start Mediasoup Server for Teacher role
   useEffect(() => {